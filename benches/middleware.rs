@@ -0,0 +1,98 @@
+use axum::body::Body;
+use axum::http::header::AUTHORIZATION;
+use axum::http::Request;
+use axum::routing::get;
+use axum::{middleware, Router};
+use axum_gatekeeper::authentication::{authenticate_user, UserRepository};
+use axum_gatekeeper::model::GateKeeperModel;
+use axum_gatekeeper::tokens::{AuthenticationToken, Token, TokenCache, TokenCodec};
+use axum_gatekeeper::{GateKeeper, GateKeeperResult};
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct User {
+    id: uuid::Uuid,
+}
+
+impl GateKeeperModel for User {
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn secret(&self) -> &'static str {
+        "benchmark secret"
+    }
+}
+
+struct Repository(User);
+
+#[async_trait::async_trait]
+impl UserRepository for Repository {
+    type User = User;
+
+    async fn find_by_login(&self, _login: &str) -> GateKeeperResult<Option<User>> {
+        Ok(None)
+    }
+
+    async fn find_by_id(&self, id: uuid::Uuid) -> GateKeeperResult<Option<User>> {
+        Ok((id == self.0.id).then(|| self.0.clone()))
+    }
+
+    #[cfg(feature = "verification")]
+    async fn mark_verified(&self, _user: &User) -> GateKeeperResult<()> {
+        Ok(())
+    }
+}
+
+fn app(user: User, token_cache: Option<TokenCache>) -> Router {
+    let mut gatekeeper = GateKeeper::build(Repository(user));
+
+    if let Some(token_cache) = token_cache {
+        gatekeeper = gatekeeper.token_cache(token_cache);
+    }
+
+    let gatekeeper = gatekeeper.build().expect("Couldn't build gatekeeper");
+
+    Router::new()
+        .route("/", get(|| async { "OK" }))
+        .layer(middleware::from_fn_with_state(
+            gatekeeper,
+            authenticate_user::<User>,
+        ))
+}
+
+fn bench_authenticate_user(c: &mut Criterion) {
+    std::env::set_var("AUTH_EXPIRE_SECS", "3600");
+
+    let user = User {
+        id: uuid::Uuid::new_v4(),
+    };
+    let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())
+        .expect("Couldn't create token");
+    let authorization = format!("Bearer {}", token.get_encoded());
+    let runtime = tokio::runtime::Runtime::new().expect("Couldn't start runtime");
+    let mut group = c.benchmark_group("authenticate_user");
+
+    for (name, app) in [
+        ("uncached", app(user.clone(), None)),
+        ("cached", app(user, Some(TokenCache::build().build()))),
+    ] {
+        group.bench_function(name, |b| {
+            b.to_async(&runtime).iter(|| {
+                let request = Request::get("/")
+                    .header(AUTHORIZATION, &authorization)
+                    .body(Body::empty())
+                    .expect("Couldn't build request");
+
+                app.clone().oneshot(request)
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_authenticate_user);
+criterion_main!(benches);
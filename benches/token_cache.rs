@@ -0,0 +1,38 @@
+use axum_gatekeeper::tokens::{AuthenticationToken, Claims, Token, TokenCache, TokenCodec};
+use chrono::Utc;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const SECRET: &str = "benchmark secret";
+
+fn encoded_token() -> String {
+    let now = Utc::now().timestamp() as usize;
+    let claims = Claims {
+        exp: now + 3600,
+        iat: now,
+        sub: uuid::Uuid::new_v4().to_string(),
+        auth_time: now,
+        jti: Some(uuid::Uuid::new_v4().to_string()),
+        ..Default::default()
+    };
+
+    AuthenticationToken::encode(&claims, &TokenCodec::default(), SECRET)
+        .expect("Couldn't encode token")
+}
+
+fn bench_token_cache(c: &mut Criterion) {
+    let encoded = encoded_token();
+    let codec = TokenCodec::default();
+    let cache = TokenCache::build().build();
+    let mut group = c.benchmark_group("authentication_token");
+
+    group.bench_function("decode", |b| {
+        b.iter(|| AuthenticationToken::decode(black_box(&encoded), &codec, SECRET))
+    });
+    group.bench_function("cached_decode", |b| {
+        b.iter(|| cache.decode::<AuthenticationToken>(black_box(&encoded), &codec, SECRET))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_token_cache);
+criterion_main!(benches);
@@ -0,0 +1,156 @@
+use axum_gatekeeper::mfa::MfaPendingToken;
+use axum_gatekeeper::tokens::{
+    AuthenticationToken, Claims, RefreshToken, Token, TokenCodec, TokenService, VerificationToken,
+};
+use chrono::Utc;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+
+const SECRET: &str = "benchmark secret";
+
+fn claims() -> Claims {
+    let now = Utc::now().timestamp() as usize;
+
+    Claims {
+        exp: now + 3600,
+        iat: now,
+        sub: uuid::Uuid::new_v4().to_string(),
+        auth_time: now,
+        jti: Some(uuid::Uuid::new_v4().to_string()),
+        ..Default::default()
+    }
+}
+
+fn bench_token_type<T: Token>(c: &mut Criterion, name: &str) {
+    let claims = claims();
+    let codec = TokenCodec::default();
+    let encoded = T::encode(&claims, &codec, SECRET).expect("Couldn't encode token");
+    let mut group = c.benchmark_group(name);
+
+    group.bench_function("encode", |b| {
+        b.iter(|| T::encode(black_box(&claims), &codec, SECRET))
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| T::decode(black_box(&encoded), &codec, SECRET))
+    });
+    group.bench_function("subject_from_encoded", |b| {
+        b.iter(|| TokenService::get_subject_from_encoded(black_box(&encoded)))
+    });
+    group.finish();
+}
+
+fn bench_token_types(c: &mut Criterion) {
+    bench_token_type::<AuthenticationToken>(c, "authentication_token");
+    bench_token_type::<RefreshToken>(c, "refresh_token");
+    bench_token_type::<VerificationToken>(c, "verification_token");
+    bench_token_type::<MfaPendingToken>(c, "mfa_pending_token");
+}
+
+/// Keys of every algorithm family, using the fixtures for asymmetric ones
+fn keys() -> Vec<(Algorithm, EncodingKey, DecodingKey)> {
+    let pem = |name: &str| {
+        std::fs::read(format!(
+            "{}/tests/fixtures/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .expect("Couldn't read key fixture")
+    };
+    let secret = || {
+        (
+            EncodingKey::from_secret(SECRET.as_bytes()),
+            DecodingKey::from_secret(SECRET.as_bytes()),
+        )
+    };
+    let (hs256_encoding, hs256_decoding) = secret();
+    let (hs512_encoding, hs512_decoding) = secret();
+    let rsa = || {
+        (
+            EncodingKey::from_rsa_pem(&pem("rsa_private.pem")).expect("Invalid RSA key"),
+            DecodingKey::from_rsa_pem(&pem("rsa_public.pem")).expect("Invalid RSA key"),
+        )
+    };
+    let (rs256_encoding, rs256_decoding) = rsa();
+    let (ps256_encoding, ps256_decoding) = rsa();
+
+    vec![
+        (Algorithm::HS256, hs256_encoding, hs256_decoding),
+        (Algorithm::HS512, hs512_encoding, hs512_decoding),
+        (Algorithm::RS256, rs256_encoding, rs256_decoding),
+        (Algorithm::PS256, ps256_encoding, ps256_decoding),
+        (
+            Algorithm::ES256,
+            EncodingKey::from_ec_pem(&pem("ec_private.pem")).expect("Invalid EC key"),
+            DecodingKey::from_ec_pem(&pem("ec_public.pem")).expect("Invalid EC key"),
+        ),
+        (
+            Algorithm::EdDSA,
+            EncodingKey::from_ed_pem(&pem("ed25519_private.pem")).expect("Invalid Ed25519 key"),
+            DecodingKey::from_ed_pem(&pem("ed25519_public.pem")).expect("Invalid Ed25519 key"),
+        ),
+    ]
+}
+
+fn bench_algorithms(c: &mut Criterion) {
+    let claims = claims();
+    let mut group = c.benchmark_group("algorithm");
+
+    for (algorithm, encoding_key, decoding_key) in keys() {
+        let header = Header::new(algorithm);
+        let validation = Validation::new(algorithm);
+        let encoded =
+            jsonwebtoken::encode(&header, &claims, &encoding_key).expect("Couldn't encode token");
+        let name = format!("{algorithm:?}");
+
+        group.bench_with_input(BenchmarkId::new("encode", &name), &claims, |b, claims| {
+            b.iter(|| jsonwebtoken::encode(&header, black_box(claims), &encoding_key))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", &name), &encoded, |b, encoded| {
+            b.iter(|| {
+                jsonwebtoken::decode::<Claims>(black_box(encoded), &decoding_key, &validation)
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_refresh_cookie(c: &mut Criterion) {
+    let claims = claims();
+    let encoded = RefreshToken::encode(&claims, &TokenCodec::default(), SECRET)
+        .expect("Couldn't encode token");
+    let token = RefreshToken::new(encoded, claims);
+
+    c.bench_function("refresh_token/try_as_cookie", |b| {
+        b.iter(|| {
+            black_box(&token)
+                .try_as_cookie()
+                .map(|cookie| cookie.to_string())
+        })
+    });
+}
+
+fn bench_verification_base64(c: &mut Criterion) {
+    let claims = claims();
+    let encoded = VerificationToken::encode(&claims, &TokenCodec::default(), SECRET)
+        .expect("Couldn't encode token");
+    let token = VerificationToken::new(encoded, claims);
+    let hash = token.try_as_base64().expect("Couldn't encode base64");
+    let mut group = c.benchmark_group("verification_token");
+
+    group.bench_function("try_as_base64", |b| {
+        b.iter(|| black_box(&token).try_as_base64())
+    });
+    group.bench_function("try_decode_base64", |b| {
+        b.iter(|| VerificationToken::try_decode_base64(black_box(&hash)))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_token_types,
+    bench_algorithms,
+    bench_refresh_cookie,
+    bench_verification_base64
+);
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+use crate::challenge::{BearerError, Challenge};
+use crate::error::GateKeeperError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiKeyError {
+    #[error("Malformed API key: {0}")]
+    Malformed(&'static str),
+    #[error("Unknown API key")]
+    Unknown,
+    #[error("Expired API key")]
+    Expired,
+}
+
+impl ApiKeyError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        "invalid_api_key"
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        "Invalid API key"
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+
+    /// Return the `WWW-Authenticate` challenge to respond with
+    pub fn challenge(&self) -> Challenge {
+        Challenge::new(BearerError::InvalidToken, "Invalid API key")
+    }
+}
+
+impl IntoResponse for ApiKeyError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
@@ -0,0 +1,173 @@
+use crate::api_key::ApiKeyError;
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Formatter};
+
+/// API key in the format `<prefix>_<random>_<checksum>`
+///
+/// The random part has 256 bits of entropy, the checksum consists of the first
+/// four bytes of the SHA-256 of everything before it.
+#[derive(Clone, Eq, PartialEq)]
+pub struct ApiKey {
+    key: String,
+}
+
+impl ApiKey {
+    /// Prefix used by [`ApiKey::generate`]
+    pub const DEFAULT_PREFIX: &'static str = "gk";
+    /// Length of the random part in bytes
+    pub const SECRET_LEN: usize = 32;
+
+    /// Generate a new random key using [`ApiKey::DEFAULT_PREFIX`]
+    pub fn generate() -> Self {
+        Self::generate_unchecked(Self::DEFAULT_PREFIX)
+    }
+
+    /// Generate a new random key using `prefix`, e.g. to tell keys of
+    /// different environments apart
+    ///
+    /// Fails unless `prefix` is lowercase alphanumeric, as other keys couldn't
+    /// be parsed.
+    pub fn generate_with_prefix(prefix: &str) -> Result<Self, ApiKeyError> {
+        if !is_valid_prefix(prefix) {
+            return Err(ApiKeyError::Malformed("Invalid prefix"));
+        }
+
+        Ok(Self::generate_unchecked(prefix))
+    }
+
+    fn generate_unchecked(prefix: &str) -> Self {
+        let mut secret = [0u8; Self::SECRET_LEN];
+
+        OsRng.fill_bytes(&mut secret);
+
+        let body = format!("{prefix}_{}", BASE32_NOPAD.encode(&secret).to_lowercase());
+        let checksum = checksum(&body);
+
+        Self {
+            key: format!("{body}_{checksum}"),
+        }
+    }
+
+    /// Parse `key`, verifying its format and checksum
+    pub fn parse(key: &str) -> Result<Self, ApiKeyError> {
+        let (body, actual) = key
+            .rsplit_once('_')
+            .ok_or(ApiKeyError::Malformed("Missing checksum"))?;
+        let (prefix, secret) = body
+            .split_once('_')
+            .ok_or(ApiKeyError::Malformed("Missing prefix"))?;
+
+        if !is_valid_prefix(prefix) {
+            return Err(ApiKeyError::Malformed("Invalid prefix"));
+        }
+
+        if BASE32_NOPAD
+            .decode(secret.to_uppercase().as_bytes())
+            .map_or(true, |secret| secret.len() != Self::SECRET_LEN)
+        {
+            return Err(ApiKeyError::Malformed("Invalid secret"));
+        }
+
+        if actual != checksum(body) {
+            return Err(ApiKeyError::Malformed("Invalid checksum"));
+        }
+
+        Ok(Self {
+            key: key.to_string(),
+        })
+    }
+
+    /// Return the key's prefix
+    pub fn prefix(&self) -> &str {
+        self.key.split('_').next().unwrap_or_default()
+    }
+
+    /// Return the full key, to be shown to its owner once
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+
+    /// Hash the key for storage or lookup
+    ///
+    /// Keys are random enough for a fast hash, so they can be looked up
+    /// directly instead of trying every stored hash.
+    pub fn hash(&self) -> String {
+        HEXLOWER.encode(&Sha256::digest(self.key.as_bytes()))
+    }
+}
+
+impl Debug for ApiKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("prefix", &self.prefix())
+            .field("key", &"[redacted]")
+            .finish()
+    }
+}
+
+fn is_valid_prefix(prefix: &str) -> bool {
+    !prefix.is_empty()
+        && prefix
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+}
+
+fn checksum(body: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(body.as_bytes())[..4])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiKey;
+
+    #[test]
+    fn test_generate() -> anyhow::Result<()> {
+        let key = ApiKey::generate();
+
+        assert!(key.as_str().starts_with("gk_"));
+        assert_eq!(key.as_str().len(), 3 + 52 + 1 + 8);
+        assert_eq!(ApiKey::parse(key.as_str())?, key);
+        assert_ne!(key, ApiKey::generate());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_with_prefix() -> anyhow::Result<()> {
+        let key = ApiKey::generate_with_prefix("ci")?;
+
+        assert_eq!(ApiKey::parse(key.as_str())?.prefix(), "ci");
+
+        for prefix in ["", "my_app", "CI", "ci-1"] {
+            assert!(ApiKey::generate_with_prefix(prefix).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        let key = ApiKey::generate();
+        let mut typo = key.as_str().to_string();
+        let replacement = if typo.as_bytes()[5] == b'a' { "b" } else { "a" };
+
+        typo.replace_range(5..6, replacement);
+
+        assert!(ApiKey::parse(&typo).is_err());
+        assert!(ApiKey::parse(&key.as_str().replace("gk_", "GK_")).is_err());
+        assert!(ApiKey::parse("gk_abc_12345678").is_err());
+        assert!(ApiKey::parse("eyJhbGciOiJIUzUxMiJ9.e30.sig").is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let key = ApiKey::generate();
+
+        assert_eq!(key.hash(), key.clone().hash());
+        assert_eq!(key.hash().len(), 64);
+        assert_ne!(key.hash(), ApiKey::generate().hash());
+        assert!(!format!("{key:?}").contains(key.as_str()));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::api_key::{ApiKey, ApiKeyError};
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::authentication::AuthenticatedSubject;
+use crate::model::GateKeeperModel;
+use crate::{GateKeeper, GateKeeperResult};
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+
+/// Header API keys are usually sent in
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Middleware authenticating requests using an API key only
+///
+/// Use it for routes JWTs shouldn't be accepted on, otherwise
+/// [`authenticate_user`](crate::authentication::authenticate_user) accepts
+/// both. Inserts the [`AuthenticatedSubject`] into the request's extensions.
+pub async fn authenticate_api_key<U: GateKeeperModel + 'static>(
+    State(gatekeeper): State<GateKeeper<U>>,
+    mut req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
+    tracing::debug!("Using middleware::authenticate_api_key");
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let audit = AuditContext::from_request(&req);
+    let key = api_key_from_headers(req.headers())
+        .ok_or(ApiKeyError::Malformed("Missing key"))
+        .and_then(|key| key);
+    let result = match key {
+        Ok(key) => resolve_api_key(&gatekeeper, &key).await,
+        Err(e) => Err(e.into()),
+    };
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decision("authenticate_api_key", started, &result);
+    let subject = audit
+        .record_failure(AuditEventKind::Authentication, result)
+        .await?;
+
+    req.extensions_mut().insert(subject);
+
+    Ok(next.run(req).await)
+}
+
+/// Return the API key sent as `X-Api-Key` header or as bearer token
+///
+/// Bearer tokens only count as API keys if they have the right format, so
+/// JWTs can be sent the same way.
+pub(crate) fn api_key_from_headers(headers: &HeaderMap) -> Option<Result<ApiKey, ApiKeyError>> {
+    if let Some(value) = headers.get(API_KEY_HEADER) {
+        let key = value
+            .to_str()
+            .map_err(|_| ApiKeyError::Malformed("Invalid header value"))
+            .and_then(|value| ApiKey::parse(value.trim()));
+
+        return Some(key);
+    }
+
+    crate::authentication::bearer_token(headers)
+        .and_then(|token| ApiKey::parse(token).ok())
+        .map(Ok)
+}
+
+/// Look up `key` in the gatekeeper's [`ApiKeyStore`](crate::api_key::ApiKeyStore)
+pub(crate) async fn resolve_api_key<U: GateKeeperModel + 'static>(
+    gatekeeper: &GateKeeper<U>,
+    key: &ApiKey,
+) -> GateKeeperResult<AuthenticatedSubject> {
+    let record = gatekeeper
+        .api_key_store()
+        .find_by_hash(&key.hash())
+        .await?
+        .ok_or(ApiKeyError::Unknown)?;
+
+    if record.is_expired() {
+        return Err(ApiKeyError::Expired.into());
+    }
+
+    Ok(AuthenticatedSubject::from_api_key(&record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::authenticate_api_key;
+    use crate::api_key::{ApiKey, ApiKeyRecord, MemoryApiKeyStore};
+    use crate::authentication::AuthenticationToken;
+    use crate::authentication::{authenticate_user, AuthenticatedSubject, AuthenticationMethod};
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Token, TokenCodec};
+    use axum::body::Body;
+    use axum::http::header::AUTHORIZATION;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    struct Setup {
+        user: TestUser,
+        key: ApiKey,
+        store: Arc<MemoryApiKeyStore>,
+    }
+
+    fn setup() -> Setup {
+        init_env();
+
+        let user = TestUser::new("ci", "");
+        let key = ApiKey::generate();
+        let store = Arc::new(MemoryApiKeyStore::new());
+
+        store.insert(key.hash(), ApiKeyRecord::new(user.id, "ci"));
+
+        Setup { user, key, store }
+    }
+
+    fn app(setup: &Setup, api_key_only: bool) -> anyhow::Result<Router> {
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([setup.user.clone()]))
+            .api_key_store(setup.store.clone())
+            .build()?;
+        let handler = get(|subject: AuthenticatedSubject| async move {
+            format!("{} {:?}", subject.id, subject.method)
+        });
+        let router = Router::new().route("/", handler);
+
+        Ok(if api_key_only {
+            router.layer(middleware::from_fn_with_state(
+                gatekeeper,
+                authenticate_api_key::<TestUser>,
+            ))
+        } else {
+            router.layer(middleware::from_fn_with_state(
+                gatekeeper,
+                authenticate_user::<TestUser>,
+            ))
+        })
+    }
+
+    async fn call(app: Router, header: (&str, String)) -> anyhow::Result<(StatusCode, String)> {
+        let request = Request::get("/")
+            .header(header.0, header.1)
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok((status, String::from_utf8(body.to_vec())?))
+    }
+
+    #[tokio::test]
+    async fn test_api_key_header() -> anyhow::Result<()> {
+        let setup = setup();
+        let expected = format!("{} {:?}", setup.user.id, AuthenticationMethod::ApiKey);
+
+        for api_key_only in [true, false] {
+            let header = ("x-api-key", setup.key.as_str().to_string());
+            let (status, body) = call(app(&setup, api_key_only)?, header).await?;
+
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body, expected);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_key_bearer() -> anyhow::Result<()> {
+        let setup = setup();
+        let header = (
+            AUTHORIZATION.as_str(),
+            format!("Bearer {}", setup.key.as_str()),
+        );
+        let (status, body) = call(app(&setup, false)?, header).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.ends_with("ApiKey"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_jwt_alongside_api_keys() -> anyhow::Result<()> {
+        let setup = setup();
+        let token = AuthenticationToken::try_new_for_model(&setup.user, &TokenCodec::default())?;
+        let header = (
+            AUTHORIZATION.as_str(),
+            format!("Bearer {}", token.get_encoded()),
+        );
+        let (status, body) = call(app(&setup, false)?, header.clone()).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.ends_with("Jwt"));
+
+        let (status, _) = call(app(&setup, true)?, header).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected_keys() -> anyhow::Result<()> {
+        let setup = setup();
+        let unknown = ApiKey::generate();
+        let expired = ApiKey::generate();
+        let mut record = ApiKeyRecord::new(setup.user.id, "expired");
+
+        record.expires_at = Some(chrono::Utc::now() - chrono::Duration::seconds(1));
+        setup.store.insert(expired.hash(), record);
+
+        for key in [unknown.as_str(), expired.as_str(), "gk_foo_bar"] {
+            let (status, body) = call(app(&setup, false)?, ("x-api-key", key.to_string())).await?;
+
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body)?["code"],
+                "invalid_api_key"
+            );
+        }
+
+        setup.store.remove(&setup.key.hash());
+
+        let header = ("x-api-key", setup.key.as_str().to_string());
+        let (status, _) = call(app(&setup, false)?, header).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+}
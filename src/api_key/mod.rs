@@ -0,0 +1,19 @@
+//! Module containing API key authentication for machine clients
+//!
+//! Keys look like `gk_<random>_<checksum>`, so they are easy to recognize,
+//! e.g. by secret scanners, and typos are detected without a store lookup.
+//! Only their hash is stored, see [`ApiKeyStore`]. The `authenticate_user`
+//! middleware accepts keys sent as `X-Api-Key` header or bearer token and
+//! inserts the same [`AuthenticatedSubject`](crate::authentication::AuthenticatedSubject)
+//! as for JWTs.
+//!
+//! Only available on feature `api-key`
+mod error;
+mod key;
+mod middleware;
+mod store;
+
+pub use error::*;
+pub use key::*;
+pub use middleware::*;
+pub use store::*;
@@ -0,0 +1,119 @@
+use crate::GateKeeperResult;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Stored data of an API key, looked up by the key's hash
+#[derive(Debug, Clone)]
+pub struct ApiKeyRecord {
+    /// Id of the user or client the key belongs to
+    pub subject: uuid::Uuid,
+    /// Name describing the key's purpose, e.g. `ci-deploy`
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Point in time after which the key is rejected, if any
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyRecord {
+    /// Create a record for a key owned by `subject` which never expires
+    pub fn new(subject: uuid::Uuid, name: &str) -> Self {
+        Self {
+            subject,
+            name: name.to_string(),
+            created_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    /// Check whether the key has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+/// Storage for API key records, keyed by [`ApiKey::hash`](crate::api_key::ApiKey::hash)
+#[async_trait::async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    /// Return the record of the key with `hash`, if it exists
+    async fn find_by_hash(&self, hash: &str) -> GateKeeperResult<Option<ApiKeyRecord>>;
+}
+
+#[async_trait::async_trait]
+impl<T: ApiKeyStore + ?Sized> ApiKeyStore for std::sync::Arc<T> {
+    async fn find_by_hash(&self, hash: &str) -> GateKeeperResult<Option<ApiKeyRecord>> {
+        self.as_ref().find_by_hash(hash).await
+    }
+}
+
+/// Process local [`ApiKeyStore`]
+#[derive(Debug, Default)]
+pub struct MemoryApiKeyStore {
+    keys: Mutex<HashMap<String, ApiKeyRecord>>,
+}
+
+impl MemoryApiKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the record of the key with `hash`
+    pub fn insert(&self, hash: String, record: ApiKeyRecord) {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+
+        keys.insert(hash, record);
+    }
+
+    /// Remove the key with `hash`, revoking it
+    pub fn remove(&self, hash: &str) -> Option<ApiKeyRecord> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+
+        keys.remove(hash)
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for MemoryApiKeyStore {
+    async fn find_by_hash(&self, hash: &str) -> GateKeeperResult<Option<ApiKeyRecord>> {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(keys.get(hash).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiKeyRecord, ApiKeyStore, MemoryApiKeyStore};
+    use crate::api_key::ApiKey;
+
+    #[tokio::test]
+    async fn test_memory_store() -> anyhow::Result<()> {
+        let store = MemoryApiKeyStore::new();
+        let key = ApiKey::generate();
+        let subject = uuid::Uuid::new_v4();
+
+        store.insert(key.hash(), ApiKeyRecord::new(subject, "ci"));
+
+        let record = store.find_by_hash(&key.hash()).await?;
+
+        assert_eq!(record.map(|record| record.subject), Some(subject));
+
+        store.remove(&key.hash());
+
+        assert!(store.find_by_hash(&key.hash()).await?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let mut record = ApiKeyRecord::new(uuid::Uuid::new_v4(), "ci");
+
+        assert!(!record.is_expired());
+
+        record.expires_at = Some(chrono::Utc::now() - chrono::Duration::seconds(1));
+
+        assert!(record.is_expired());
+    }
+}
@@ -0,0 +1,397 @@
+//! Module containing the audit event stream of authentication and
+//! authorization decisions
+//!
+//! The crate's middleware and handlers record an [`AuditEvent`] for every
+//! login, refresh, rejected credential, authorization denial and redeemed
+//! verification token. Events go to the [`AuditSink`] set using the
+//! [`record_audit_events`] middleware, by default [`TracingAuditSink`].
+//!
+//! Only available on feature `authentication`
+use crate::authentication::AuthenticatedSubject;
+use crate::error::GateKeeperError;
+use crate::GateKeeperResult;
+use axum::extract::{ConnectInfo, FromRequestParts, Request, State};
+use axum::http::header::USER_AGENT;
+use axum::http::request::Parts;
+use axum::http::{Extensions, HeaderMap};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+/// Decision an audit event describes
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    /// Login using credentials or an identity provider
+    Login,
+    /// Second login step using a TOTP or recovery code
+    MfaVerification,
+    /// Exchange of a refresh token for a new token pair
+    Refresh,
+    /// Authentication of a request by middleware
+    Authentication,
+    /// Check of a request's authentication level or scopes
+    Authorization,
+    /// CSRF check of a request
+    CsrfCheck,
+    /// Redemption of a verification token
+    Verification,
+    /// Token issued by the OAuth2 token endpoint
+    TokenIssued,
+    /// OAuth2 token introspection
+    TokenIntrospection,
+    /// OAuth2 token revocation
+    TokenRevocation,
+}
+
+/// Outcome of the decision an audit event describes
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// Authentication or authorization decision
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Time of the decision as unix timestamp
+    pub timestamp: usize,
+    pub kind: AuditEventKind,
+    pub outcome: AuditOutcome,
+    /// ID of the user or client, if known
+    pub subject: Option<uuid::Uuid>,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    /// Error code of failures, see [`GateKeeperError::code`]
+    pub reason: Option<String>,
+    /// `jti` of the token involved, if any
+    pub jti: Option<String>,
+}
+
+impl AuditEvent {
+    /// Create successful event of `kind` without request details
+    pub fn new(kind: AuditEventKind) -> Self {
+        Self {
+            timestamp: Utc::now().timestamp() as usize,
+            kind,
+            outcome: AuditOutcome::Success,
+            subject: None,
+            ip: None,
+            user_agent: None,
+            reason: None,
+            jti: None,
+        }
+    }
+
+    /// Set field `subject`
+    pub fn subject(mut self, subject: uuid::Uuid) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+
+    /// Set field `jti`
+    pub fn jti(mut self, jti: Option<&str>) -> Self {
+        self.jti = jti.map(str::to_string);
+        self
+    }
+
+    /// Set fields `subject` and `jti` from an authenticated subject
+    pub fn authenticated(self, subject: &AuthenticatedSubject) -> Self {
+        let jti = subject
+            .claims
+            .as_ref()
+            .and_then(|claims| claims.jti.as_deref());
+
+        self.subject(subject.id).jti(jti)
+    }
+
+    /// Set field `reason`
+    pub fn reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    /// Mark the event as failed because of `error`
+    pub fn failure(mut self, error: &GateKeeperError) -> Self {
+        self.outcome = AuditOutcome::Failure;
+        self.reason = Some(error.code().to_string());
+        self
+    }
+}
+
+/// Client details of a request, added to its audit events, and the sink they
+/// are recorded to
+///
+/// The IP is read from `ConnectInfo<SocketAddr>` like
+/// [`ClientIp`](crate::rate_limit::ClientIp), the sink from the extension set
+/// by [`record_audit_events`].
+#[derive(Clone, Default)]
+pub struct AuditContext {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    sink: Option<Arc<dyn AuditSink>>,
+}
+
+impl std::fmt::Debug for AuditContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditContext")
+            .field("ip", &self.ip)
+            .field("user_agent", &self.user_agent)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuditContext {
+    /// Read the client details from a request's `headers` and `extensions`
+    pub fn from_parts(headers: &HeaderMap, extensions: &Extensions) -> Self {
+        Self {
+            ip: extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+            user_agent: headers
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            sink: extensions.get::<Arc<dyn AuditSink>>().cloned(),
+        }
+    }
+
+    /// Read the client details from `req`
+    pub fn from_request(req: &Request) -> Self {
+        Self::from_parts(req.headers(), req.extensions())
+    }
+
+    /// Create successful event of `kind` for this request
+    pub fn event(&self, kind: AuditEventKind) -> AuditEvent {
+        AuditEvent {
+            ip: self.ip,
+            user_agent: self.user_agent.clone(),
+            ..AuditEvent::new(kind)
+        }
+    }
+
+    /// Pass `event` to the request's [`AuditSink`]
+    pub async fn record(&self, event: AuditEvent) {
+        match &self.sink {
+            Some(sink) => sink.record(&event).await,
+            None => TracingAuditSink.record(&event).await,
+        }
+    }
+
+    /// Record a failed event of `kind` if `result` is an error, passing it on
+    pub async fn record_failure<T>(
+        &self,
+        kind: AuditEventKind,
+        result: GateKeeperResult<T>,
+    ) -> GateKeeperResult<T> {
+        if let Err(e) = &result {
+            self.record(self.event(kind).failure(e)).await;
+        }
+
+        result
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuditContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(&parts.headers, &parts.extensions))
+    }
+}
+
+/// Receiver of audit events, e.g. writing them to a SIEM
+///
+/// Recording must not fail requests, so sinks handle their errors themselves.
+#[async_trait::async_trait]
+pub trait AuditSink: Send + Sync {
+    /// Record `event`
+    async fn record(&self, event: &AuditEvent);
+}
+
+#[async_trait::async_trait]
+impl<T: AuditSink + ?Sized> AuditSink for Arc<T> {
+    async fn record(&self, event: &AuditEvent) {
+        self.as_ref().record(event).await
+    }
+}
+
+/// Middleware recording the audit events of the wrapped routes to the sink
+/// in its state
+///
+/// Add it as outermost layer, so all middleware and handlers see the sink. Use
+/// it with `axum::middleware::from_fn_with_state(sink, record_audit_events)`
+/// where `sink` is an `Arc<dyn AuditSink>`.
+pub async fn record_audit_events(
+    State(sink): State<Arc<dyn AuditSink>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    req.extensions_mut().insert(sink);
+    next.run(req).await
+}
+
+/// Sink emitting events as `tracing` events with target `audit`
+///
+/// Successes are emitted at level `INFO`, failures at level `WARN`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingAuditSink;
+
+#[async_trait::async_trait]
+impl AuditSink for TracingAuditSink {
+    async fn record(&self, event: &AuditEvent) {
+        macro_rules! emit {
+            ($level:ident) => {
+                tracing::$level!(
+                    target: "audit",
+                    kind = ?event.kind,
+                    outcome = ?event.outcome,
+                    subject = ?event.subject,
+                    ip = ?event.ip,
+                    user_agent = event.user_agent.as_deref(),
+                    reason = event.reason.as_deref(),
+                    jti = event.jti.as_deref(),
+                    "Audit event"
+                )
+            };
+        }
+
+        match event.outcome {
+            AuditOutcome::Success => emit!(info),
+            AuditOutcome::Failure => emit!(warn),
+        }
+    }
+}
+
+/// Sink writing every event as a line of JSON to a writer, e.g. a file
+pub struct JsonLinesAuditSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLinesAuditSink<W> {
+    /// Create sink writing to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Return the writer
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait::async_trait]
+impl<W: Write + Send> AuditSink for JsonLinesAuditSink<W> {
+    async fn record(&self, event: &AuditEvent) {
+        let mut line = match serde_json::to_vec(event) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!("Error serializing audit event: {e:?}");
+                return;
+            }
+        };
+
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Err(e) = writer.write_all(&line).and_then(|_| writer.flush()) {
+            tracing::error!("Error writing audit event: {e:?}");
+        }
+    }
+}
+
+/// Sink keeping events in memory, e.g. for tests
+#[derive(Default)]
+pub struct MemoryAuditSink {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl MemoryAuditSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the events recorded so far
+    pub fn events(&self) -> Vec<AuditEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditSink for MemoryAuditSink {
+    async fn record(&self, event: &AuditEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AuditContext, AuditEvent, AuditEventKind, AuditOutcome, AuditSink, JsonLinesAuditSink,
+    };
+    use crate::authentication::AuthenticationError;
+    use axum::body::Body;
+    use axum::extract::{ConnectInfo, Request};
+    use axum::http::header::USER_AGENT;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn test_json_lines() -> anyhow::Result<()> {
+        let mut request = Request::get("/")
+            .header(USER_AGENT, "curl/8.0")
+            .body(Body::empty())?;
+
+        request
+            .extensions_mut()
+            .insert(ConnectInfo("192.0.2.1:4711".parse::<SocketAddr>()?));
+
+        let context = AuditContext::from_request(&request);
+        let subject = uuid::Uuid::new_v4();
+        let sink = JsonLinesAuditSink::new(Vec::new());
+        let failure = context
+            .event(AuditEventKind::Login)
+            .failure(&AuthenticationError::InvalidCredentials.into());
+
+        sink.record(
+            &context
+                .event(AuditEventKind::Refresh)
+                .subject(subject)
+                .jti(Some("abc")),
+        )
+        .await;
+        sink.record(&failure).await;
+
+        let output = String::from_utf8(sink.into_inner())?;
+        let events = output
+            .lines()
+            .map(serde_json::from_str::<AuditEvent>)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, AuditEventKind::Refresh);
+        assert_eq!(events[0].subject, Some(subject));
+        assert_eq!(events[0].ip, Some("192.0.2.1".parse()?));
+        assert_eq!(events[0].user_agent.as_deref(), Some("curl/8.0"));
+        assert_eq!(events[0].jti.as_deref(), Some("abc"));
+        assert_eq!(events[1], failure);
+        assert_eq!(events[1].outcome, AuditOutcome::Failure);
+        assert_eq!(events[1].reason.as_deref(), Some("invalid_credentials"));
+
+        Ok(())
+    }
+}
@@ -1,14 +1,99 @@
+use crate::challenge::{BearerError, Challenge};
+use crate::error::GateKeeperError;
+use crate::tokens::Acr;
+use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 
 #[derive(thiserror::Error, Debug)]
-pub enum AuthenticationError {}
+pub enum AuthenticationError {
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+    #[error("Malformed credentials: {0}")]
+    MalformedCredentials(String),
+    #[error("Request isn't authenticated")]
+    Unauthenticated,
+    #[error("Step-up authentication required (max_age: {max_age:?}, acr: {acr:?})")]
+    StepUpRequired {
+        max_age: Option<u64>,
+        acr: Option<Acr>,
+    },
+    #[error("Scope {scope:?} required")]
+    InsufficientScope { scope: String },
+}
+
+impl AuthenticationError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthenticationError::InvalidCredentials => "invalid_credentials",
+            AuthenticationError::MalformedCredentials(_) => "malformed_credentials",
+            AuthenticationError::Unauthenticated => "unauthenticated",
+            AuthenticationError::StepUpRequired { .. } => "insufficient_user_authentication",
+            AuthenticationError::InsufficientScope { .. } => "insufficient_scope",
+        }
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            AuthenticationError::InvalidCredentials => "Invalid credentials",
+            AuthenticationError::MalformedCredentials(_) => "Malformed credentials",
+            AuthenticationError::Unauthenticated => "Unauthenticated",
+            AuthenticationError::StepUpRequired { .. } => "Step-up authentication required",
+            AuthenticationError::InsufficientScope { .. } => "Insufficient scope",
+        }
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuthenticationError::MalformedCredentials(_) => StatusCode::BAD_REQUEST,
+            AuthenticationError::InsufficientScope { .. } => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    /// Return the `WWW-Authenticate` challenge to respond with, if any
+    pub fn challenge(&self) -> Option<Challenge> {
+        match self {
+            AuthenticationError::InvalidCredentials => None,
+            AuthenticationError::MalformedCredentials(_) => Some(Challenge::new(
+                BearerError::InvalidRequest,
+                "Malformed credentials",
+            )),
+            AuthenticationError::Unauthenticated => Some(Challenge::default()),
+            AuthenticationError::StepUpRequired { max_age, acr } => {
+                // Challenge as defined by RFC 9470
+                let description = match max_age {
+                    Some(_) => "A more recent authentication is required",
+                    None => "A different authentication level is required",
+                };
+                let mut challenge =
+                    Challenge::new(BearerError::InsufficientUserAuthentication, description);
+
+                if let Some(max_age) = max_age {
+                    challenge = challenge.with_token_param("max_age", max_age);
+                }
+
+                if let Some(acr) = acr {
+                    challenge = challenge.with_param("acr_values", acr.as_str());
+                }
+
+                Some(challenge)
+            }
+            AuthenticationError::InsufficientScope { scope } => Some(
+                Challenge::new(
+                    BearerError::InsufficientScope,
+                    format!("Scope {scope} required"),
+                )
+                .with_param("scope", scope),
+            ),
+        }
+    }
+}
 
 impl IntoResponse for AuthenticationError {
     fn into_response(self) -> Response {
-        let response = match self {
-            _ => "",
-        };
-
-        response.into_response()
+        GateKeeperError::from(self).into_response()
     }
 }
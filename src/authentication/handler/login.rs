@@ -0,0 +1,331 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::authentication::handler::{json_or_form, token_response};
+use crate::authentication::AuthenticationError;
+use crate::error::GateKeeperError;
+use crate::password::PasswordModel;
+use crate::rate_limit::{ClientIp, RateLimitKey};
+use crate::tokens::Acr;
+use crate::{GateKeeper, GateKeeperResult};
+use axum::extract::{FromRequest, Request, State};
+use axum::response::Response;
+use chrono::Utc;
+use serde::Deserialize;
+use std::fmt::{Debug, Formatter};
+
+/// Login name and password sent as JSON or URL encoded form
+#[derive(Deserialize)]
+pub struct Credentials {
+    pub login: String,
+    pub password: String,
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("login", &self.login)
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
+impl<S: Send + Sync> FromRequest<S> for Credentials {
+    type Rejection = GateKeeperError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(json_or_form(req, state)
+            .await
+            .map_err(AuthenticationError::MalformedCredentials)?)
+    }
+}
+
+/// Handler verifying the user's credentials
+///
+/// Responds with the `AuthenticationToken` as JSON body and sets the
+/// `RefreshToken` cookie. Unknown login names and wrong passwords both result
+/// in [`AuthenticationError::InvalidCredentials`] after the same amount of
+/// hashing work, so responses don't reveal which users exist.
+///
+/// With feature `mfa`, users having a TOTP secret get an `MfaPendingToken`
+/// instead, which has to be exchanged using the `verify_mfa` handler.
+pub async fn login<U: PasswordModel + 'static>(
+    State(gatekeeper): State<GateKeeper<U>>,
+    client_ip: ClientIp,
+    audit: AuditContext,
+    credentials: Credentials,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::login");
+
+    let keys = client_ip.keys_with([RateLimitKey::login(&credentials.login)]);
+    let result = gatekeeper
+        .attempt(&keys, verify_credentials(&gatekeeper, credentials))
+        .await;
+    let (user, rehash) = audit.record_failure(AuditEventKind::Login, result).await?;
+
+    if let Some(hash) = rehash {
+        gatekeeper.users().update_password_hash(&user, hash).await?;
+    }
+
+    #[cfg(feature = "mfa")]
+    if user.totp_secret().is_some() {
+        let response = crate::mfa::mfa_challenge(&user, gatekeeper.token_codec())?;
+
+        audit
+            .record(
+                audit
+                    .event(AuditEventKind::Login)
+                    .subject(user.id())
+                    .reason("mfa_required"),
+            )
+            .await;
+
+        return Ok(response);
+    }
+
+    let response = token_response(
+        &user,
+        Utc::now().timestamp() as usize,
+        Some(Acr::Password),
+        gatekeeper.token_codec(),
+    )?;
+
+    audit
+        .record(audit.event(AuditEventKind::Login).subject(user.id()))
+        .await;
+
+    Ok(response)
+}
+
+async fn verify_credentials<U: PasswordModel + 'static>(
+    gatekeeper: &GateKeeper<U>,
+    credentials: Credentials,
+) -> GateKeeperResult<(U, Option<String>)> {
+    let hasher = gatekeeper.hasher().clone();
+    let Some(user) = gatekeeper.users().find_by_login(&credentials.login).await? else {
+        let dummy_hash = gatekeeper.dummy_hash().clone();
+        let _ =
+            tokio::task::spawn_blocking(move || hasher.verify(&credentials.password, &dummy_hash))
+                .await?;
+
+        return Err(AuthenticationError::InvalidCredentials.into());
+    };
+
+    let (user, result) = tokio::task::spawn_blocking(move || {
+        let result = crate::password::check_password(&hasher, &user, &credentials.password);
+
+        (user, result)
+    })
+    .await?;
+
+    Ok((user, result?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::login;
+    use crate::audit::{
+        record_audit_events, AuditEventKind, AuditOutcome, AuditSink, MemoryAuditSink,
+    };
+    use crate::password::PasswordHasher;
+    use crate::rate_limit::RateLimiter;
+    use crate::test_support::{init_env, test_hasher, TestRepository, TestUser};
+    use crate::GateKeeper;
+    use axum::body::Body;
+    use axum::http::header::{CONTENT_TYPE, SET_COOKIE};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::{middleware, Router};
+    use http_body_util::BodyExt;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn app_with(
+        repository: Arc<TestRepository>,
+        hasher: PasswordHasher,
+        rate_limiter: Option<RateLimiter>,
+    ) -> anyhow::Result<Router> {
+        init_env();
+
+        let mut gatekeeper = GateKeeper::build(repository).hasher(hasher);
+
+        if let Some(rate_limiter) = rate_limiter {
+            gatekeeper = gatekeeper.rate_limiter(rate_limiter);
+        }
+
+        Ok(Router::new()
+            .route("/login", post(login::<TestUser>))
+            .with_state(gatekeeper.build()?))
+    }
+
+    fn app() -> anyhow::Result<Router> {
+        let hasher = test_hasher();
+        let user = TestUser::new("alice", &hasher.hash("correct horse")?);
+
+        app_with(Arc::new(TestRepository::with_users([user])), hasher, None)
+    }
+
+    fn request(content_type: &str, body: &str) -> anyhow::Result<Request<Body>> {
+        Ok(Request::post("/login")
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))?)
+    }
+
+    #[tokio::test]
+    async fn test_login_json() -> anyhow::Result<()> {
+        let response = app()?
+            .oneshot(request(
+                "application/json",
+                r#"{"login":"alice","password":"correct horse"}"#,
+            )?)
+            .await?;
+        let status = response.status();
+        let cookie = response.headers().get(SET_COOKIE).cloned();
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(cookie.unwrap().to_str()?.starts_with("refresh_token="));
+        assert!(body["encoded"].is_string());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_form() -> anyhow::Result<()> {
+        let response = app()?
+            .oneshot(request(
+                "application/x-www-form-urlencoded",
+                "login=alice&password=correct+horse",
+            )?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_failures_are_uniform() -> anyhow::Result<()> {
+        let wrong_password = app()?
+            .oneshot(request(
+                "application/json",
+                r#"{"login":"alice","password":"battery staple"}"#,
+            )?)
+            .await?;
+        let unknown_user = app()?
+            .oneshot(request(
+                "application/json",
+                r#"{"login":"bob","password":"correct horse"}"#,
+            )?)
+            .await?;
+
+        assert_eq!(wrong_password.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(unknown_user.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            wrong_password.into_body().collect().await?.to_bytes(),
+            unknown_user.into_body().collect().await?.to_bytes()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_audit() -> anyhow::Result<()> {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let app = app()?.layer(middleware::from_fn_with_state(
+            sink.clone() as Arc<dyn AuditSink>,
+            record_audit_events,
+        ));
+
+        for password in ["battery staple", "correct horse"] {
+            let request = request(
+                "application/json",
+                &format!(r#"{{"login":"alice","password":"{password}"}}"#),
+            )?;
+
+            app.clone().oneshot(request).await?;
+        }
+
+        let events = sink.events();
+
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|event| event.kind == AuditEventKind::Login));
+        assert_eq!(events[0].outcome, AuditOutcome::Failure);
+        assert_eq!(events[0].subject, None);
+        assert_eq!(events[0].reason.as_deref(), Some("invalid_credentials"));
+        assert_eq!(events[1].outcome, AuditOutcome::Success);
+        assert!(events[1].subject.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_malformed() -> anyhow::Result<()> {
+        let response = app()?
+            .oneshot(request("application/json", r#"{"login":"alice"}"#)?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_upgrades_hash() -> anyhow::Result<()> {
+        let old = test_hasher();
+        let user = TestUser::new("alice", &old.hash("correct horse")?);
+        let id = user.id;
+        let repository = Arc::new(TestRepository::with_users([user]));
+        let new = PasswordHasher::build()
+            .memory_cost(2048)
+            .time_cost(1)
+            .build()?;
+        let response = app_with(repository.clone(), new.clone(), None)?
+            .oneshot(request(
+                "application/json",
+                r#"{"login":"alice","password":"correct horse"}"#,
+            )?)
+            .await?;
+        let stored = repository.get(id).unwrap().password_hash;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!new.needs_rehash(&stored)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_rate_limited() -> anyhow::Result<()> {
+        let hasher = test_hasher();
+        let user = TestUser::new("alice", &hasher.hash("correct horse")?);
+        let app = app_with(
+            Arc::new(TestRepository::with_users([user])),
+            hasher,
+            Some(RateLimiter::build().free_attempts(2).build()),
+        )?;
+
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(request(
+                    "application/json",
+                    r#"{"login":"ALICE","password":"battery staple"}"#,
+                )?)
+                .await?;
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let response = app
+            .oneshot(request(
+                "application/json",
+                r#"{"login":"alice","password":"correct horse"}"#,
+            )?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+#[cfg(feature = "password")]
+mod login;
+mod refresh;
+
+#[cfg(feature = "password")]
+pub use login::*;
+pub use refresh::*;
+
+use crate::authentication::{AuthenticationToken, RefreshToken};
+use crate::model::GateKeeperModel;
+use crate::tokens::{Acr, Token, TokenCodec};
+use crate::GateKeeperResult;
+use axum::http::header::SET_COOKIE;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+#[cfg(feature = "password")]
+use axum::extract::{FromRequest, Request};
+#[cfg(feature = "password")]
+use axum::http::header::CONTENT_TYPE;
+#[cfg(feature = "password")]
+use axum::Form;
+#[cfg(feature = "password")]
+use serde::de::DeserializeOwned;
+
+/// Issue a token pair for `user` and respond with the `AuthenticationToken` as
+/// JSON body and the `RefreshToken` as cookie
+///
+/// `auth_time` and `acr` describe the login the tokens originate from, so they
+/// are carried over unchanged when refreshing.
+pub(crate) fn token_response(
+    user: &impl GateKeeperModel,
+    auth_time: usize,
+    acr: Option<Acr>,
+    codec: &TokenCodec,
+) -> GateKeeperResult<Response> {
+    let authentication_token =
+        AuthenticationToken::try_new_for_authentication(user, auth_time, acr, codec)?;
+    let refresh_token = RefreshToken::try_new_for_authentication(user, auth_time, acr, codec)?;
+    let cookie = refresh_token.try_as_cookie()?;
+
+    Ok((
+        [(SET_COOKIE, cookie.to_string())],
+        Json(authentication_token),
+    )
+        .into_response())
+}
+
+/// Extract `T` from a JSON or, for any other content type, URL encoded form body
+///
+/// Fails with the rejection's message, so callers can wrap it in their error.
+#[cfg(feature = "password")]
+pub(crate) async fn json_or_form<T, S>(req: Request, state: &S) -> Result<T, String>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    let is_json = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    if is_json {
+        Json::<T>::from_request(req, state)
+            .await
+            .map(|Json(value)| value)
+            .map_err(|e| e.body_text())
+    } else {
+        Form::<T>::from_request(req, state)
+            .await
+            .map(|Form(value)| value)
+            .map_err(|e| e.body_text())
+    }
+}
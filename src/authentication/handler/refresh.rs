@@ -0,0 +1,281 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::authentication::handler::token_response;
+use crate::authentication::RefreshToken;
+use crate::error::TokenError;
+use crate::model::GateKeeperModel;
+use crate::rate_limit::{ClientIp, RateLimitKey};
+use crate::tokens::Token;
+use crate::{GateKeeper, GateKeeperResult};
+use axum::extract::State;
+use axum::http::header::COOKIE;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use cookie::Cookie;
+
+/// Handler exchanging the `RefreshToken` cookie for a new token pair
+///
+/// Responds like [`login`](super::login): the new `AuthenticationToken` as JSON
+/// body and the rotated `RefreshToken` as cookie.
+pub async fn refresh<U: GateKeeperModel + 'static>(
+    State(gatekeeper): State<GateKeeper<U>>,
+    client_ip: ClientIp,
+    audit: AuditContext,
+    headers: HeaderMap,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::refresh");
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let result = rotate(&gatekeeper, &client_ip, &headers)
+        .await
+        .and_then(|(user, token)| {
+            let claims = token.get_claims();
+            let response = token_response(
+                &user,
+                claims.auth_time,
+                claims.acr,
+                gatekeeper.token_codec(),
+            )?;
+
+            Ok((response, user.id(), claims.jti.clone()))
+        });
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_refresh(started, &result);
+
+    let (response, subject, jti) = audit
+        .record_failure(AuditEventKind::Refresh, result)
+        .await?;
+
+    audit
+        .record(
+            audit
+                .event(AuditEventKind::Refresh)
+                .subject(subject)
+                .jti(jti.as_deref()),
+        )
+        .await;
+
+    Ok(response)
+}
+
+/// Decode the refresh token sent with `headers` and revoke it, so it can't be
+/// used again
+///
+/// Decoding is only limited per client IP. The token's subject is limited once
+/// its signature was verified, so forged tokens can't lock out their subject.
+async fn rotate<U: GateKeeperModel + 'static>(
+    gatekeeper: &GateKeeper<U>,
+    client_ip: &ClientIp,
+    headers: &HeaderMap,
+) -> GateKeeperResult<(U, RefreshToken)> {
+    let encoded = refresh_token_from_headers(headers).ok_or(TokenError::MissingTokenString)?;
+    let (user, token) = gatekeeper
+        .attempt(
+            &client_ip.keys_with([]),
+            gatekeeper.decode_for_user::<RefreshToken>(&encoded),
+        )
+        .await?;
+    let keys = client_ip.keys_with([RateLimitKey::Subject(user.id())]);
+
+    gatekeeper
+        .attempt(&keys, gatekeeper.revoke_token(&token))
+        .await?;
+
+    Ok((user, token))
+}
+
+/// Read the encoded refresh token from the request's cookies
+fn refresh_token_from_headers(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(Cookie::split_parse)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == RefreshToken::COOKIE_NAME)
+        .map(|cookie| cookie.value().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::refresh;
+    use crate::authentication::{AuthenticationToken, RefreshToken};
+    use crate::rate_limit::RateLimiter;
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Acr, Token, TokenCodec};
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::header::{COOKIE, RETRY_AFTER, SET_COOKIE};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    fn app(user: TestUser, rate_limiter: Option<RateLimiter>) -> anyhow::Result<Router> {
+        let mut gatekeeper = test_gatekeeper(TestRepository::with_users([user]));
+
+        if let Some(rate_limiter) = rate_limiter {
+            gatekeeper = gatekeeper.rate_limiter(rate_limiter);
+        }
+
+        Ok(Router::new()
+            .route("/refresh", post(refresh::<TestUser>))
+            .with_state(gatekeeper.build()?))
+    }
+
+    fn request(cookie: &str) -> anyhow::Result<Request<Body>> {
+        request_from(cookie, "192.0.2.1:4711")
+    }
+
+    fn request_from(cookie: &str, addr: &str) -> anyhow::Result<Request<Body>> {
+        let mut request = Request::post("/refresh")
+            .header(COOKIE, format!("foo=bar; refresh_token={cookie}"))
+            .body(Body::empty())?;
+
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(addr.parse::<SocketAddr>()?));
+
+        Ok(request)
+    }
+
+    #[tokio::test]
+    async fn test_refresh() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = RefreshToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let response = app(user, None)?
+            .oneshot(request(token.get_encoded())?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[SET_COOKIE]
+            .to_str()?
+            .starts_with("refresh_token="));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_revokes_presented_token() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = RefreshToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let app = app(user, None)?;
+        let rotated = app.clone().oneshot(request(token.get_encoded())?).await?;
+        let cookie = cookie::Cookie::parse(rotated.headers()[SET_COOKIE].to_str()?.to_string())?;
+        let reused = app.clone().oneshot(request(token.get_encoded())?).await?;
+        let refreshed = app.oneshot(request(cookie.value())?).await?;
+
+        assert_eq!(rotated.status(), StatusCode::OK);
+        assert_eq!(reused.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(refreshed.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_concurrent_reuse() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = RefreshToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let app = app(user, None)?;
+        let (first, second) = tokio::join!(
+            app.clone().oneshot(request(token.get_encoded())?),
+            app.oneshot(request(token.get_encoded())?)
+        );
+        let mut statuses = [first?.status(), second?.status()];
+
+        statuses.sort();
+
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::UNAUTHORIZED]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_keeps_authentication() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = RefreshToken::try_new_for_authentication(
+            &user,
+            1000,
+            Some(Acr::Mfa),
+            &TokenCodec::default(),
+        )?;
+        let response = app(user, None)?
+            .oneshot(request(token.get_encoded())?)
+            .await?;
+        let body = response.into_body().collect().await?.to_bytes();
+        let encoded = serde_json::from_slice::<serde_json::Value>(&body)?["encoded"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let claims = AuthenticationToken::decode(&encoded, &TokenCodec::default(), "test")?
+            .get_claims()
+            .clone();
+
+        assert_eq!(claims.auth_time, 1000);
+        assert_eq!(claims.acr, Some(Acr::Mfa));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_missing_cookie() -> anyhow::Result<()> {
+        init_env();
+
+        let response = app(TestUser::new("alice", ""), None)?
+            .oneshot(Request::post("/refresh").body(Body::empty())?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rate_limited() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = RefreshToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let forged = format!("{}x", token.get_encoded());
+        let app = app(user, Some(RateLimiter::build().free_attempts(1).build()))?;
+        let failed = app.clone().oneshot(request(&forged)?).await?;
+        let limited = app.oneshot(request(token.get_encoded())?).await?;
+
+        assert_eq!(failed.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(limited.headers().contains_key(RETRY_AFTER));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forged_tokens_dont_lock_out_subject() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = RefreshToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let forged = format!("{}x", token.get_encoded());
+        let app = app(user, Some(RateLimiter::build().free_attempts(1).build()))?;
+        let failed = app
+            .clone()
+            .oneshot(request_from(&forged, "203.0.113.7:4711")?)
+            .await?;
+        let refreshed = app.oneshot(request(token.get_encoded())?).await?;
+
+        assert_eq!(failed.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(refreshed.status(), StatusCode::OK);
+
+        Ok(())
+    }
+}
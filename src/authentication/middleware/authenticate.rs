@@ -1,8 +1,375 @@
-use crate::GateKeeperResult;
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::authentication::{AuthenticatedSubject, AuthenticationToken};
+use crate::error::TokenError;
+use crate::model::GateKeeperModel;
+use crate::tokens::Token;
+use crate::{GateKeeper, GateKeeperResult};
+use axum::extract::State;
+use axum::http::header::AUTHORIZATION;
+use axum::http::HeaderMap;
 use axum::{body::Body, extract::Request, middleware::Next, response::Response};
 
-pub async fn authenticate_user(req: Request, next: Next) -> GateKeeperResult<Response<Body>> {
+/// Middleware authenticating requests using a bearer `AuthenticationToken`
+///
+/// With feature `api-key`, API keys sent as `X-Api-Key` header or bearer token
+/// are accepted as well. With feature `external-jwt` and a verifier set on the
+/// gatekeeper, so are bearer tokens having an `iss` claim. Inserts the
+/// [`AuthenticatedSubject`] into the request's extensions. Use it with
+/// `axum::middleware::from_fn_with_state(gatekeeper, authenticate_user::<User>)`.
+pub async fn authenticate_user<U: GateKeeperModel + 'static>(
+    State(gatekeeper): State<GateKeeper<U>>,
+    mut req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
     tracing::debug!("Using middleware::authenticate_user");
 
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let audit = AuditContext::from_request(&req);
+    let result = authenticate_request(&gatekeeper, &mut req).await;
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decision("authenticate_user", started, &result);
+
+    audit
+        .record_failure(AuditEventKind::Authentication, result)
+        .await?;
+
     Ok(next.run(req).await)
 }
+
+/// Authenticate `req`, inserting the subject into its extensions
+async fn authenticate_request<U: GateKeeperModel + 'static>(
+    gatekeeper: &GateKeeper<U>,
+    req: &mut Request,
+) -> GateKeeperResult<()> {
+    #[cfg(feature = "api-key")]
+    if let Some(key) = crate::api_key::api_key_from_headers(req.headers()) {
+        let subject = crate::api_key::resolve_api_key(gatekeeper, &key?).await?;
+
+        req.extensions_mut().insert(subject);
+
+        return Ok(());
+    }
+
+    let encoded = bearer_token(req.headers()).ok_or(TokenError::MissingTokenString)?;
+
+    #[cfg(feature = "external-jwt")]
+    if let Some(verifier) = gatekeeper.external_jwt() {
+        if crate::external_jwt::ExternalJwtVerifier::issuer_of(encoded).is_some() {
+            let (subject, claims) = verifier.verify(encoded).await?;
+
+            req.extensions_mut().insert(subject);
+            req.extensions_mut().insert(claims);
+
+            return Ok(());
+        }
+    }
+    let (user, token) = gatekeeper
+        .decode_for_user::<AuthenticationToken>(encoded)
+        .await?;
+
+    req.extensions_mut()
+        .insert(AuthenticatedSubject::from_claims(
+            user.id(),
+            token.into_claims(),
+        ));
+
+    Ok(())
+}
+
+/// Return the credentials of an `Authorization: Bearer` header
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let (scheme, credentials) = headers.get(AUTHORIZATION)?.to_str().ok()?.split_once(' ')?;
+
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| credentials.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::authenticate_user;
+    use crate::authentication::{AuthenticatedSubject, AuthenticationToken, RefreshToken};
+    use crate::model::GateKeeperModel;
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Claims, Token, TokenCodec};
+    use axum::body::Body;
+    use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn app(user: TestUser) -> anyhow::Result<Router> {
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user])).build()?;
+
+        Ok(Router::new()
+            .route(
+                "/",
+                get(|subject: AuthenticatedSubject| async move { subject.id.to_string() }),
+            )
+            .layer(middleware::from_fn_with_state(
+                gatekeeper,
+                authenticate_user::<TestUser>,
+            )))
+    }
+
+    fn request(authorization: &str) -> anyhow::Result<Request<Body>> {
+        Ok(Request::get("/")
+            .header(AUTHORIZATION, authorization)
+            .body(Body::empty())?)
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_user() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let id = user.id;
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let response = app(user)?
+            .oneshot(request(&format!("bearer {}", token.get_encoded()))?)
+            .await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, id.to_string());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_token() -> anyhow::Result<()> {
+        init_env();
+
+        let response = app(TestUser::new("alice", ""))?
+            .oneshot(Request::get("/").body(Body::empty())?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="Restricted""#
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_challenges_invalid_tokens() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let now = chrono::Utc::now().timestamp() as usize;
+        let claims = Claims {
+            exp: now - 600,
+            iat: now - 1200,
+            sub: user.id.to_string(),
+            ..Default::default()
+        };
+        let expired = AuthenticationToken::encode(&claims, &TokenCodec::default(), user.secret())?;
+        let claims = Claims {
+            exp: now + 600,
+            ..claims
+        };
+        let forged = AuthenticationToken::encode(&claims, &TokenCodec::default(), "forged")?;
+        let app = app(user)?;
+
+        for (encoded, description) in [
+            (expired, "Token has expired"),
+            (forged, "Invalid token signature"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(request(&format!("Bearer {encoded}"))?)
+                .await?;
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                response.headers()[WWW_AUTHENTICATE],
+                format!(
+                    r#"Bearer realm="Restricted", error="invalid_token", error_description="{description}""#
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_other_token_types() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = RefreshToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let response = app(user)?
+            .oneshot(request(&format!("Bearer {}", token.get_encoded()))?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "verification")]
+    #[tokio::test]
+    async fn test_rejects_verification_tokens() -> anyhow::Result<()> {
+        use crate::verification::VerificationToken;
+
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = VerificationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let response = app(user)?
+            .oneshot(request(&format!("Bearer {}", token.get_encoded()))?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_revoked_token() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let claims = token.get_claims();
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user])).build()?;
+
+        gatekeeper
+            .revocation_store()
+            .revoke(claims.jti.as_deref().unwrap_or_default(), claims.exp)
+            .await?;
+
+        let app =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    gatekeeper,
+                    authenticate_user::<TestUser>,
+                ));
+        let response = app
+            .oneshot(request(&format!("Bearer {}", token.get_encoded()))?)
+            .await?;
+        let status = response.status();
+        let challenge = response.headers()[WWW_AUTHENTICATE].clone();
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            challenge,
+            r#"Bearer realm="Restricted", error="invalid_token", error_description="Token has been revoked""#
+        );
+        assert_eq!(body["code"], "token_revoked");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_uses_gatekeeper_codec() -> anyhow::Result<()> {
+        use crate::tokens::TokenKeys;
+        use jsonwebtoken::Algorithm;
+
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let codec = TokenCodec::build()
+            .algorithm(Algorithm::HS256)
+            .keys(TokenKeys::from_secret(b"gatekeeper secret"))
+            .build()?;
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user.clone()]))
+            .token_codec(codec)
+            .build()?;
+        let token = AuthenticationToken::try_new_for_model(&user, gatekeeper.token_codec())?;
+        let other = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let app =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    gatekeeper,
+                    authenticate_user::<TestUser>,
+                ));
+
+        for (token, status) in [(token, StatusCode::OK), (other, StatusCode::UNAUTHORIZED)] {
+            let response = app
+                .clone()
+                .oneshot(request(&format!("Bearer {}", token.get_encoded()))?)
+                .await?;
+
+            assert_eq!(response.status(), status);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "external-jwt")]
+    #[tokio::test]
+    async fn test_external_jwt() -> anyhow::Result<()> {
+        use crate::external_jwt::{ExternalClaims, ExternalJwtVerifier, TrustedIssuer};
+        use crate::jwks::KeySet;
+        use crate::test_support::{test_jwks, test_rsa_key};
+
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let id = user.id;
+        let own_token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let issuer = TrustedIssuer::build(
+            "https://corp.example.com",
+            "api",
+            KeySet::from_jwks(test_jwks()),
+        )
+        .build();
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user]))
+            .external_jwt(ExternalJwtVerifier::new().trust(issuer))
+            .build()?;
+        let app = Router::new()
+            .route(
+                "/",
+                get(
+                    |subject: AuthenticatedSubject, claims: Option<ExternalClaims>| async move {
+                        format!("{} {:?}", subject.id, claims.map(|claims| claims.issuer))
+                    },
+                ),
+            )
+            .layer(middleware::from_fn_with_state(
+                gatekeeper,
+                authenticate_user::<TestUser>,
+            ));
+        let now = chrono::Utc::now().timestamp();
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+
+        header.kid = Some("test-key".to_string());
+
+        let claims = serde_json::json!({
+            "iss": "https://corp.example.com",
+            "aud": "api",
+            "sub": id,
+            "exp": now + 60,
+        });
+        let external = jsonwebtoken::encode(&header, &claims, &test_rsa_key())?;
+        let response = app
+            .clone()
+            .oneshot(request(&format!("Bearer {external}"))?)
+            .await?;
+        let body = response.into_body().collect().await?.to_bytes();
+
+        assert_eq!(body, format!("{id} Some(\"https://corp.example.com\")"));
+
+        // The crate's own tokens are still accepted
+        let response = app
+            .oneshot(request(&format!("Bearer {}", own_token.get_encoded()))?)
+            .await?;
+        let body = response.into_body().collect().await?.to_bytes();
+
+        assert_eq!(body, format!("{id} None"));
+
+        Ok(())
+    }
+}
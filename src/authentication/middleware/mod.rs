@@ -1,3 +1,7 @@
 mod authenticate;
+mod scope;
+mod step_up;
 
 pub use authenticate::*;
+pub use scope::*;
+pub use step_up::*;
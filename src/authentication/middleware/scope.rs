@@ -0,0 +1,103 @@
+use super::step_up::deny;
+use crate::audit::AuditContext;
+use crate::authentication::{AuthenticatedSubject, AuthenticationError};
+use crate::GateKeeperResult;
+use axum::extract::State;
+use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+
+/// Guard requiring the subject's token to be granted the given OAuth2 scope
+///
+/// Use it with `axum::middleware::from_fn_with_state(RequireScope("orders:read"), require_scope)`
+/// behind the authentication middleware. Rejected requests get a 403 with
+/// `error="insufficient_scope"` (RFC 6750). Scopes are read from the crate's
+/// own `AuthenticationToken`s, so subjects authenticated otherwise, e.g. by
+/// API key, are rejected.
+#[derive(Debug, Clone, Copy)]
+pub struct RequireScope(pub &'static str);
+
+/// Middleware enforcing [`RequireScope`]
+pub async fn require_scope(
+    State(RequireScope(scope)): State<RequireScope>,
+    subject: AuthenticatedSubject,
+    req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
+    tracing::debug!("Using middleware::require_scope");
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let result = match subject.has_scope(scope) {
+        true => Ok(()),
+        false => Err(AuthenticationError::InsufficientScope {
+            scope: scope.to_string(),
+        }
+        .into()),
+    };
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decision("require_scope", started, &result);
+
+    if let Err(error) = result {
+        let audit = AuditContext::from_request(&req);
+
+        return Err(deny(audit, &subject, error).await);
+    }
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authentication::AuthenticationMethod;
+    use crate::tokens::Claims;
+    use axum::http::header::WWW_AUTHENTICATE;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use tower::ServiceExt;
+
+    fn request(scope: Option<&str>) -> anyhow::Result<Request> {
+        let mut request = Request::get("/").body(Body::empty())?;
+        let claims = Claims {
+            scope: scope.map(str::to_string),
+            ..Default::default()
+        };
+
+        request.extensions_mut().insert(AuthenticatedSubject {
+            id: uuid::Uuid::new_v4(),
+            method: AuthenticationMethod::Jwt,
+            auth_time: 0,
+            acr: None,
+            claims: Some(claims),
+        });
+
+        Ok(request)
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", get(|| async { "OK" }))
+            .layer(middleware::from_fn_with_state(
+                RequireScope("orders:read"),
+                require_scope,
+            ))
+    }
+
+    #[tokio::test]
+    async fn test_require_scope() -> anyhow::Result<()> {
+        let granted = app().oneshot(request(Some("profile orders:read"))?).await?;
+        let other = app().oneshot(request(Some("orders:readwrite"))?).await?;
+        let missing = app().oneshot(request(None)?).await?;
+
+        assert_eq!(granted.status(), StatusCode::OK);
+        assert_eq!(other.status(), StatusCode::FORBIDDEN);
+        assert_eq!(missing.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            missing.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="Restricted", error="insufficient_scope", error_description="Scope orders:read required", scope="orders:read""#
+        );
+
+        Ok(())
+    }
+}
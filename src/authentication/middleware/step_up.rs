@@ -0,0 +1,244 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::authentication::{AuthenticatedSubject, AuthenticationError};
+use crate::error::GateKeeperError;
+use crate::tokens::Acr;
+use crate::GateKeeperResult;
+use axum::extract::State;
+use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+use chrono::Utc;
+use std::time::Duration;
+
+/// Guard requiring the subject to have authenticated within the given duration
+///
+/// Use it with `axum::middleware::from_fn_with_state(RequireRecentAuth(max_age), require_recent_auth)`
+/// behind the authentication middleware. Rejected requests get a 401 with
+/// `error="insufficient_user_authentication"` (RFC 9470), telling clients to
+/// send the user through login again.
+#[derive(Debug, Clone, Copy)]
+pub struct RequireRecentAuth(pub Duration);
+
+/// Guard requiring the subject to have authenticated using at least the given
+/// authentication context class
+///
+/// Use it with `axum::middleware::from_fn_with_state(RequireAcr(Acr::Mfa), require_acr)`.
+#[derive(Debug, Clone, Copy)]
+pub struct RequireAcr(pub Acr);
+
+/// Middleware enforcing [`RequireRecentAuth`]
+pub async fn require_recent_auth(
+    State(RequireRecentAuth(max_age)): State<RequireRecentAuth>,
+    subject: AuthenticatedSubject,
+    req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
+    tracing::debug!("Using middleware::require_recent_auth");
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let age = (Utc::now().timestamp() as u64).saturating_sub(subject.auth_time as u64);
+    let result = match age > max_age.as_secs() {
+        true => Err(AuthenticationError::StepUpRequired {
+            max_age: Some(max_age.as_secs()),
+            acr: None,
+        }
+        .into()),
+        false => Ok(()),
+    };
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decision("require_recent_auth", started, &result);
+
+    if let Err(error) = result {
+        let audit = AuditContext::from_request(&req);
+
+        return Err(deny(audit, &subject, error).await);
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Middleware enforcing [`RequireAcr`]
+pub async fn require_acr(
+    State(RequireAcr(acr)): State<RequireAcr>,
+    subject: AuthenticatedSubject,
+    req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
+    tracing::debug!("Using middleware::require_acr");
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let result = match subject.acr.is_none_or(|actual| actual < acr) {
+        true => Err(AuthenticationError::StepUpRequired {
+            max_age: None,
+            acr: Some(acr),
+        }
+        .into()),
+        false => Ok(()),
+    };
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decision("require_acr", started, &result);
+
+    if let Err(error) = result {
+        let audit = AuditContext::from_request(&req);
+
+        return Err(deny(audit, &subject, error).await);
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Record the denial of a request because of `error` and return the error
+pub(crate) async fn deny(
+    audit: AuditContext,
+    subject: &AuthenticatedSubject,
+    error: GateKeeperError,
+) -> GateKeeperError {
+    audit
+        .record(
+            audit
+                .event(AuditEventKind::Authorization)
+                .authenticated(subject)
+                .failure(&error),
+        )
+        .await;
+
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::{record_audit_events, AuditOutcome, AuditSink, MemoryAuditSink};
+    use crate::authentication::AuthenticationMethod;
+    use crate::tokens::Claims;
+    use axum::http::header::WWW_AUTHENTICATE;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn request(auth_time: usize, acr: Option<Acr>) -> anyhow::Result<Request> {
+        let mut request = Request::get("/").body(Body::empty())?;
+
+        request.extensions_mut().insert(AuthenticatedSubject {
+            id: uuid::Uuid::new_v4(),
+            method: AuthenticationMethod::Jwt,
+            auth_time,
+            acr,
+            claims: None,
+        });
+
+        Ok(request)
+    }
+
+    fn recent_auth_app() -> Router {
+        Router::new()
+            .route("/", get(|| async { "OK" }))
+            .layer(middleware::from_fn_with_state(
+                RequireRecentAuth(Duration::from_secs(300)),
+                require_recent_auth,
+            ))
+    }
+
+    fn acr_app() -> Router {
+        Router::new()
+            .route("/", get(|| async { "OK" }))
+            .layer(middleware::from_fn_with_state(
+                RequireAcr(Acr::Mfa),
+                require_acr,
+            ))
+    }
+
+    #[tokio::test]
+    async fn test_require_recent_auth() -> anyhow::Result<()> {
+        let now = Utc::now().timestamp() as usize;
+        let recent = recent_auth_app().oneshot(request(now - 10, None)?).await?;
+        let stale = recent_auth_app().oneshot(request(now - 600, None)?).await?;
+
+        assert_eq!(recent.status(), StatusCode::OK);
+        assert_eq!(stale.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            stale.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="Restricted", error="insufficient_user_authentication", error_description="A more recent authentication is required", max_age=300"#
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_require_acr() -> anyhow::Result<()> {
+        let now = Utc::now().timestamp() as usize;
+        let mfa = acr_app().oneshot(request(now, Some(Acr::Mfa))?).await?;
+        let password = acr_app()
+            .oneshot(request(now, Some(Acr::Password))?)
+            .await?;
+        let unknown = acr_app().oneshot(request(now, None)?).await?;
+
+        assert_eq!(mfa.status(), StatusCode::OK);
+        assert_eq!(password.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            password.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="Restricted", error="insufficient_user_authentication", error_description="A different authentication level is required", acr_values="mfa""#
+        );
+        assert_eq!(unknown.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_audits_denials() -> anyhow::Result<()> {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let now = Utc::now().timestamp() as usize;
+        let mut request = request(now, Some(Acr::Password))?;
+        let subject = request
+            .extensions_mut()
+            .get_mut::<AuthenticatedSubject>()
+            .ok_or_else(|| anyhow::anyhow!("Missing subject"))?;
+        let id = subject.id;
+
+        subject.claims = Some(Claims {
+            jti: Some("test_audits_denials".to_string()),
+            ..Default::default()
+        });
+        acr_app()
+            .layer(middleware::from_fn_with_state(
+                sink.clone() as Arc<dyn AuditSink>,
+                record_audit_events,
+            ))
+            .oneshot(request)
+            .await?;
+
+        let events = sink.events();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, AuditEventKind::Authorization);
+        assert_eq!(events[0].outcome, AuditOutcome::Failure);
+        assert_eq!(events[0].subject, Some(id));
+        assert_eq!(events[0].jti.as_deref(), Some("test_audits_denials"));
+        assert_eq!(
+            events[0].reason.as_deref(),
+            Some("insufficient_user_authentication")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated() -> anyhow::Result<()> {
+        let response = acr_app()
+            .oneshot(Request::get("/").body(Body::empty())?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        // Challenged without error, as no credentials were sent
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Bearer realm="Restricted""#
+        );
+
+        Ok(())
+    }
+}
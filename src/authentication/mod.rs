@@ -2,10 +2,18 @@
 //!
 //! Only available on feature `authentication`
 mod error;
+mod handler;
 mod middleware;
+mod repository;
+mod revocation;
+mod subject;
 mod token;
 
 pub use error::*;
+pub use handler::*;
 pub use middleware::*;
+pub use repository::*;
+pub use revocation::*;
+pub use subject::*;
 pub use token::AuthenticationToken;
 pub use token::RefreshToken;
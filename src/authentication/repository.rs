@@ -0,0 +1,52 @@
+use crate::model::GateKeeperModel;
+use crate::GateKeeperResult;
+
+/// Storage backend used to look up users during authentication
+#[async_trait::async_trait]
+pub trait UserRepository: Send + Sync {
+    type User: GateKeeperModel;
+
+    /// Find a user by the name they log in with, e.g. username or email
+    async fn find_by_login(&self, login: &str) -> GateKeeperResult<Option<Self::User>>;
+
+    /// Find a user by ID
+    async fn find_by_id(&self, id: uuid::Uuid) -> GateKeeperResult<Option<Self::User>>;
+
+    /// Persist an upgraded password hash for `user`
+    ///
+    /// Called after a successful login if the stored hash uses outdated
+    /// parameters. Does nothing by default.
+    #[cfg(feature = "password")]
+    async fn update_password_hash(&self, user: &Self::User, hash: String) -> GateKeeperResult<()> {
+        let _ = (user, hash);
+
+        Ok(())
+    }
+
+    /// Mark `user` as verified after a `VerificationToken` was redeemed
+    #[cfg(feature = "verification")]
+    async fn mark_verified(&self, user: &Self::User) -> GateKeeperResult<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: UserRepository + ?Sized> UserRepository for std::sync::Arc<T> {
+    type User = T::User;
+
+    async fn find_by_login(&self, login: &str) -> GateKeeperResult<Option<Self::User>> {
+        self.as_ref().find_by_login(login).await
+    }
+
+    async fn find_by_id(&self, id: uuid::Uuid) -> GateKeeperResult<Option<Self::User>> {
+        self.as_ref().find_by_id(id).await
+    }
+
+    #[cfg(feature = "password")]
+    async fn update_password_hash(&self, user: &Self::User, hash: String) -> GateKeeperResult<()> {
+        self.as_ref().update_password_hash(user, hash).await
+    }
+
+    #[cfg(feature = "verification")]
+    async fn mark_verified(&self, user: &Self::User) -> GateKeeperResult<()> {
+        self.as_ref().mark_verified(user).await
+    }
+}
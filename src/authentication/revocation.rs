@@ -0,0 +1,108 @@
+use crate::GateKeeperResult;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Storage for revoked tokens, keyed by their `jti` claim
+///
+/// Revoked tokens are rejected by [`GateKeeper::decode_for_user`](crate::GateKeeper::decode_for_user),
+/// so by the authentication middleware and all handlers accepting tokens.
+#[async_trait::async_trait]
+pub trait RevocationStore: Send + Sync {
+    /// Mark the token with `jti` as revoked until unix timestamp `exp`, after
+    /// which it's rejected anyway
+    ///
+    /// [`GateKeeper::revoke`](crate::GateKeeper::revoke) passes the token's
+    /// `exp` plus the [`TokenCodec`](crate::tokens::TokenCodec)'s leeway.
+    ///
+    /// Returns whether the token was revoked already. Checking and marking has
+    /// to happen atomically, so single-use tokens can't be redeemed twice by
+    /// concurrent requests.
+    async fn revoke(&self, jti: &str, exp: usize) -> GateKeeperResult<bool>;
+
+    /// Check whether the token with `jti` was revoked
+    async fn is_revoked(&self, jti: &str) -> GateKeeperResult<bool>;
+}
+
+#[async_trait::async_trait]
+impl<T: RevocationStore + ?Sized> RevocationStore for std::sync::Arc<T> {
+    async fn revoke(&self, jti: &str, exp: usize) -> GateKeeperResult<bool> {
+        self.as_ref().revoke(jti, exp).await
+    }
+
+    async fn is_revoked(&self, jti: &str) -> GateKeeperResult<bool> {
+        self.as_ref().is_revoked(jti).await
+    }
+}
+
+/// Process local [`RevocationStore`]
+#[derive(Debug, Default)]
+pub struct MemoryRevocationStore {
+    revoked: Mutex<HashMap<String, usize>>,
+}
+
+impl MemoryRevocationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl RevocationStore for MemoryRevocationStore {
+    async fn revoke(&self, jti: &str, exp: usize) -> GateKeeperResult<bool> {
+        let now = Utc::now().timestamp() as usize;
+        let mut revoked = self.revoked.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Expired tokens don't need to be remembered
+        revoked.retain(|_, exp| *exp >= now);
+
+        Ok(revoked.insert(jti.to_string(), exp).is_some())
+    }
+
+    async fn is_revoked(&self, jti: &str) -> GateKeeperResult<bool> {
+        let revoked = self.revoked.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(revoked.contains_key(jti))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryRevocationStore, RevocationStore};
+    use crate::test_support::{test_gatekeeper, TestRepository};
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_memory_store() -> anyhow::Result<()> {
+        let store = MemoryRevocationStore::new();
+        let now = Utc::now().timestamp() as usize;
+
+        store.revoke("expired", now - 10).await?;
+
+        assert!(!store.revoke("active", now + 60).await?);
+        assert!(store.revoke("active", now + 60).await?);
+        assert!(store.is_revoked("active").await?);
+        assert!(!store.is_revoked("other").await?);
+
+        // Expired entries are pruned on the next revocation
+        store.revoke("other", now + 60).await?;
+
+        assert!(!store.is_revoked("expired").await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revoked_during_leeway() -> anyhow::Result<()> {
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([])).build()?;
+        let now = Utc::now().timestamp() as usize;
+
+        // Expired tokens are still accepted for the codec's leeway
+        gatekeeper.revoke("expired", now - 10).await?;
+        gatekeeper.revoke("other", now + 60).await?;
+
+        assert!(gatekeeper.revocation_store().is_revoked("expired").await?);
+
+        Ok(())
+    }
+}
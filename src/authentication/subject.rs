@@ -0,0 +1,112 @@
+use crate::authentication::AuthenticationError;
+use crate::error::GateKeeperError;
+use crate::tokens::{Acr, Claims};
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
+use axum::http::request::Parts;
+use std::convert::Infallible;
+
+/// How a request's subject was authenticated
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AuthenticationMethod {
+    /// `AuthenticationToken` sent as bearer token
+    Jwt,
+    /// API key sent as `X-Api-Key` header or bearer token
+    #[cfg(feature = "api-key")]
+    ApiKey,
+    /// Credentials sent using an `Authorization: Basic` header
+    #[cfg(feature = "basic")]
+    Basic,
+    /// Access token of a [`TrustedIssuer`](crate::external_jwt::TrustedIssuer)
+    /// sent as bearer token
+    #[cfg(feature = "external-jwt")]
+    ExternalJwt,
+    /// Session id sent as cookie
+    #[cfg(feature = "session")]
+    Session,
+}
+
+/// The authenticated user or client of a request
+///
+/// Inserted into the request's extensions by the authentication middleware,
+/// so handlers can extract it regardless of the method used.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedSubject {
+    pub id: uuid::Uuid,
+    pub method: AuthenticationMethod,
+    /// Time the subject actually authenticated as unix timestamp
+    pub auth_time: usize,
+    pub acr: Option<Acr>,
+    /// Claims of the token used, if any
+    pub claims: Option<Claims>,
+}
+
+impl AuthenticatedSubject {
+    /// Create subject from the claims of a verified `AuthenticationToken`
+    pub fn from_claims(id: uuid::Uuid, claims: Claims) -> Self {
+        Self {
+            id,
+            method: AuthenticationMethod::Jwt,
+            auth_time: claims.auth_time,
+            acr: claims.acr,
+            claims: Some(claims),
+        }
+    }
+
+    /// Create subject from the record of a valid API key
+    ///
+    /// API keys don't carry an authentication context, so `auth_time` is the
+    /// key's creation time and step-up guards requiring an `acr` reject them.
+    #[cfg(feature = "api-key")]
+    pub fn from_api_key(record: &crate::api_key::ApiKeyRecord) -> Self {
+        Self {
+            id: record.subject,
+            method: AuthenticationMethod::ApiKey,
+            auth_time: record.created_at.timestamp() as usize,
+            acr: None,
+            claims: None,
+        }
+    }
+
+    /// Create subject from the record of a valid server-side session
+    #[cfg(feature = "session")]
+    pub fn from_session(record: &crate::session::SessionRecord) -> Self {
+        Self {
+            id: record.subject,
+            method: AuthenticationMethod::Session,
+            auth_time: record.auth_time,
+            acr: record.acr,
+            claims: None,
+        }
+    }
+
+    /// Test if the token used was granted OAuth2 `scope`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.claims
+            .as_ref()
+            .and_then(|claims| claims.scope.as_deref())
+            .is_some_and(|scopes| scopes.split(' ').any(|granted| granted == scope))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthenticatedSubject {
+    type Rejection = GateKeeperError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or_else(|| AuthenticationError::Unauthenticated.into())
+    }
+}
+
+impl<S: Send + Sync> OptionalFromRequestParts<S> for AuthenticatedSubject {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned())
+    }
+}
@@ -9,6 +9,8 @@ pub struct AuthenticationToken {
 
 impl Token for AuthenticationToken {
     const EXPIRE_SECS_VAR: &'static str = "AUTH_EXPIRE_SECS";
+    const TOKEN_TYPE: &'static str = "at+jwt";
+    const NAME: &'static str = "authentication";
 
     fn new(encoded: String, claims: Claims) -> Self
     where
@@ -21,7 +23,11 @@ impl Token for AuthenticationToken {
         &self.claims
     }
 
-    fn get_encoded(&self) -> &String {
+    fn into_claims(self) -> Claims {
+        self.claims
+    }
+
+    fn get_encoded(&self) -> &str {
         &self.encoded
     }
 }
@@ -29,7 +35,7 @@ impl Token for AuthenticationToken {
 #[cfg(test)]
 mod tests {
     use super::AuthenticationToken;
-    use crate::tokens::{Claims, Token};
+    use crate::tokens::{Claims, Token, TokenCodec};
     use jsonwebtoken::{Algorithm, EncodingKey, Header};
 
     #[test]
@@ -39,17 +45,20 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = AuthenticationToken::encode(&claims, secret.to_string())?;
+        let encoded = AuthenticationToken::encode(&claims, &TokenCodec::default(), secret)?;
         let raw_encoded = move || {
             let mut header = Header::new(Algorithm::HS512);
             let claims = Claims {
                 exp: 1,
                 iat: 1,
                 sub: uuid.to_string(),
+                ..Default::default()
             };
 
+            header.typ = Some("at+jwt".to_string());
             header.kid = Some(claims.sub.clone());
 
             let key = EncodingKey::from_secret(secret.as_bytes());
@@ -70,10 +79,11 @@ mod tests {
             exp: now + 1000,
             iat: now,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = AuthenticationToken::encode(&claims, secret.to_string())?;
-        let decoded = AuthenticationToken::decode(encoded.clone(), &secret.to_string())?;
+        let encoded = AuthenticationToken::encode(&claims, &TokenCodec::default(), secret)?;
+        let decoded = AuthenticationToken::decode(&encoded, &TokenCodec::default(), secret)?;
 
         assert_eq!(encoded, decoded.encoded);
 
@@ -87,10 +97,11 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = AuthenticationToken::encode(&claims, secret.to_string())?;
-        let decoded = AuthenticationToken::decode(encoded.clone(), &secret.to_string());
+        let encoded = AuthenticationToken::encode(&claims, &TokenCodec::default(), secret)?;
+        let decoded = AuthenticationToken::decode(&encoded, &TokenCodec::default(), secret);
 
         // Expired, so is_err must be true
         assert!(decoded.is_err());
@@ -105,9 +116,10 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = AuthenticationToken::encode(&claims, secret.to_string())?;
+        let encoded = AuthenticationToken::encode(&claims, &TokenCodec::default(), secret)?;
         let token = AuthenticationToken::new(encoded, claims);
 
         assert!(token.is_expired());
@@ -122,9 +134,10 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = AuthenticationToken::encode(&claims, secret.to_string())?;
+        let encoded = AuthenticationToken::encode(&claims, &TokenCodec::default(), secret)?;
         let token = AuthenticationToken::new(encoded, claims);
         let headers = token.get_headers()?;
 
@@ -12,8 +12,11 @@ pub struct RefreshToken {
 }
 
 impl RefreshToken {
-    pub fn try_as_cookie(&self) -> GateKeeperResult<Cookie> {
-        Ok(Cookie::build(("refresh_token", &self.encoded))
+    /// Name of the cookie the refresh token is stored in
+    pub const COOKIE_NAME: &'static str = "refresh_token";
+
+    pub fn try_as_cookie(&self) -> GateKeeperResult<Cookie<'_>> {
+        Ok(Cookie::build((Self::COOKIE_NAME, &self.encoded))
             .path("/")
             .expires(
                 OffsetDateTime::from_unix_timestamp(self.claims.exp as i64)
@@ -28,6 +31,8 @@ impl RefreshToken {
 
 impl Token for RefreshToken {
     const EXPIRE_SECS_VAR: &'static str = "REFRESH_EXPIRE_SECS";
+    const TOKEN_TYPE: &'static str = "refresh+jwt";
+    const NAME: &'static str = "refresh";
 
     fn new(encoded: String, claims: Claims) -> Self
     where
@@ -40,7 +45,11 @@ impl Token for RefreshToken {
         &self.claims
     }
 
-    fn get_encoded(&self) -> &String {
+    fn into_claims(self) -> Claims {
+        self.claims
+    }
+
+    fn get_encoded(&self) -> &str {
         &self.encoded
     }
 }
@@ -48,7 +57,7 @@ impl Token for RefreshToken {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tokens::{Claims, Token};
+    use crate::tokens::{Claims, Token, TokenCodec};
     use jsonwebtoken::{Algorithm, EncodingKey, Header};
 
     #[test]
@@ -58,17 +67,20 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = RefreshToken::encode(&claims, secret.to_string())?;
+        let encoded = RefreshToken::encode(&claims, &TokenCodec::default(), secret)?;
         let raw_encoded = move || {
             let mut header = Header::new(Algorithm::HS512);
             let claims = Claims {
                 exp: 1,
                 iat: 1,
                 sub: uuid.to_string(),
+                ..Default::default()
             };
 
+            header.typ = Some("refresh+jwt".to_string());
             header.kid = Some(claims.sub.clone());
 
             let key = EncodingKey::from_secret(secret.as_bytes());
@@ -89,10 +101,11 @@ mod tests {
             exp: now + 1000,
             iat: now,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = RefreshToken::encode(&claims, secret.to_string())?;
-        let decoded = RefreshToken::decode(encoded.clone(), &secret.to_string())?;
+        let encoded = RefreshToken::encode(&claims, &TokenCodec::default(), secret)?;
+        let decoded = RefreshToken::decode(&encoded, &TokenCodec::default(), secret)?;
 
         assert_eq!(encoded, decoded.encoded);
 
@@ -106,10 +119,11 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = RefreshToken::encode(&claims, secret.to_string())?;
-        let decoded = RefreshToken::decode(encoded.clone(), &secret.to_string());
+        let encoded = RefreshToken::encode(&claims, &TokenCodec::default(), secret)?;
+        let decoded = RefreshToken::decode(&encoded, &TokenCodec::default(), secret);
 
         // Expired, so is_err must be true
         assert!(decoded.is_err());
@@ -124,9 +138,10 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = RefreshToken::encode(&claims, secret.to_string())?;
+        let encoded = RefreshToken::encode(&claims, &TokenCodec::default(), secret)?;
         let token = RefreshToken::new(encoded, claims);
 
         assert!(token.is_expired());
@@ -141,9 +156,10 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = RefreshToken::encode(&claims, secret.to_string())?;
+        let encoded = RefreshToken::encode(&claims, &TokenCodec::default(), secret)?;
         let token = RefreshToken::new(encoded, claims);
         let headers = token.get_headers()?;
 
@@ -159,9 +175,10 @@ mod tests {
             exp: 1,
             iat: 1,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = RefreshToken::encode(&claims, secret.to_string())?;
+        let encoded = RefreshToken::encode(&claims, &TokenCodec::default(), secret)?;
         let token = RefreshToken::new(encoded.clone(), claims);
 
         assert_eq!(token.try_as_cookie()?.value(), encoded);
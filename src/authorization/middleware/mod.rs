@@ -1,3 +1,4 @@
 mod authorize;
 
+#[allow(unused_imports)]
 pub use authorize::*;
@@ -5,6 +5,7 @@ mod error;
 mod middleware;
 
 pub use error::*;
+#[allow(unused_imports)]
 pub use middleware::*;
 
 /// Return the owner's ID so we can check for ownership against some e.g. User model
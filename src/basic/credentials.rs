@@ -0,0 +1,133 @@
+use crate::basic::BasicAuthError;
+use crate::challenge::ChallengeConfig;
+use crate::error::GateKeeperError;
+use axum::extract::FromRequestParts;
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::http::HeaderMap;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use std::fmt::{Debug, Formatter};
+
+/// Credentials sent using an `Authorization: Basic` header
+///
+/// As extractor it only parses the header, use [`authenticate_basic`](crate::basic::authenticate_basic)
+/// to verify them. Rejections challenge the client with [`ChallengeConfig::DEFAULT_REALM`].
+#[derive(Clone)]
+pub struct BasicCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl BasicCredentials {
+    /// Parse the `Authorization` header, returning `None` if there is none or
+    /// it uses another scheme
+    pub fn from_headers(headers: &HeaderMap) -> Option<Result<Self, String>> {
+        let value = match headers.get(AUTHORIZATION)?.to_str() {
+            Ok(value) => value,
+            Err(_) => return Some(Err("Invalid header value".to_string())),
+        };
+        let (scheme, encoded) = value.split_once(' ')?;
+
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+
+        Some(Self::decode(encoded.trim()))
+    }
+
+    /// Decode base64 encoded `user-id:password`
+    fn decode(encoded: &str) -> Result<Self, String> {
+        let decoded = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Invalid base64: {e}"))?;
+        let decoded = String::from_utf8(decoded).map_err(|_| "Invalid UTF-8".to_string())?;
+        // User ids can't contain colons, passwords can
+        let (username, password) = decoded
+            .split_once(':')
+            .ok_or_else(|| "Missing colon".to_string())?;
+
+        Ok(Self {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+impl Debug for BasicCredentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BasicCredentials")
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for BasicCredentials {
+    type Rejection = GateKeeperError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let realm = ChallengeConfig::DEFAULT_REALM.into();
+
+        match Self::from_headers(&parts.headers) {
+            Some(Ok(credentials)) => Ok(credentials),
+            Some(Err(reason)) => Err(BasicAuthError::MalformedCredentials { realm, reason }.into()),
+            None => Err(BasicAuthError::MissingCredentials { realm }.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BasicCredentials;
+    use axum::http::header::AUTHORIZATION;
+    use axum::http::HeaderMap;
+
+    fn headers(value: &str) -> anyhow::Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+
+        headers.insert(AUTHORIZATION, value.parse()?);
+
+        Ok(headers)
+    }
+
+    #[test]
+    fn test_from_headers() -> anyhow::Result<()> {
+        // Example from RFC 7617
+        let credentials =
+            BasicCredentials::from_headers(&headers("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==")?)
+                .expect("Basic credentials")
+                .map_err(anyhow::Error::msg)?;
+
+        assert_eq!(credentials.username, "Aladdin");
+        assert_eq!(credentials.password, "open sesame");
+        assert!(!format!("{credentials:?}").contains("sesame"));
+
+        // Scheme is case-insensitive, passwords may contain colons
+        let credentials = BasicCredentials::from_headers(&headers("basic YTpiOmM=")?)
+            .expect("Basic credentials")
+            .map_err(anyhow::Error::msg)?;
+
+        assert_eq!(credentials.username, "a");
+        assert_eq!(credentials.password, "b:c");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_headers_invalid() -> anyhow::Result<()> {
+        assert!(BasicCredentials::from_headers(&HeaderMap::new()).is_none());
+        assert!(BasicCredentials::from_headers(&headers("Bearer foo")?).is_none());
+        assert!(matches!(
+            BasicCredentials::from_headers(&headers("Basic %%%")?),
+            Some(Err(_))
+        ));
+        // "foo" without colon
+        assert!(matches!(
+            BasicCredentials::from_headers(&headers("Basic Zm9v")?),
+            Some(Err(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use crate::challenge::Challenge;
+use crate::error::GateKeeperError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BasicAuthError {
+    #[error("Missing Basic credentials for realm {realm}")]
+    MissingCredentials { realm: Arc<str> },
+    #[error("Malformed Basic credentials for realm {realm}: {reason}")]
+    MalformedCredentials { realm: Arc<str>, reason: String },
+    #[error("Invalid Basic credentials for realm {realm}")]
+    InvalidCredentials { realm: Arc<str> },
+}
+
+impl BasicAuthError {
+    /// Return the realm to challenge the client with
+    pub fn realm(&self) -> &str {
+        match self {
+            BasicAuthError::MissingCredentials { realm }
+            | BasicAuthError::MalformedCredentials { realm, .. }
+            | BasicAuthError::InvalidCredentials { realm } => realm,
+        }
+    }
+
+    /// Return the `WWW-Authenticate` header value to respond with
+    pub fn challenge(&self) -> String {
+        Challenge::default().to_header_value("Basic", Some(self.realm()))
+    }
+
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        "invalid_credentials"
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        "Invalid credentials"
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
+impl IntoResponse for BasicAuthError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
@@ -0,0 +1,212 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::authentication::{AuthenticatedSubject, AuthenticationMethod};
+use crate::basic::{BasicAuthError, BasicCredentials, BasicVerifier};
+use crate::challenge::ChallengeConfig;
+use crate::tokens::Acr;
+use crate::GateKeeperResult;
+use axum::extract::State;
+use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+use chrono::Utc;
+use std::sync::Arc;
+
+/// State of the [`authenticate_basic`] middleware
+#[derive(Clone)]
+pub struct BasicAuth {
+    realm: Arc<str>,
+    verifier: Arc<dyn BasicVerifier>,
+}
+
+impl BasicAuth {
+    /// Create a builder for BasicAuth checking credentials using `verifier`
+    pub fn build(verifier: impl BasicVerifier + 'static) -> BasicAuthBuilder {
+        BasicAuthBuilder {
+            realm: None,
+            verifier: Arc::new(verifier),
+        }
+    }
+
+    /// Return the realm clients are challenged with
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// Verify `credentials`, returning the authenticated subject
+    pub async fn authenticate(
+        &self,
+        credentials: &BasicCredentials,
+    ) -> GateKeeperResult<AuthenticatedSubject> {
+        let id = self
+            .verifier
+            .verify(&credentials.username, &credentials.password)
+            .await?
+            .ok_or_else(|| BasicAuthError::InvalidCredentials {
+                realm: self.realm.clone(),
+            })?;
+
+        Ok(AuthenticatedSubject {
+            id,
+            method: AuthenticationMethod::Basic,
+            auth_time: Utc::now().timestamp() as usize,
+            acr: Some(Acr::Password),
+            claims: None,
+        })
+    }
+}
+
+pub struct BasicAuthBuilder {
+    realm: Option<String>,
+    verifier: Arc<dyn BasicVerifier>,
+}
+
+impl BasicAuthBuilder {
+    /// Set field `realm`, defaults to [`ChallengeConfig::DEFAULT_REALM`]
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realm = Some(realm.to_string());
+        self
+    }
+
+    /// Actually create the BasicAuth
+    pub fn build(self) -> BasicAuth {
+        BasicAuth {
+            realm: self
+                .realm
+                .as_deref()
+                .unwrap_or(ChallengeConfig::DEFAULT_REALM)
+                .into(),
+            verifier: self.verifier,
+        }
+    }
+}
+
+/// Middleware authenticating requests using an `Authorization: Basic` header
+///
+/// Inserts the [`AuthenticatedSubject`] into the request's extensions. Use it
+/// with `axum::middleware::from_fn_with_state(basic_auth, authenticate_basic)`.
+/// Failures respond with 401 and a `WWW-Authenticate: Basic` challenge.
+pub async fn authenticate_basic(
+    State(basic_auth): State<BasicAuth>,
+    mut req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
+    tracing::debug!("Using middleware::authenticate_basic");
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let audit = AuditContext::from_request(&req);
+    let realm = basic_auth.realm.clone();
+    let result = match BasicCredentials::from_headers(req.headers()) {
+        Some(Ok(credentials)) => basic_auth.authenticate(&credentials).await,
+        Some(Err(reason)) => Err(BasicAuthError::MalformedCredentials { realm, reason }.into()),
+        None => Err(BasicAuthError::MissingCredentials { realm }.into()),
+    };
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decision("authenticate_basic", started, &result);
+    let subject = audit
+        .record_failure(AuditEventKind::Authentication, result)
+        .await?;
+
+    req.extensions_mut().insert(subject);
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{authenticate_basic, BasicAuth};
+    use crate::authentication::AuthenticatedSubject;
+    use crate::basic::{BasicCredentials, StaticBasicVerifier};
+    use axum::body::Body;
+    use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn app(admin: uuid::Uuid) -> Router {
+        let verifier = StaticBasicVerifier::new().with_credentials(admin, "admin", "secret");
+        let basic_auth = BasicAuth::build(verifier).realm("Admin \"tools\"").build();
+
+        Router::new()
+            .route(
+                "/",
+                get(|subject: AuthenticatedSubject| async move { subject.id.to_string() }),
+            )
+            .layer(middleware::from_fn_with_state(
+                basic_auth,
+                authenticate_basic,
+            ))
+    }
+
+    async fn call(
+        app: Router,
+        authorization: Option<&str>,
+    ) -> anyhow::Result<axum::response::Response> {
+        let mut request = Request::get("/");
+
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        Ok(app.oneshot(request.body(Body::empty())?).await?)
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_basic() -> anyhow::Result<()> {
+        let admin = uuid::Uuid::new_v4();
+        // admin:secret
+        let response = call(app(admin), Some("Basic YWRtaW46c2VjcmV0")).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await?.to_bytes();
+
+        assert_eq!(body, admin.to_string());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_basic_rejected() -> anyhow::Result<()> {
+        // admin:wrong, malformed, missing and bearer
+        for authorization in [
+            Some("Basic YWRtaW46d3Jvbmc="),
+            Some("Basic Zm9v"),
+            None,
+            Some("Bearer foo"),
+        ] {
+            let response = call(app(uuid::Uuid::new_v4()), authorization).await?;
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                response.headers()[WWW_AUTHENTICATE],
+                r#"Basic realm="Admin \"tools\"", charset="UTF-8""#
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extractor_default_realm() -> anyhow::Result<()> {
+        let app = Router::new().route(
+            "/",
+            get(|credentials: BasicCredentials| async move { credentials.username }),
+        );
+        let response = call(app.clone(), None).await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()[WWW_AUTHENTICATE],
+            r#"Basic realm="Restricted", charset="UTF-8""#
+        );
+
+        let response = call(app, Some("Basic YWRtaW46c2VjcmV0")).await?;
+        let body = response.into_body().collect().await?.to_bytes();
+
+        assert_eq!(body, "admin");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+//! Module containing HTTP Basic authentication (RFC 7617)
+//!
+//! Meant for admin tooling and legacy integrations which can't use bearer
+//! tokens. Credentials are checked by a [`BasicVerifier`] on every request.
+//!
+//! Only available on feature `basic`
+mod credentials;
+mod error;
+mod middleware;
+mod verifier;
+
+pub use credentials::*;
+pub use error::*;
+pub use middleware::*;
+pub use verifier::*;
@@ -0,0 +1,92 @@
+use crate::util::constant_time_eq;
+use crate::GateKeeperResult;
+use sha2::{Digest, Sha256};
+
+/// Check Basic credentials, e.g. against a user database or static config
+#[async_trait::async_trait]
+pub trait BasicVerifier: Send + Sync {
+    /// Return the id of the subject `username` belongs to, if `password` is
+    /// correct
+    ///
+    /// Implementations should compare credentials in constant time.
+    async fn verify(&self, username: &str, password: &str) -> GateKeeperResult<Option<uuid::Uuid>>;
+}
+
+#[async_trait::async_trait]
+impl<T: BasicVerifier + ?Sized> BasicVerifier for std::sync::Arc<T> {
+    async fn verify(&self, username: &str, password: &str) -> GateKeeperResult<Option<uuid::Uuid>> {
+        self.as_ref().verify(username, password).await
+    }
+}
+
+/// [`BasicVerifier`] checking against a fixed list of credentials
+///
+/// Every entry is compared on each request, so the response time doesn't
+/// reveal whether a username exists.
+#[derive(Default)]
+pub struct StaticBasicVerifier {
+    credentials: Vec<(uuid::Uuid, [u8; 32], [u8; 32])>,
+}
+
+impl StaticBasicVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add credentials for `subject`
+    pub fn with_credentials(mut self, subject: uuid::Uuid, username: &str, password: &str) -> Self {
+        self.credentials
+            .push((subject, digest(username), digest(password)));
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl BasicVerifier for StaticBasicVerifier {
+    async fn verify(&self, username: &str, password: &str) -> GateKeeperResult<Option<uuid::Uuid>> {
+        // Digests have a fixed length, so the comparison doesn't leak it either
+        let username = digest(username);
+        let password = digest(password);
+
+        Ok(self.credentials.iter().fold(
+            None,
+            |found, (subject, expected_username, expected_password)| {
+                let username_matches = constant_time_eq(&username, expected_username);
+                let password_matches = constant_time_eq(&password, expected_password);
+
+                found.or((username_matches & password_matches).then_some(*subject))
+            },
+        ))
+    }
+}
+
+fn digest(value: &str) -> [u8; 32] {
+    Sha256::digest(value.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BasicVerifier, StaticBasicVerifier};
+
+    #[tokio::test]
+    async fn test_static_verifier() -> anyhow::Result<()> {
+        let admin = uuid::Uuid::new_v4();
+        let legacy = uuid::Uuid::new_v4();
+        let verifier = StaticBasicVerifier::new()
+            .with_credentials(admin, "admin", "correct horse")
+            .with_credentials(legacy, "legacy", "battery staple");
+
+        assert_eq!(
+            verifier.verify("admin", "correct horse").await?,
+            Some(admin)
+        );
+        assert_eq!(
+            verifier.verify("legacy", "battery staple").await?,
+            Some(legacy)
+        );
+        assert_eq!(verifier.verify("admin", "battery staple").await?, None);
+        assert_eq!(verifier.verify("nobody", "correct horse").await?, None);
+
+        Ok(())
+    }
+}
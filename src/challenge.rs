@@ -0,0 +1,249 @@
+//! Module containing `WWW-Authenticate` challenges as defined by RFC 6750
+//!
+//! Error responses of bearer authentication carry a challenge describing the
+//! failure, e.g.
+//!
+//! ```text
+//! WWW-Authenticate: Bearer realm="Restricted", error="invalid_token", error_description="Token has expired"
+//! ```
+//!
+//! Requests lacking a token are challenged without `error`, as required by
+//! RFC 6750, section 3.1. To change the realm or to challenge clients with
+//! several schemes, e.g. `Bearer` and `DPoP`, set a [`ChallengeConfig`] on the
+//! [`ErrorConfig`](crate::problem::ErrorConfig) of the
+//! [`render_errors`](crate::problem::render_errors) middleware.
+use axum::http::header::WWW_AUTHENTICATE;
+use axum::http::{HeaderMap, HeaderValue};
+
+/// Error codes of bearer challenges
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BearerError {
+    /// The request is malformed, responded with `400 Bad Request`
+    InvalidRequest,
+    /// The token is expired, revoked, malformed or otherwise invalid
+    InvalidToken,
+    /// The token lacks the scope required by the resource
+    InsufficientScope,
+    /// The user has to authenticate again, see RFC 9470
+    InsufficientUserAuthentication,
+}
+
+impl BearerError {
+    /// Return the value of the `error` parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BearerError::InvalidRequest => "invalid_request",
+            BearerError::InvalidToken => "invalid_token",
+            BearerError::InsufficientScope => "insufficient_scope",
+            BearerError::InsufficientUserAuthentication => "insufficient_user_authentication",
+        }
+    }
+}
+
+/// Parameters of the challenge sent with an error response
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Challenge {
+    /// Error code, omitted if the request carried no credentials
+    pub error: Option<BearerError>,
+    /// Human-readable explanation of the error
+    pub description: Option<String>,
+    /// Further parameters, e.g. `scope` or `max_age`, with already quoted
+    /// values where needed
+    pub params: Vec<(&'static str, String)>,
+}
+
+impl Challenge {
+    /// Create challenge with `error` and `description`
+    pub fn new(error: BearerError, description: impl Into<String>) -> Self {
+        Self {
+            error: Some(error),
+            description: Some(description.into()),
+            params: Vec::new(),
+        }
+    }
+
+    /// Add parameter `name` with `value`, which is quoted
+    pub fn with_param(mut self, name: &'static str, value: &str) -> Self {
+        self.params.push((name, quote(value)));
+        self
+    }
+
+    /// Add parameter `name` with `value` as token, e.g. a number
+    pub fn with_token_param(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.params.push((name, value.to_string()));
+        self
+    }
+
+    /// Return the challenge for `scheme` using `realm`
+    ///
+    /// `Basic` challenges only carry the realm, as defined by RFC 7617.
+    pub fn to_header_value(&self, scheme: &str, realm: Option<&str>) -> String {
+        let mut params = Vec::new();
+
+        if let Some(realm) = realm {
+            params.push(format!("realm={}", quote(realm)));
+        }
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            params.push(r#"charset="UTF-8""#.to_string());
+        } else {
+            if let Some(error) = self.error {
+                params.push(format!(r#"error="{}""#, error.as_str()));
+            }
+
+            if let Some(description) = &self.description {
+                params.push(format!("error_description={}", quote(description)));
+            }
+
+            params.extend(
+                self.params
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}")),
+            );
+        }
+
+        match params.is_empty() {
+            true => scheme.to_string(),
+            false => format!("{scheme} {}", params.join(", ")),
+        }
+    }
+
+    /// Append one `WWW-Authenticate` header per configured scheme to `headers`
+    pub fn append_to(&self, config: &ChallengeConfig, headers: &mut HeaderMap) {
+        for scheme in &config.schemes {
+            let value = self.to_header_value(scheme, config.realm.as_deref());
+
+            match HeaderValue::try_from(value) {
+                Ok(value) => {
+                    headers.append(WWW_AUTHENTICATE, value);
+                }
+                Err(e) => tracing::error!("Invalid challenge for scheme {scheme}: {e:?}"),
+            }
+        }
+    }
+}
+
+/// Realm and schemes used for challenges
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChallengeConfig {
+    realm: Option<String>,
+    schemes: Vec<String>,
+}
+
+impl ChallengeConfig {
+    /// Realm used if none is set
+    pub const DEFAULT_REALM: &'static str = "Restricted";
+
+    /// Create a builder for ChallengeConfig
+    pub fn build() -> ChallengeConfigBuilder {
+        ChallengeConfigBuilder {
+            realm: None,
+            schemes: Vec::new(),
+        }
+    }
+
+    /// Return the realm clients are challenged with, if any
+    pub fn realm(&self) -> Option<&str> {
+        self.realm.as_deref()
+    }
+
+    /// Return the schemes clients are challenged with
+    pub fn schemes(&self) -> &[String] {
+        &self.schemes
+    }
+}
+
+impl Default for ChallengeConfig {
+    fn default() -> Self {
+        Self::build().build()
+    }
+}
+
+pub struct ChallengeConfigBuilder {
+    realm: Option<Option<String>>,
+    schemes: Vec<String>,
+}
+
+impl ChallengeConfigBuilder {
+    /// Set field `realm`, defaults to [`ChallengeConfig::DEFAULT_REALM`]
+    pub fn realm(mut self, realm: impl Into<String>) -> Self {
+        self.realm = Some(Some(realm.into()));
+        self
+    }
+
+    /// Omit the realm from challenges
+    pub fn without_realm(mut self) -> Self {
+        self.realm = Some(None);
+        self
+    }
+
+    /// Add a scheme to challenge clients with, defaults to `Bearer` only
+    pub fn scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.schemes.push(scheme.into());
+        self
+    }
+
+    /// Actually create the ChallengeConfig
+    pub fn build(self) -> ChallengeConfig {
+        ChallengeConfig {
+            realm: self
+                .realm
+                .unwrap_or_else(|| Some(ChallengeConfig::DEFAULT_REALM.to_string())),
+            schemes: match self.schemes.is_empty() {
+                true => vec!["Bearer".to_string()],
+                false => self.schemes,
+            },
+        }
+    }
+}
+
+/// Quote `value` as `quoted-string`
+fn quote(value: &str) -> String {
+    format!(r#""{}""#, value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BearerError, Challenge, ChallengeConfig};
+    use axum::http::header::WWW_AUTHENTICATE;
+    use axum::http::HeaderMap;
+
+    #[test]
+    fn test_challenge() {
+        let challenge = Challenge::new(BearerError::InsufficientScope, "Scope \"admin\" required")
+            .with_param("scope", "admin");
+
+        assert_eq!(
+            challenge.to_header_value("Bearer", Some("api")),
+            r#"Bearer realm="api", error="insufficient_scope", error_description="Scope \"admin\" required", scope="admin""#
+        );
+        assert_eq!(
+            challenge.to_header_value("Basic", Some("api")),
+            r#"Basic realm="api", charset="UTF-8""#
+        );
+        assert_eq!(
+            Challenge::default().to_header_value("Bearer", None),
+            "Bearer"
+        );
+    }
+
+    #[test]
+    fn test_multiple_schemes() {
+        let config = ChallengeConfig::build()
+            .realm("api")
+            .scheme("Bearer")
+            .scheme("DPoP")
+            .build();
+        let mut headers = HeaderMap::new();
+
+        Challenge::default().append_to(&config, &mut headers);
+
+        let challenges = headers
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>();
+
+        assert_eq!(challenges, [r#"Bearer realm="api""#, r#"DPoP realm="api""#]);
+    }
+}
@@ -0,0 +1,38 @@
+use crate::error::GateKeeperError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+#[derive(thiserror::Error, Debug)]
+pub enum CsrfError {
+    #[error("Missing Origin and Referer headers")]
+    MissingOrigin,
+    #[error("Origin {0} isn't allowed")]
+    OriginMismatch(String),
+    #[error("Missing CSRF token")]
+    MissingToken,
+    #[error("CSRF token doesn't match cookie")]
+    InvalidToken,
+}
+
+impl CsrfError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        "csrf_check_failed"
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        "CSRF check failed"
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        StatusCode::FORBIDDEN
+    }
+}
+
+impl IntoResponse for CsrfError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
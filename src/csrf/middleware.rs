@@ -0,0 +1,403 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::csrf::{CsrfError, CsrfToken};
+use crate::util::constant_time_eq;
+use crate::GateKeeperResult;
+use axum::extract::{FromRequest, State};
+use axum::http::header::{CONTENT_TYPE, COOKIE, HOST, ORIGIN, REFERER, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use axum::Form;
+use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+use cookie::Cookie;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Maximum size of form bodies searched for the token
+const MAX_FORM_LEN: usize = 2 * 1024 * 1024;
+
+/// Configuration of the [`protect_csrf`] middleware
+#[derive(Clone)]
+pub struct CsrfProtection {
+    allowed_origins: Arc<[String]>,
+    cookie_name: Arc<str>,
+    header_name: HeaderName,
+}
+
+impl CsrfProtection {
+    /// Name of the CSRF cookie if none is set
+    pub const DEFAULT_COOKIE_NAME: &'static str = "csrf_token";
+    /// Name of the header carrying the token if none is set
+    pub const DEFAULT_HEADER_NAME: &'static str = "x-csrf-token";
+    /// Name of the form field carrying the token
+    pub const FORM_FIELD: &'static str = "csrf_token";
+
+    /// Create a builder for CsrfProtection
+    pub fn build() -> CsrfProtectionBuilder {
+        CsrfProtectionBuilder::default()
+    }
+
+    /// Check that the request originates from an allowed origin
+    ///
+    /// Uses the `Origin` header, falling back to the `Referer` header. Without
+    /// any configured origins, the request's `Host` is the only allowed one.
+    pub fn check_origin(&self, headers: &HeaderMap) -> Result<(), CsrfError> {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let origin = header(ORIGIN)
+            .map(str::to_string)
+            .or_else(|| header(REFERER).and_then(origin_of))
+            .ok_or(CsrfError::MissingOrigin)?;
+        let allowed = if self.allowed_origins.is_empty() {
+            let authority = origin.split_once("://").map(|(_, authority)| authority);
+
+            authority.is_some() && authority == header(HOST)
+        } else {
+            self.allowed_origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(&origin))
+        };
+
+        if !allowed {
+            return Err(CsrfError::OriginMismatch(origin));
+        }
+
+        Ok(())
+    }
+
+    /// Return the cookie storing `token`
+    ///
+    /// Unlike other cookies it's readable by scripts, which have to send its
+    /// value as header.
+    pub fn cookie(&self, token: &CsrfToken) -> Cookie<'static> {
+        Cookie::build((self.cookie_name.to_string(), token.0.clone()))
+            .path("/")
+            .secure(true)
+            .same_site(cookie::SameSite::Strict)
+            .build()
+    }
+
+    /// Read the token from the request's cookies
+    fn cookie_token(&self, headers: &HeaderMap) -> Option<CsrfToken> {
+        headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(Cookie::split_parse)
+            .filter_map(Result::ok)
+            .find(|cookie| cookie.name() == &*self.cookie_name)
+            .map(|cookie| CsrfToken(cookie.value().to_string()))
+    }
+
+    /// Read the submitted token from the header or, for URL encoded forms, the
+    /// body, returning the request with its body restored
+    async fn submitted_token(&self, req: Request) -> (Option<String>, Request) {
+        if let Some(value) = req.headers().get(&self.header_name) {
+            return (value.to_str().ok().map(str::to_string), req);
+        }
+
+        let is_form = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+
+        if !is_form {
+            return (None, req);
+        }
+
+        let (parts, body) = req.into_parts();
+        let Ok(bytes) = axum::body::to_bytes(body, MAX_FORM_LEN).await else {
+            return (None, Request::from_parts(parts, Body::empty()));
+        };
+        let form = Request::from_parts(parts.clone(), Body::from(bytes.clone()));
+        let token = Form::<CsrfForm>::from_request(form, &())
+            .await
+            .ok()
+            .and_then(|Form(form)| form.csrf_token);
+
+        (token, Request::from_parts(parts, Body::from(bytes)))
+    }
+}
+
+#[derive(Deserialize)]
+struct CsrfForm {
+    csrf_token: Option<String>,
+}
+
+/// Return the origin, i.e. scheme, host and port, of `url`
+fn origin_of(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+
+    Some(format!("{scheme}://{authority}"))
+}
+
+#[derive(Default)]
+pub struct CsrfProtectionBuilder {
+    allowed_origins: Vec<String>,
+    cookie_name: Option<String>,
+    header_name: Option<HeaderName>,
+}
+
+impl CsrfProtectionBuilder {
+    /// Allow requests from `origin`, e.g. `https://app.example.com`
+    pub fn allowed_origin(mut self, origin: &str) -> Self {
+        self.allowed_origins
+            .push(origin.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Set field `cookie_name`, defaults to [`CsrfProtection::DEFAULT_COOKIE_NAME`]
+    pub fn cookie_name(mut self, cookie_name: &str) -> Self {
+        self.cookie_name = Some(cookie_name.to_string());
+        self
+    }
+
+    /// Set field `header_name`, defaults to [`CsrfProtection::DEFAULT_HEADER_NAME`]
+    pub fn header_name(mut self, header_name: HeaderName) -> Self {
+        self.header_name = Some(header_name);
+        self
+    }
+
+    /// Actually create the CsrfProtection
+    pub fn build(self) -> CsrfProtection {
+        CsrfProtection {
+            allowed_origins: self.allowed_origins.into(),
+            cookie_name: self
+                .cookie_name
+                .as_deref()
+                .unwrap_or(CsrfProtection::DEFAULT_COOKIE_NAME)
+                .into(),
+            header_name: self
+                .header_name
+                .unwrap_or(HeaderName::from_static(CsrfProtection::DEFAULT_HEADER_NAME)),
+        }
+    }
+}
+
+/// Middleware protecting cookie authenticated routes against CSRF
+///
+/// Requests using unsafe methods, i.e. anything but `GET`, `HEAD`, `OPTIONS`
+/// and `TRACE`, have to come from an allowed origin and submit the CSRF
+/// cookie's value as header or form field. Failures are rejected with 403.
+/// Inserts the [`CsrfToken`] into the request's extensions and sets the
+/// cookie if the request hasn't got one. Use it with
+/// `axum::middleware::from_fn_with_state(csrf, protect_csrf)`.
+pub async fn protect_csrf(
+    State(csrf): State<CsrfProtection>,
+    req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
+    tracing::debug!("Using middleware::protect_csrf");
+
+    let cookie_token = csrf.cookie_token(req.headers());
+    let is_safe = matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    );
+    let mut req = if is_safe {
+        req
+    } else {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let audit = AuditContext::from_request(&req);
+        let result = check_token(&csrf, cookie_token.as_ref(), req).await;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_decision("protect_csrf", started, &result);
+
+        audit
+            .record_failure(AuditEventKind::CsrfCheck, result)
+            .await?
+    };
+    let is_new = cookie_token.is_none();
+    let token = cookie_token.unwrap_or_else(CsrfToken::generate);
+
+    req.extensions_mut().insert(token.clone());
+
+    let mut response = next.run(req).await;
+
+    if is_new {
+        if let Ok(cookie) = HeaderValue::from_str(&csrf.cookie(&token).to_string()) {
+            response.headers_mut().append(SET_COOKIE, cookie);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Check the origin and the token submitted with `req` against `expected`
+async fn check_token(
+    csrf: &CsrfProtection,
+    expected: Option<&CsrfToken>,
+    req: Request,
+) -> GateKeeperResult<Request> {
+    csrf.check_origin(req.headers())?;
+
+    let expected = expected.ok_or(CsrfError::MissingToken)?;
+    let (submitted, req) = csrf.submitted_token(req).await;
+    let submitted = submitted.ok_or(CsrfError::MissingToken)?;
+
+    if !constant_time_eq(submitted.as_bytes(), expected.0.as_bytes()) {
+        return Err(CsrfError::InvalidToken.into());
+    }
+
+    Ok(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{protect_csrf, CsrfProtection};
+    use crate::csrf::CsrfToken;
+    use axum::body::Body;
+    use axum::http::header::{CONTENT_TYPE, COOKIE, HOST, ORIGIN, REFERER, SET_COOKIE};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::{middleware, Form, Router};
+    use http_body_util::BodyExt;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    const ORIGIN_URL: &str = "https://app.example.com";
+
+    fn app(csrf: CsrfProtection) -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|token: CsrfToken| async move { token.0 }).post(
+                    |Form(form): Form<HashMap<String, String>>| async move {
+                        form.get("comment").cloned().unwrap_or_default()
+                    },
+                ),
+            )
+            .layer(middleware::from_fn_with_state(csrf, protect_csrf))
+    }
+
+    fn protected() -> Router {
+        app(CsrfProtection::build().allowed_origin(ORIGIN_URL).build())
+    }
+
+    fn post(token: &str) -> axum::http::request::Builder {
+        Request::post("/")
+            .header(COOKIE, format!("csrf_token={token}"))
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+    }
+
+    async fn call(app: Router, request: Request<Body>) -> anyhow::Result<(StatusCode, String)> {
+        let response = app.oneshot(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok((status, String::from_utf8(body.to_vec())?))
+    }
+
+    #[tokio::test]
+    async fn test_issues_token() -> anyhow::Result<()> {
+        let response = protected()
+            .oneshot(Request::get("/").body(Body::empty())?)
+            .await?;
+        let cookie = cookie::Cookie::parse(response.headers()[SET_COOKIE].to_str()?.to_string())?;
+        let body = response.into_body().collect().await?.to_bytes();
+
+        assert_eq!(cookie.name(), "csrf_token");
+        assert_eq!(cookie.http_only(), None);
+        assert_eq!(body, cookie.value());
+
+        // Existing tokens are kept
+        let response = protected()
+            .oneshot(
+                Request::get("/")
+                    .header(COOKIE, "csrf_token=existing")
+                    .body(Body::empty())?,
+            )
+            .await?;
+
+        assert!(!response.headers().contains_key(SET_COOKIE));
+        assert_eq!(response.into_body().collect().await?.to_bytes(), "existing");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_accepts_matching_token() -> anyhow::Result<()> {
+        let header = post("t0ken")
+            .header(ORIGIN, ORIGIN_URL)
+            .header("x-csrf-token", "t0ken")
+            .body(Body::from("comment=hi"))?;
+        let form = post("t0ken")
+            .header(REFERER, format!("{ORIGIN_URL}/posts/1?page=2"))
+            .body(Body::from("comment=hi&csrf_token=t0ken"))?;
+
+        for request in [header, form] {
+            assert_eq!(
+                call(protected(), request).await?,
+                (StatusCode::OK, "hi".to_string())
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_forged_requests() -> anyhow::Result<()> {
+        let wrong_token = post("t0ken")
+            .header(ORIGIN, ORIGIN_URL)
+            .header("x-csrf-token", "other")
+            .body(Body::empty())?;
+        let missing_token = post("t0ken")
+            .header(ORIGIN, ORIGIN_URL)
+            .body(Body::from("comment=hi"))?;
+        let missing_cookie = Request::post("/")
+            .header(ORIGIN, ORIGIN_URL)
+            .header("x-csrf-token", "t0ken")
+            .body(Body::empty())?;
+        let foreign_origin = post("t0ken")
+            .header(ORIGIN, "https://evil.example.com")
+            .header("x-csrf-token", "t0ken")
+            .body(Body::empty())?;
+        let missing_origin = post("t0ken")
+            .header("x-csrf-token", "t0ken")
+            .body(Body::empty())?;
+
+        for request in [
+            wrong_token,
+            missing_token,
+            missing_cookie,
+            foreign_origin,
+            missing_origin,
+        ] {
+            let (status, body) = call(protected(), request).await?;
+
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body)?["code"],
+                "csrf_check_failed"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_defaults_to_host() -> anyhow::Result<()> {
+        let request = |origin: &str| {
+            post("t0ken")
+                .header(HOST, "app.example.com")
+                .header(ORIGIN, origin)
+                .header("x-csrf-token", "t0ken")
+                .body(Body::empty())
+        };
+        let csrf = CsrfProtection::build().build();
+
+        assert_eq!(
+            call(app(csrf.clone()), request(ORIGIN_URL)?).await?.0,
+            StatusCode::OK
+        );
+        assert_eq!(
+            call(app(csrf), request("https://evil.example.com")?)
+                .await?
+                .0,
+            StatusCode::FORBIDDEN
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+//! Module containing CSRF protection for cookie authenticated routes
+//!
+//! Cookies like the `RefreshToken`'s are sent with cross-site requests, so
+//! routes relying on them have to make sure requests originate from the
+//! application itself. The [`protect_csrf`] middleware checks the `Origin`,
+//! or `Referer`, header of requests using unsafe methods and requires a
+//! double-submit token: the value of the CSRF cookie has to be sent as
+//! `X-CSRF-Token` header or `csrf_token` form field as well. Handlers
+//! rendering forms get the token using the [`CsrfToken`] extractor.
+//!
+//! Only available on feature `csrf`
+mod error;
+mod middleware;
+mod token;
+
+pub use error::*;
+pub use middleware::*;
+pub use token::*;
@@ -0,0 +1,44 @@
+use crate::csrf::CsrfError;
+use crate::error::GateKeeperError;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use data_encoding::BASE64URL_NOPAD;
+use rand_core::{OsRng, RngCore};
+
+/// The request's CSRF token, to be embedded into forms or read by scripts
+///
+/// Inserted into the request's extensions by the
+/// [`protect_csrf`](crate::csrf::protect_csrf) middleware, which sets the
+/// cookie as well if the request hasn't got one yet.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CsrfToken(pub String);
+
+impl CsrfToken {
+    /// Number of random bytes of a token
+    const LEN: usize = 32;
+
+    /// Generate a new random token
+    pub fn generate() -> Self {
+        let mut token = [0u8; Self::LEN];
+
+        OsRng.fill_bytes(&mut token);
+
+        Self(BASE64URL_NOPAD.encode(&token))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for CsrfToken {
+    type Rejection = GateKeeperError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Self>().cloned().ok_or_else(|| {
+            tracing::error!("CsrfToken extracted on a route without protect_csrf middleware");
+
+            CsrfError::MissingToken.into()
+        })
+    }
+}
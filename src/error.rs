@@ -1,59 +1,388 @@
+use crate::challenge::Challenge;
+use crate::problem::{ErrorConfig, ErrorFormat, Problem};
+use axum::http::header::RETRY_AFTER;
+#[cfg(feature = "basic")]
+use axum::http::header::WWW_AUTHENTICATE;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
 
 pub use crate::tokens::TokenError;
 
 #[derive(thiserror::Error, Debug)]
 pub enum GateKeeperError {
+    #[cfg(feature = "api-key")]
+    #[error("API key error: {0}")]
+    ApiKey(#[from] crate::api_key::ApiKeyError),
     #[cfg(feature = "authentication")]
     #[error("Authentication error: {0}")]
     Authentication(#[from] crate::authentication::AuthenticationError),
+    #[cfg(feature = "basic")]
+    #[error("Basic authentication error: {0}")]
+    Basic(#[from] crate::basic::BasicAuthError),
+    #[cfg(feature = "csrf")]
+    #[error("CSRF error: {0}")]
+    Csrf(#[from] crate::csrf::CsrfError),
     #[cfg(feature = "authorization")]
     #[error("Authorization error: {0}")]
     Authorization(#[from] crate::authorization::AuthorizationError),
+    #[cfg(feature = "mfa")]
+    #[error("MFA error: {0}")]
+    Mfa(#[from] crate::mfa::MfaError),
+    #[cfg(feature = "oauth2")]
+    #[error("OAuth2 error: {0}")]
+    OAuth(#[from] crate::oauth2::OAuthError),
+    #[cfg(feature = "oidc")]
+    #[error("OpenID Connect error: {0}")]
+    Oidc(#[from] crate::oidc::OidcError),
+    #[cfg(feature = "password")]
+    #[error("Password error: {0}")]
+    Password(#[from] crate::password::PasswordError),
+    #[cfg(feature = "session")]
+    #[error("Session error: {0}")]
+    Session(#[from] crate::session::SessionError),
     #[cfg(feature = "verification")]
     #[error("Verification error: {0}")]
     Verification(#[from] crate::verification::VerificationError),
+    #[cfg(feature = "external-jwt")]
+    #[error("External token error: {0}")]
+    ExternalJwt(#[from] crate::external_jwt::ExternalJwtError),
+    #[cfg(feature = "jwks")]
+    #[error("Key set error: {0}")]
+    Jwks(#[from] crate::jwks::JwksError),
     #[error("Cannot parse int value: {0}")]
     ParseInt(#[from] std::num::ParseIntError),
     #[error("Error reading env var: {0}")]
     EnvVar(#[from] std::env::VarError),
     #[error("Token handling error: {0}")]
     Token(#[from] TokenError),
+    #[error("Error running blocking task: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("Too many attempts, retry after {0:?}")]
+    RateLimited(std::time::Duration),
 }
 
-impl IntoResponse for GateKeeperError {
-    fn into_response(self) -> Response {
+/// Module an error originates from
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    #[cfg(feature = "api-key")]
+    ApiKey,
+    #[cfg(feature = "authentication")]
+    Authentication,
+    #[cfg(feature = "basic")]
+    Basic,
+    #[cfg(feature = "csrf")]
+    Csrf,
+    #[cfg(feature = "authorization")]
+    Authorization,
+    #[cfg(feature = "mfa")]
+    Mfa,
+    #[cfg(feature = "oauth2")]
+    OAuth,
+    #[cfg(feature = "oidc")]
+    Oidc,
+    #[cfg(feature = "password")]
+    Password,
+    #[cfg(feature = "session")]
+    Session,
+    #[cfg(feature = "verification")]
+    Verification,
+    #[cfg(feature = "external-jwt")]
+    ExternalJwt,
+    #[cfg(feature = "jwks")]
+    Jwks,
+    Token,
+    RateLimited,
+    /// Parsing, configuration and task errors
+    Internal,
+}
+
+/// Structured description of an error passed to
+/// [`ErrorRenderer`](crate::problem::ErrorRenderer)s
+///
+/// Added to the extensions of every error response.
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    pub kind: ErrorKind,
+    /// Machine-readable error code, see [`GateKeeperError::code`]
+    pub code: &'static str,
+    pub status: StatusCode,
+    /// Short summary which is safe to show to clients
+    pub title: &'static str,
+    /// Details which are safe to show to clients, if any
+    pub detail: Option<String>,
+    /// Full error message for logging, which may contain internal details
+    pub internal: String,
+}
+
+impl GateKeeperError {
+    /// Return the module the error originates from
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            #[cfg(feature = "api-key")]
+            GateKeeperError::ApiKey(_) => ErrorKind::ApiKey,
+            #[cfg(feature = "authentication")]
+            GateKeeperError::Authentication(_) => ErrorKind::Authentication,
+            #[cfg(feature = "basic")]
+            GateKeeperError::Basic(_) => ErrorKind::Basic,
+            #[cfg(feature = "csrf")]
+            GateKeeperError::Csrf(_) => ErrorKind::Csrf,
+            #[cfg(feature = "authorization")]
+            GateKeeperError::Authorization(_) => ErrorKind::Authorization,
+            #[cfg(feature = "mfa")]
+            GateKeeperError::Mfa(_) => ErrorKind::Mfa,
+            #[cfg(feature = "oauth2")]
+            GateKeeperError::OAuth(_) => ErrorKind::OAuth,
+            #[cfg(feature = "oidc")]
+            GateKeeperError::Oidc(_) => ErrorKind::Oidc,
+            #[cfg(feature = "password")]
+            GateKeeperError::Password(_) => ErrorKind::Password,
+            #[cfg(feature = "session")]
+            GateKeeperError::Session(_) => ErrorKind::Session,
+            #[cfg(feature = "verification")]
+            GateKeeperError::Verification(_) => ErrorKind::Verification,
+            #[cfg(feature = "external-jwt")]
+            GateKeeperError::ExternalJwt(_) => ErrorKind::ExternalJwt,
+            #[cfg(feature = "jwks")]
+            GateKeeperError::Jwks(_) => ErrorKind::Jwks,
+            GateKeeperError::Token(_) => ErrorKind::Token,
+            GateKeeperError::RateLimited(_) => ErrorKind::RateLimited,
+            GateKeeperError::ParseInt(_)
+            | GateKeeperError::EnvVar(_)
+            | GateKeeperError::Task(_) => ErrorKind::Internal,
+        }
+    }
+
+    /// Return the machine-readable error code, which is stable across releases
+    pub fn code(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "api-key")]
+            GateKeeperError::ApiKey(e) => e.code(),
+            #[cfg(feature = "authentication")]
+            GateKeeperError::Authentication(e) => e.code(),
+            #[cfg(feature = "basic")]
+            GateKeeperError::Basic(e) => e.code(),
+            #[cfg(feature = "csrf")]
+            GateKeeperError::Csrf(e) => e.code(),
+            #[cfg(feature = "authorization")]
+            GateKeeperError::Authorization(e) => e.code(),
+            #[cfg(feature = "mfa")]
+            GateKeeperError::Mfa(e) => e.code(),
+            #[cfg(feature = "oauth2")]
+            GateKeeperError::OAuth(e) => e.code(),
+            #[cfg(feature = "oidc")]
+            GateKeeperError::Oidc(e) => e.code(),
+            #[cfg(feature = "password")]
+            GateKeeperError::Password(e) => e.code(),
+            #[cfg(feature = "session")]
+            GateKeeperError::Session(e) => e.code(),
+            #[cfg(feature = "verification")]
+            GateKeeperError::Verification(e) => e.code(),
+            #[cfg(feature = "external-jwt")]
+            GateKeeperError::ExternalJwt(e) => e.code(),
+            #[cfg(feature = "jwks")]
+            GateKeeperError::Jwks(e) => e.code(),
+            GateKeeperError::Token(e) => e.code(),
+            GateKeeperError::ParseInt(_)
+            | GateKeeperError::EnvVar(_)
+            | GateKeeperError::Task(_) => "internal_error",
+            GateKeeperError::RateLimited(_) => "rate_limited",
+        }
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "api-key")]
+            GateKeeperError::ApiKey(e) => e.title(),
+            #[cfg(feature = "authentication")]
+            GateKeeperError::Authentication(e) => e.title(),
+            #[cfg(feature = "basic")]
+            GateKeeperError::Basic(e) => e.title(),
+            #[cfg(feature = "csrf")]
+            GateKeeperError::Csrf(e) => e.title(),
+            #[cfg(feature = "authorization")]
+            GateKeeperError::Authorization(e) => e.title(),
+            #[cfg(feature = "mfa")]
+            GateKeeperError::Mfa(e) => e.title(),
+            #[cfg(feature = "oauth2")]
+            GateKeeperError::OAuth(e) => e.title(),
+            #[cfg(feature = "oidc")]
+            GateKeeperError::Oidc(e) => e.title(),
+            #[cfg(feature = "password")]
+            GateKeeperError::Password(e) => e.title(),
+            #[cfg(feature = "session")]
+            GateKeeperError::Session(e) => e.title(),
+            #[cfg(feature = "verification")]
+            GateKeeperError::Verification(e) => e.title(),
+            #[cfg(feature = "external-jwt")]
+            GateKeeperError::ExternalJwt(e) => e.title(),
+            #[cfg(feature = "jwks")]
+            GateKeeperError::Jwks(e) => e.title(),
+            GateKeeperError::Token(e) => e.title(),
+            GateKeeperError::ParseInt(_)
+            | GateKeeperError::EnvVar(_)
+            | GateKeeperError::Task(_) => "Internal error",
+            GateKeeperError::RateLimited(_) => "Too many attempts",
+        }
+    }
+
+    /// Return details which are safe to show to clients, if any
+    pub fn detail(&self) -> Option<String> {
         match self {
+            GateKeeperError::Token(e) => e.detail(),
+            _ => None,
+        }
+    }
+
+    /// Return the `WWW-Authenticate` challenge to respond with, if any
+    ///
+    /// Only errors of bearer authentication and scope checks are challenged.
+    /// Basic authentication errors carry their own challenge.
+    pub fn challenge(&self) -> Option<Challenge> {
+        match self {
+            #[cfg(feature = "api-key")]
+            GateKeeperError::ApiKey(e) => Some(e.challenge()),
             #[cfg(feature = "authentication")]
-            GateKeeperError::Authentication(e) => {
-                tracing::error!("{e:?}");
-                (StatusCode::UNAUTHORIZED, "Error authenticating user").into_response()
+            GateKeeperError::Authentication(e) => e.challenge(),
+            #[cfg(feature = "external-jwt")]
+            GateKeeperError::ExternalJwt(e) => Some(e.challenge()),
+            #[cfg(feature = "jwks")]
+            GateKeeperError::Jwks(e) => e.challenge(),
+            GateKeeperError::Token(e) => e.challenge(),
+            _ => None,
+        }
+    }
+
+    /// Render the error as configured by `config`
+    ///
+    /// [`IntoResponse`] renders using the default [`ErrorConfig`], the
+    /// [`render_errors`](crate::problem::render_errors) middleware renders
+    /// again using the config in its state.
+    pub fn render(&self, config: &ErrorConfig) -> Response {
+        let kind = self.kind();
+        let (code, title, detail) = (self.code(), self.title(), self.detail());
+        let internal = self.to_string();
+        let challenge = self.challenge();
+        let mut response = self.legacy_response();
+
+        if let Some(challenge) = challenge {
+            challenge.append_to(config.challenge(), response.headers_mut());
+        }
+
+        let details = ErrorDetails {
+            kind,
+            code,
+            status: response.status(),
+            title,
+            detail,
+            internal,
+        };
+        let format = config.format();
+        // RFC 6749 defines the error format of the OAuth2 endpoints
+        #[cfg(feature = "oauth2")]
+        let format = match kind {
+            ErrorKind::OAuth => ErrorFormat::Legacy,
+            _ => format,
+        };
+        let mut response = match format {
+            ErrorFormat::Legacy => response,
+            ErrorFormat::Problem => Problem {
+                detail: details.detail.clone(),
+                ..Problem::new(code, title, details.status)
             }
+            .render_into(response),
+        };
+
+        response.extensions_mut().insert(details);
+
+        match config.renderer() {
+            Some(renderer) => crate::problem::render_with(renderer, response),
+            None => response,
+        }
+    }
+
+    /// Render the error as done by previous versions
+    ///
+    /// Errors added since are rendered as their title in plain text.
+    fn legacy_response(&self) -> Response {
+        match self {
+            #[cfg(feature = "api-key")]
+            GateKeeperError::ApiKey(e) => self.plain_response(e.status()),
+            #[cfg(feature = "authentication")]
+            GateKeeperError::Authentication(e) => self.plain_response(e.status()),
+            #[cfg(feature = "basic")]
+            GateKeeperError::Basic(e) => (
+                [(WWW_AUTHENTICATE, e.challenge())],
+                self.plain_response(e.status()),
+            )
+                .into_response(),
+            #[cfg(feature = "csrf")]
+            GateKeeperError::Csrf(e) => self.plain_response(e.status()),
             #[cfg(feature = "authorization")]
-            GateKeeperError::Authorization(e) => {
-                tracing::error!("{e:?}");
+            GateKeeperError::Authorization(_) => {
                 (StatusCode::FORBIDDEN, "Error authorizing user").into_response()
             }
+            #[cfg(feature = "mfa")]
+            GateKeeperError::Mfa(e) => self.plain_response(e.status()),
+            #[cfg(feature = "oauth2")]
+            GateKeeperError::OAuth(e) => e.legacy_response(),
+            #[cfg(feature = "oidc")]
+            GateKeeperError::Oidc(e) => self.plain_response(e.status()),
+            #[cfg(feature = "password")]
+            GateKeeperError::Password(_) => self.plain_response(StatusCode::INTERNAL_SERVER_ERROR),
+            #[cfg(feature = "session")]
+            GateKeeperError::Session(e) => self.plain_response(e.status()),
             #[cfg(feature = "verification")]
-            GateKeeperError::Verification(e) => {
-                tracing::error!("{e:?}");
+            GateKeeperError::Verification(_) => {
                 (StatusCode::FORBIDDEN, "Error verifying token").into_response()
             }
-            GateKeeperError::ParseInt(e) => {
-                tracing::error!("Int parsing error: {:?}", e);
+            #[cfg(feature = "external-jwt")]
+            GateKeeperError::ExternalJwt(e) => self.plain_response(e.status()),
+            #[cfg(feature = "jwks")]
+            GateKeeperError::Jwks(e) => self.plain_response(e.status()),
+            GateKeeperError::ParseInt(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Error parsing value").into_response()
             }
-            GateKeeperError::EnvVar(e) => {
-                tracing::error!("Env var error: {:?}", e);
+            GateKeeperError::EnvVar(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Error reading env var").into_response()
             }
             GateKeeperError::Token(e) => {
-                tracing::error!("Token error: {:?}", e);
-                (StatusCode::UNAUTHORIZED, e).into_response()
+                (StatusCode::UNAUTHORIZED, e.legacy_response()).into_response()
+            }
+            GateKeeperError::Task(_) => self.plain_response(StatusCode::INTERNAL_SERVER_ERROR),
+            GateKeeperError::RateLimited(retry_after) => {
+                // Round up, so clients never retry too early
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+                (
+                    [(RETRY_AFTER, seconds.to_string())],
+                    self.plain_response(StatusCode::TOO_MANY_REQUESTS),
+                )
+                    .into_response()
             }
         }
     }
+
+    fn plain_response(&self, status: StatusCode) -> Response {
+        (status, self.title()).into_response()
+    }
+}
+
+/// Error a response was rendered from, so middleware can render it again
+#[derive(Clone)]
+pub(crate) struct RenderedError(pub(crate) Arc<GateKeeperError>);
+
+impl IntoResponse for GateKeeperError {
+    fn into_response(self) -> Response {
+        tracing::error!("{self:?}");
+
+        let error = Arc::new(self);
+        let mut response = error.render(&ErrorConfig::default());
+
+        response.extensions_mut().insert(RenderedError(error));
+        response
+    }
 }
 
 #[cfg(test)]
@@ -61,6 +390,7 @@ mod tests {
     use super::*;
     use crate::tokens::TokenError;
     use crate::ErrorResponse;
+    use axum::http::header::CONTENT_TYPE;
     use http_body_util::BodyExt;
 
     #[tokio::test]
@@ -70,7 +400,7 @@ mod tests {
             .message("foo => bar".to_string())
             .build();
         let e = GateKeeperError::Token(TokenError::DecodeHeader(error));
-        let response = e.into_response();
+        let response = e.render(&ErrorConfig::build().format(ErrorFormat::Legacy).build());
         let status = response.status();
         let body = response.into_body().collect().await?.to_bytes();
         let body = serde_json::from_slice::<serde_json::Value>(&body)?;
@@ -84,4 +414,38 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_problem() -> anyhow::Result<()> {
+        let error = ErrorResponse::build()
+            .message("Couldn't decode token claims".to_string())
+            .build();
+        let response = GateKeeperError::Token(TokenError::Decode(error)).into_response();
+        let status = response.status();
+        let content_type = response.headers()[CONTENT_TYPE].clone();
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+        let expected = serde_json::json!({
+            "type": "urn:axum-gatekeeper:error:invalid_token",
+            "title": "Invalid token",
+            "status": 401,
+            "code": "invalid_token",
+            "detail": "Couldn't decode token claims"
+        });
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(content_type, crate::problem::PROBLEM_JSON);
+        assert_eq!(body, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rate_limited_error() {
+        let e = GateKeeperError::RateLimited(std::time::Duration::from_millis(1500));
+        let response = e.into_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "2");
+    }
 }
@@ -0,0 +1,119 @@
+use crate::authentication::{AuthenticatedSubject, AuthenticationMethod};
+use crate::error::GateKeeperError;
+use crate::tokens::Acr;
+use crate::GateKeeperResult;
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
+use axum::http::request::Parts;
+use serde_json::{Map, Value};
+use std::convert::Infallible;
+
+/// Verified claims of an external token
+///
+/// Inserted into the request's extensions next to the [`AuthenticatedSubject`],
+/// so handlers can read issuer specific claims like roles or groups.
+#[derive(Debug, Clone)]
+pub struct ExternalClaims {
+    pub issuer: String,
+    pub claims: Map<String, Value>,
+}
+
+impl ExternalClaims {
+    /// Return the `sub` claim
+    pub fn subject(&self) -> Option<&str> {
+        self.claims.get("sub").and_then(Value::as_str)
+    }
+
+    /// Return claim `name` as unsigned integer, e.g. a timestamp
+    pub fn get_u64(&self, name: &str) -> Option<u64> {
+        self.claims.get(name).and_then(Value::as_u64)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ExternalClaims {
+    type Rejection = GateKeeperError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or_else(|| crate::authentication::AuthenticationError::Unauthenticated.into())
+    }
+}
+
+impl<S: Send + Sync> OptionalFromRequestParts<S> for ExternalClaims {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned())
+    }
+}
+
+/// Map the claims of an external token to the crate's subject type
+#[async_trait::async_trait]
+pub trait ClaimsMapper: Send + Sync {
+    /// Return the subject for `claims`, e.g. by looking up the local user
+    /// linked to the issuer's `sub`
+    ///
+    /// Returning `None` rejects the token.
+    async fn map_claims(
+        &self,
+        claims: &ExternalClaims,
+    ) -> GateKeeperResult<Option<AuthenticatedSubject>>;
+}
+
+#[async_trait::async_trait]
+impl<T: ClaimsMapper + ?Sized> ClaimsMapper for std::sync::Arc<T> {
+    async fn map_claims(
+        &self,
+        claims: &ExternalClaims,
+    ) -> GateKeeperResult<Option<AuthenticatedSubject>> {
+        self.as_ref().map_claims(claims).await
+    }
+}
+
+/// [`ClaimsMapper`] for issuers using the crate's user ids as `sub`
+///
+/// `auth_time` falls back to `iat`. The `acr` is [`Acr::Mfa`] if the `acr`
+/// claim is `mfa` or `amr` proves multiple factors, see [`Acr::from_amr`],
+/// otherwise there is none.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UuidClaimsMapper;
+
+#[async_trait::async_trait]
+impl ClaimsMapper for UuidClaimsMapper {
+    async fn map_claims(
+        &self,
+        claims: &ExternalClaims,
+    ) -> GateKeeperResult<Option<AuthenticatedSubject>> {
+        let Some(id) = claims.subject().and_then(|sub| sub.parse().ok()) else {
+            return Ok(None);
+        };
+        let auth_time = claims
+            .get_u64("auth_time")
+            .or_else(|| claims.get_u64("iat"))
+            .unwrap_or_default();
+
+        Ok(Some(AuthenticatedSubject {
+            id,
+            method: AuthenticationMethod::ExternalJwt,
+            auth_time: auth_time as usize,
+            acr: external_acr(claims),
+            claims: None,
+        }))
+    }
+}
+
+fn external_acr(claims: &ExternalClaims) -> Option<Acr> {
+    let acr = claims.claims.get("acr").and_then(Value::as_str) == Some("mfa");
+    let amr = claims
+        .claims
+        .get("amr")
+        .and_then(Value::as_array)
+        .is_some_and(|amr| Acr::from_amr(amr.iter().filter_map(Value::as_str)) == Acr::Mfa);
+
+    (acr || amr).then_some(Acr::Mfa)
+}
@@ -0,0 +1,45 @@
+use crate::challenge::{BearerError, Challenge};
+use crate::error::GateKeeperError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExternalJwtError {
+    #[error("Token issuer {0:?} isn't trusted")]
+    UntrustedIssuer(Option<String>),
+    #[error("No subject for external identity {0}")]
+    UnknownSubject(String),
+}
+
+impl ExternalJwtError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        "invalid_token"
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        "Invalid token"
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+
+    /// Return the `WWW-Authenticate` challenge to respond with
+    pub fn challenge(&self) -> Challenge {
+        let description = match self {
+            ExternalJwtError::UntrustedIssuer(_) => "Untrusted token issuer",
+            ExternalJwtError::UnknownSubject(_) => "Unknown token subject",
+        };
+
+        Challenge::new(BearerError::InvalidToken, description)
+    }
+}
+
+impl IntoResponse for ExternalJwtError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
@@ -0,0 +1,90 @@
+use crate::external_jwt::{ClaimsMapper, UuidClaimsMapper};
+use crate::jwks::KeySet;
+use std::sync::Arc;
+
+/// Issuer whose tokens are accepted, identified by the `iss` claim
+pub struct TrustedIssuer {
+    issuer: String,
+    audiences: Vec<String>,
+    keys: KeySet,
+    mapper: Arc<dyn ClaimsMapper>,
+    leeway: u64,
+}
+
+impl TrustedIssuer {
+    /// Create a builder for TrustedIssuer accepting tokens of `issuer` meant
+    /// for `audience` and signed by one of `keys`
+    pub fn build(issuer: &str, audience: &str, keys: KeySet) -> TrustedIssuerBuilder {
+        TrustedIssuerBuilder {
+            issuer: issuer.to_string(),
+            audiences: vec![audience.to_string()],
+            keys,
+            mapper: None,
+            leeway: None,
+        }
+    }
+
+    /// Return the issuer's `iss` value
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// Return the accepted audiences
+    pub fn audiences(&self) -> &[String] {
+        &self.audiences
+    }
+
+    /// Return the issuer's key set
+    pub fn keys(&self) -> &KeySet {
+        &self.keys
+    }
+
+    /// Return the claims mapper
+    pub fn mapper(&self) -> &dyn ClaimsMapper {
+        self.mapper.as_ref()
+    }
+
+    /// Return the allowed clock skew in seconds
+    pub fn leeway(&self) -> u64 {
+        self.leeway
+    }
+}
+
+pub struct TrustedIssuerBuilder {
+    issuer: String,
+    audiences: Vec<String>,
+    keys: KeySet,
+    mapper: Option<Arc<dyn ClaimsMapper>>,
+    leeway: Option<u64>,
+}
+
+impl TrustedIssuerBuilder {
+    /// Accept tokens meant for `audience` as well
+    pub fn audience(mut self, audience: &str) -> Self {
+        self.audiences.push(audience.to_string());
+        self
+    }
+
+    /// Set field `mapper`, defaults to [`UuidClaimsMapper`]
+    pub fn mapper(mut self, mapper: impl ClaimsMapper + 'static) -> Self {
+        self.mapper = Some(Arc::new(mapper));
+        self
+    }
+
+    /// Set field `leeway` in seconds, defaults to 60
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.leeway = Some(leeway);
+        self
+    }
+
+    /// Actually create the TrustedIssuer
+    pub fn build(self) -> TrustedIssuer {
+        TrustedIssuer {
+            issuer: self.issuer,
+            audiences: self.audiences,
+            keys: self.keys,
+            mapper: self.mapper.unwrap_or_else(|| Arc::new(UuidClaimsMapper)),
+            leeway: self.leeway.unwrap_or(60),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! Module containing verification of access tokens issued by third parties,
+//! e.g. a corporate identity provider
+//!
+//! Each [`TrustedIssuer`] has its own audience, key set and claims mapping.
+//! Once an [`ExternalJwtVerifier`] is set on the gatekeeper, the
+//! `authenticate_user` middleware accepts bearer tokens carrying the `iss`
+//! claim of a trusted issuer next to the crate's own tokens.
+//!
+//! Only available on feature `external-jwt`
+mod claims;
+mod error;
+mod issuer;
+mod verifier;
+
+pub use claims::*;
+pub use error::*;
+pub use issuer::*;
+pub use verifier::*;
@@ -0,0 +1,211 @@
+use crate::authentication::AuthenticatedSubject;
+use crate::external_jwt::{ExternalClaims, ExternalJwtError, TrustedIssuer};
+use crate::GateKeeperResult;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{Algorithm, Validation};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Verifier for tokens of several trusted issuers, selected by `iss`
+#[derive(Default)]
+pub struct ExternalJwtVerifier {
+    issuers: HashMap<String, TrustedIssuer>,
+}
+
+impl ExternalJwtVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept tokens of `issuer`, replacing an issuer with the same `iss`
+    pub fn trust(mut self, issuer: TrustedIssuer) -> Self {
+        self.issuers.insert(issuer.issuer().to_string(), issuer);
+        self
+    }
+
+    /// Read the `iss` claim of `encoded` without verifying it
+    ///
+    /// Only used to select the issuer to verify the token with. The crate's
+    /// own tokens don't have an `iss` claim.
+    pub fn issuer_of(encoded: &str) -> Option<String> {
+        let payload = encoded.split('.').nth(1)?;
+        let payload = BASE64_URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let claims = serde_json::from_slice::<Map<String, Value>>(&payload).ok()?;
+
+        claims.get("iss")?.as_str().map(str::to_string)
+    }
+
+    /// Verify `encoded` using the trusted issuer named in its `iss` claim and
+    /// map its claims to a subject
+    pub async fn verify(
+        &self,
+        encoded: &str,
+    ) -> GateKeeperResult<(AuthenticatedSubject, ExternalClaims)> {
+        let iss = Self::issuer_of(encoded);
+        let issuer = iss
+            .as_ref()
+            .and_then(|iss| self.issuers.get(iss))
+            .ok_or(ExternalJwtError::UntrustedIssuer(iss.clone()))?;
+        let mut validation = Validation::new(Algorithm::RS256);
+
+        validation.set_issuer(&[issuer.issuer()]);
+        validation.set_audience(issuer.audiences());
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        validation.leeway = issuer.leeway();
+
+        let claims = ExternalClaims {
+            issuer: issuer.issuer().to_string(),
+            claims: issuer
+                .keys()
+                .decode::<Map<String, Value>>(encoded, &validation)
+                .await?
+                .claims,
+        };
+        let subject = issuer.mapper().map_claims(&claims).await?.ok_or_else(|| {
+            ExternalJwtError::UnknownSubject(claims.subject().unwrap_or_default().to_string())
+        })?;
+
+        Ok((subject, claims))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExternalJwtVerifier;
+    use crate::authentication::{AuthenticatedSubject, AuthenticationMethod};
+    use crate::external_jwt::{ClaimsMapper, ExternalClaims, TrustedIssuer};
+    use crate::jwks::KeySet;
+    use crate::test_support::{test_jwks, test_rsa_key};
+    use crate::tokens::Acr;
+    use crate::GateKeeperResult;
+    use jsonwebtoken::jwk::JwkSet;
+    use jsonwebtoken::{Algorithm, Header};
+    use serde_json::{json, Value};
+
+    fn sign(claims: Value) -> anyhow::Result<String> {
+        let mut header = Header::new(Algorithm::RS256);
+
+        header.kid = Some("test-key".to_string());
+
+        Ok(jsonwebtoken::encode(&header, &claims, &test_rsa_key())?)
+    }
+
+    fn claims(iss: &str, aud: &str, sub: &str) -> Value {
+        let now = chrono::Utc::now().timestamp();
+
+        json!({"iss": iss, "aud": aud, "sub": sub, "iat": now, "exp": now + 60})
+    }
+
+    /// Map every subject to a fixed id, remembering the issuer's roles
+    struct FixedMapper(uuid::Uuid);
+
+    #[async_trait::async_trait]
+    impl ClaimsMapper for FixedMapper {
+        async fn map_claims(
+            &self,
+            claims: &ExternalClaims,
+        ) -> GateKeeperResult<Option<AuthenticatedSubject>> {
+            Ok(claims.claims.get("roles").map(|_| AuthenticatedSubject {
+                id: self.0,
+                method: AuthenticationMethod::ExternalJwt,
+                auth_time: 0,
+                acr: None,
+                claims: None,
+            }))
+        }
+    }
+
+    fn verifier(fixed: uuid::Uuid) -> ExternalJwtVerifier {
+        ExternalJwtVerifier::new()
+            .trust(
+                TrustedIssuer::build(
+                    "https://corp.example.com",
+                    "api",
+                    KeySet::from_jwks(test_jwks()),
+                )
+                .audience("legacy-api")
+                .build(),
+            )
+            .trust(
+                TrustedIssuer::build(
+                    "https://partner.example.com",
+                    "api",
+                    KeySet::from_jwks(test_jwks()),
+                )
+                .mapper(FixedMapper(fixed))
+                .build(),
+            )
+            .trust(
+                TrustedIssuer::build(
+                    "https://empty.example.com",
+                    "api",
+                    KeySet::from_jwks(JwkSet { keys: Vec::new() }),
+                )
+                .build(),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_verify() -> anyhow::Result<()> {
+        let id = uuid::Uuid::new_v4();
+        let fixed = uuid::Uuid::new_v4();
+        let verifier = verifier(fixed);
+        let mut corp = claims("https://corp.example.com", "legacy-api", &id.to_string());
+
+        corp["amr"] = json!(["pwd", "hwk"]);
+
+        let (subject, external) = verifier.verify(&sign(corp)?).await?;
+
+        assert_eq!(subject.id, id);
+        assert_eq!(subject.method, AuthenticationMethod::ExternalJwt);
+        assert_eq!(subject.acr, Some(Acr::Mfa));
+        assert_eq!(external.issuer, "https://corp.example.com");
+
+        let mut partner = claims("https://partner.example.com", "api", "partner-user");
+
+        partner["roles"] = json!(["admin"]);
+
+        let (subject, _) = verifier.verify(&sign(partner)?).await?;
+
+        assert_eq!(subject.id, fixed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejected() -> anyhow::Result<()> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let verifier = verifier(uuid::Uuid::new_v4());
+        let mut expired = claims("https://corp.example.com", "api", &id);
+
+        expired["exp"] = json!(chrono::Utc::now().timestamp() - 600);
+
+        for claims in [
+            // Untrusted issuer, wrong audience, unknown key, unmappable subject
+            claims("https://evil.example.com", "api", &id),
+            claims("https://corp.example.com", "other", &id),
+            claims("https://empty.example.com", "api", &id),
+            claims("https://corp.example.com", "api", "not-a-uuid"),
+            claims("https://partner.example.com", "api", "partner-user"),
+            expired,
+        ] {
+            assert!(verifier.verify(&sign(claims)?).await.is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_issuer_of() -> anyhow::Result<()> {
+        let encoded = sign(claims("https://corp.example.com", "api", "alice"))?;
+
+        assert_eq!(
+            ExternalJwtVerifier::issuer_of(&encoded).as_deref(),
+            Some("https://corp.example.com")
+        );
+        assert_eq!(ExternalJwtVerifier::issuer_of("foo.bar.baz"), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+use crate::authentication::{MemoryRevocationStore, RevocationStore, UserRepository};
+use crate::error::TokenError;
+use crate::model::GateKeeperModel;
+use crate::rate_limit::{RateLimitKey, RateLimiter};
+use crate::tokens::{Token, TokenCodec, TokenService};
+use crate::GateKeeperResult;
+use std::future::Future;
+use std::sync::Arc;
+
+#[cfg(feature = "api-key")]
+use crate::api_key::{ApiKeyStore, MemoryApiKeyStore};
+#[cfg(feature = "external-jwt")]
+use crate::external_jwt::ExternalJwtVerifier;
+#[cfg(feature = "mfa")]
+use crate::mfa::{MemoryMfaStore, MfaStore};
+#[cfg(feature = "password")]
+use crate::password::PasswordHasher;
+#[cfg(feature = "token-cache")]
+use crate::tokens::TokenCache;
+
+/// Shared state used by the gatekeeper's handlers and middleware
+///
+/// Cheap to clone, so it can be used directly as axum router state or be
+/// extracted from it using `FromRef`.
+pub struct GateKeeper<U> {
+    users: Arc<dyn UserRepository<User = U>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    revocation_store: Arc<dyn RevocationStore>,
+    token_codec: Arc<TokenCodec>,
+    #[cfg(feature = "password")]
+    hasher: Arc<PasswordHasher>,
+    #[cfg(feature = "password")]
+    dummy_hash: Arc<str>,
+    #[cfg(feature = "mfa")]
+    mfa_store: Arc<dyn MfaStore>,
+    #[cfg(feature = "api-key")]
+    api_key_store: Arc<dyn ApiKeyStore>,
+    #[cfg(feature = "external-jwt")]
+    external_jwt: Option<Arc<ExternalJwtVerifier>>,
+    #[cfg(feature = "token-cache")]
+    token_cache: Option<Arc<TokenCache>>,
+}
+
+impl<U: GateKeeperModel + 'static> GateKeeper<U> {
+    /// Create a builder for GateKeeper using `users` to look up users
+    pub fn build(users: impl UserRepository<User = U> + 'static) -> GateKeeperBuilder<U> {
+        GateKeeperBuilder {
+            users: Arc::new(users),
+            rate_limiter: None,
+            revocation_store: None,
+            token_codec: None,
+            #[cfg(feature = "password")]
+            hasher: None,
+            #[cfg(feature = "mfa")]
+            mfa_store: None,
+            #[cfg(feature = "api-key")]
+            api_key_store: None,
+            #[cfg(feature = "external-jwt")]
+            external_jwt: None,
+            #[cfg(feature = "token-cache")]
+            token_cache: None,
+        }
+    }
+
+    /// Return the user repository
+    pub fn users(&self) -> &dyn UserRepository<User = U> {
+        self.users.as_ref()
+    }
+
+    /// Return the rate limiter, if brute-force protection is enabled
+    pub fn rate_limiter(&self) -> Option<&Arc<RateLimiter>> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Return the store of revoked tokens
+    pub fn revocation_store(&self) -> &dyn RevocationStore {
+        self.revocation_store.as_ref()
+    }
+
+    /// Revoke the token having `jti` until it expires at `exp` and return
+    /// whether it was revoked already
+    ///
+    /// Prefer this to revoking using the [`RevocationStore`] directly, as it
+    /// drops the token from the token cache as well, and keeps the token
+    /// revoked for the codec's leeway, during which it's still accepted.
+    pub async fn revoke(&self, jti: &str, exp: usize) -> GateKeeperResult<bool> {
+        let exp = exp.saturating_add(self.token_codec.leeway() as usize);
+        let revoked = self.revocation_store.revoke(jti, exp).await?;
+
+        #[cfg(feature = "token-cache")]
+        if let Some(cache) = &self.token_cache {
+            cache.invalidate(jti);
+        }
+
+        Ok(revoked)
+    }
+
+    /// Revoke `token` until it expires, e.g. a refresh token after rotating it
+    ///
+    /// Fails for tokens without `jti`, as they can't be revoked, and with
+    /// [`TokenError::Revoked`] if `token` was revoked already, so single-use
+    /// tokens are only redeemed once even by concurrent requests.
+    pub async fn revoke_token(&self, token: &impl Token) -> GateKeeperResult<()> {
+        let claims = token.get_claims();
+        let jti = claims.jti.as_deref().ok_or_else(|| {
+            tracing::error!("Token of subject {} has no jti", claims.sub);
+
+            TokenError::Malformed("Token has no jti".to_string())
+        })?;
+
+        match self.revoke(jti, claims.exp).await? {
+            true => Err(TokenError::Revoked.into()),
+            false => Ok(()),
+        }
+    }
+
+    /// Return the codec tokens are encoded and decoded with
+    pub fn token_codec(&self) -> &Arc<TokenCodec> {
+        &self.token_codec
+    }
+
+    /// Return the cache of verified tokens, if enabled
+    #[cfg(feature = "token-cache")]
+    pub fn token_cache(&self) -> Option<&Arc<TokenCache>> {
+        self.token_cache.as_ref()
+    }
+
+    /// Return the password hasher
+    #[cfg(feature = "password")]
+    pub fn hasher(&self) -> &Arc<PasswordHasher> {
+        &self.hasher
+    }
+
+    /// Return a hash to verify against if no user was found, so failed logins
+    /// take the same time whether or not the user exists
+    #[cfg(feature = "password")]
+    pub(crate) fn dummy_hash(&self) -> &Arc<str> {
+        &self.dummy_hash
+    }
+
+    /// Return the store for used TOTP steps and recovery codes
+    #[cfg(feature = "mfa")]
+    pub fn mfa_store(&self) -> &dyn MfaStore {
+        self.mfa_store.as_ref()
+    }
+
+    /// Return the store API keys are looked up in
+    #[cfg(feature = "api-key")]
+    pub fn api_key_store(&self) -> &dyn ApiKeyStore {
+        self.api_key_store.as_ref()
+    }
+
+    /// Return the verifier for tokens of trusted third party issuers, if set
+    #[cfg(feature = "external-jwt")]
+    pub fn external_jwt(&self) -> Option<&ExternalJwtVerifier> {
+        self.external_jwt.as_deref()
+    }
+
+    /// Run `attempt` through the rate limiter using `keys`, if one is set
+    pub async fn attempt<T>(
+        &self,
+        keys: &[RateLimitKey],
+        attempt: impl Future<Output = GateKeeperResult<T>>,
+    ) -> GateKeeperResult<T> {
+        match &self.rate_limiter {
+            Some(rate_limiter) => rate_limiter.attempt(keys, attempt).await,
+            None => attempt.await,
+        }
+    }
+
+    /// Decode `encoded` using the secret of the user referenced in its `kid` header
+    ///
+    /// Fails for tokens revoked in the [`RevocationStore`]. Uses the token
+    /// cache, if enabled, to skip verifying the signature of known tokens.
+    pub async fn decode_for_user<T: Token>(&self, encoded: &str) -> GateKeeperResult<(U, T)> {
+        let id = TokenService::get_subject_from_encoded(encoded)?;
+        let user = self.users.find_by_id(id).await?.ok_or_else(|| {
+            tracing::error!("No user found for token subject {id}");
+
+            TokenError::UnknownKid(Some(id.to_string()))
+        })?;
+        #[cfg(feature = "token-cache")]
+        let token = match &self.token_cache {
+            Some(cache) => cache.decode::<T>(encoded, &self.token_codec, user.secret())?,
+            None => T::decode(encoded, &self.token_codec, user.secret())?,
+        };
+        #[cfg(not(feature = "token-cache"))]
+        let token = T::decode(encoded, &self.token_codec, user.secret())?;
+
+        if let Some(jti) = &token.get_claims().jti {
+            #[cfg(feature = "metrics")]
+            let started = std::time::Instant::now();
+            let revoked = self.revocation_store.is_revoked(jti).await;
+
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_revocation_lookup(started, &revoked);
+
+            if revoked? {
+                return Err(TokenError::Revoked.into());
+            }
+        }
+
+        Ok((user, token))
+    }
+}
+
+impl<U> Clone for GateKeeper<U> {
+    fn clone(&self) -> Self {
+        Self {
+            users: self.users.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            revocation_store: self.revocation_store.clone(),
+            token_codec: self.token_codec.clone(),
+            #[cfg(feature = "password")]
+            hasher: self.hasher.clone(),
+            #[cfg(feature = "password")]
+            dummy_hash: self.dummy_hash.clone(),
+            #[cfg(feature = "mfa")]
+            mfa_store: self.mfa_store.clone(),
+            #[cfg(feature = "api-key")]
+            api_key_store: self.api_key_store.clone(),
+            #[cfg(feature = "external-jwt")]
+            external_jwt: self.external_jwt.clone(),
+            #[cfg(feature = "token-cache")]
+            token_cache: self.token_cache.clone(),
+        }
+    }
+}
+
+pub struct GateKeeperBuilder<U> {
+    users: Arc<dyn UserRepository<User = U>>,
+    rate_limiter: Option<RateLimiter>,
+    revocation_store: Option<Arc<dyn RevocationStore>>,
+    token_codec: Option<TokenCodec>,
+    #[cfg(feature = "password")]
+    hasher: Option<PasswordHasher>,
+    #[cfg(feature = "mfa")]
+    mfa_store: Option<Arc<dyn MfaStore>>,
+    #[cfg(feature = "api-key")]
+    api_key_store: Option<Arc<dyn ApiKeyStore>>,
+    #[cfg(feature = "external-jwt")]
+    external_jwt: Option<ExternalJwtVerifier>,
+    #[cfg(feature = "token-cache")]
+    token_cache: Option<TokenCache>,
+}
+
+impl<U> GateKeeperBuilder<U> {
+    /// Set field `rate_limiter`, enabling brute-force protection
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Set field `revocation_store`, defaults to [`MemoryRevocationStore`]
+    pub fn revocation_store(mut self, revocation_store: impl RevocationStore + 'static) -> Self {
+        self.revocation_store = Some(Arc::new(revocation_store));
+        self
+    }
+
+    /// Set field `token_codec`, defaults to [`TokenCodec::default`]
+    pub fn token_codec(mut self, token_codec: TokenCodec) -> Self {
+        self.token_codec = Some(token_codec);
+        self
+    }
+
+    /// Set field `hasher`
+    #[cfg(feature = "password")]
+    pub fn hasher(mut self, hasher: PasswordHasher) -> Self {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// Set field `mfa_store`, defaults to [`MemoryMfaStore`]
+    #[cfg(feature = "mfa")]
+    pub fn mfa_store(mut self, mfa_store: impl MfaStore + 'static) -> Self {
+        self.mfa_store = Some(Arc::new(mfa_store));
+        self
+    }
+
+    /// Set field `api_key_store`, defaults to an empty [`MemoryApiKeyStore`]
+    #[cfg(feature = "api-key")]
+    pub fn api_key_store(mut self, api_key_store: impl ApiKeyStore + 'static) -> Self {
+        self.api_key_store = Some(Arc::new(api_key_store));
+        self
+    }
+
+    /// Set field `external_jwt`, making the authentication middleware accept
+    /// tokens of trusted third party issuers
+    #[cfg(feature = "external-jwt")]
+    pub fn external_jwt(mut self, external_jwt: ExternalJwtVerifier) -> Self {
+        self.external_jwt = Some(external_jwt);
+        self
+    }
+
+    /// Set field `token_cache`, caching verified tokens
+    #[cfg(feature = "token-cache")]
+    pub fn token_cache(mut self, token_cache: TokenCache) -> Self {
+        self.token_cache = Some(token_cache);
+        self
+    }
+
+    /// Actually create the gatekeeper
+    pub fn build(self) -> GateKeeperResult<GateKeeper<U>> {
+        #[cfg(feature = "password")]
+        let hasher = self.hasher.unwrap_or_default();
+        #[cfg(feature = "password")]
+        let dummy_hash = hasher.hash(&uuid::Uuid::new_v4().to_string())?;
+
+        Ok(GateKeeper {
+            users: self.users,
+            rate_limiter: self.rate_limiter.map(Arc::new),
+            revocation_store: self
+                .revocation_store
+                .unwrap_or_else(|| Arc::new(MemoryRevocationStore::new())),
+            token_codec: Arc::new(self.token_codec.unwrap_or_default()),
+            #[cfg(feature = "password")]
+            hasher: Arc::new(hasher),
+            #[cfg(feature = "password")]
+            dummy_hash: dummy_hash.into(),
+            #[cfg(feature = "mfa")]
+            mfa_store: self
+                .mfa_store
+                .unwrap_or_else(|| Arc::new(MemoryMfaStore::new())),
+            #[cfg(feature = "api-key")]
+            api_key_store: self
+                .api_key_store
+                .unwrap_or_else(|| Arc::new(MemoryApiKeyStore::new())),
+            #[cfg(feature = "external-jwt")]
+            external_jwt: self.external_jwt.map(Arc::new),
+            #[cfg(feature = "token-cache")]
+            token_cache: self.token_cache.map(Arc::new),
+        })
+    }
+}
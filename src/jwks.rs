@@ -0,0 +1,292 @@
+//! Module containing JSON Web Key Sets used to verify tokens signed by third
+//! parties, e.g. OpenID Connect providers
+//!
+//! Only available on feature `jwks`
+use crate::challenge::{BearerError, Challenge};
+use crate::error::{GateKeeperError, TokenError};
+use crate::GateKeeperResult;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Header, TokenData, Validation};
+use serde::de::DeserializeOwned;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Algorithms accepted for third party tokens
+///
+/// Symmetric algorithms are excluded, as they'd allow anyone knowing the key
+/// to issue tokens.
+pub const ASYMMETRIC_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum JwksError {
+    #[error("Error fetching key set: {0}")]
+    Fetch(#[from] reqwest::Error),
+    #[error("No key found for kid {0:?}")]
+    UnknownKey(Option<String>),
+    #[error("Algorithm {0:?} isn't allowed")]
+    UnsupportedAlgorithm(Algorithm),
+    #[error("Invalid token: {0}")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+}
+
+impl JwksError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            JwksError::Fetch(_) => "key_set_unavailable",
+            JwksError::UnknownKey(_) => "unknown_token_key",
+            JwksError::UnsupportedAlgorithm(_) => "invalid_token_algorithm",
+            JwksError::InvalidToken(e) => TokenError::from_jwt_error(e).code(),
+        }
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            JwksError::Fetch(_) => "Error fetching keys",
+            JwksError::UnknownKey(_) => "Unknown token key",
+            JwksError::UnsupportedAlgorithm(_) => "Invalid token algorithm",
+            JwksError::InvalidToken(e) => TokenError::from_jwt_error(e).title(),
+        }
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            JwksError::Fetch(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    /// Return the `WWW-Authenticate` challenge to respond with, if any
+    pub fn challenge(&self) -> Option<Challenge> {
+        match self {
+            JwksError::Fetch(_) => None,
+            _ => Some(Challenge::new(BearerError::InvalidToken, self.title())),
+        }
+    }
+}
+
+impl IntoResponse for JwksError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
+
+/// Keys of a token issuer, either given statically or fetched from a
+/// `jwks_uri`
+///
+/// Remote key sets are fetched on first use and refetched if a token
+/// references an unknown `kid`, so key rotation works without a restart.
+/// Refetching happens at most once per [`KeySet::MIN_REFRESH_INTERVAL`].
+pub struct KeySet {
+    keys: RwLock<JwkSet>,
+    remote: Option<(reqwest::Client, String)>,
+    last_refresh: Mutex<Option<Instant>>,
+}
+
+impl KeySet {
+    /// Minimum time between two fetches of a remote key set
+    pub const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Create key set from fixed `keys`
+    pub fn from_jwks(keys: JwkSet) -> Self {
+        Self {
+            keys: RwLock::new(keys),
+            remote: None,
+            last_refresh: Mutex::new(None),
+        }
+    }
+
+    /// Create key set fetched from `uri` on first use
+    pub fn remote(uri: &str) -> Self {
+        Self::remote_with_client(reqwest::Client::new(), uri)
+    }
+
+    /// Create key set fetched from `uri` using `client`
+    pub fn remote_with_client(client: reqwest::Client, uri: &str) -> Self {
+        Self {
+            keys: RwLock::new(JwkSet { keys: Vec::new() }),
+            remote: Some((client, uri.to_string())),
+            last_refresh: Mutex::new(None),
+        }
+    }
+
+    /// Refetch a remote key set, unless it was fetched within
+    /// [`KeySet::MIN_REFRESH_INTERVAL`]
+    ///
+    /// Returns whether the keys were refetched.
+    pub async fn refresh(&self) -> GateKeeperResult<bool> {
+        let Some((client, uri)) = &self.remote else {
+            return Ok(false);
+        };
+
+        {
+            let mut last_refresh = self.last_refresh.lock().unwrap_or_else(|e| e.into_inner());
+
+            if last_refresh.is_some_and(|last| last.elapsed() < Self::MIN_REFRESH_INTERVAL) {
+                return Ok(false);
+            }
+
+            *last_refresh = Some(Instant::now());
+        }
+
+        tracing::debug!("Fetching key set from {uri}");
+
+        let keys = client
+            .get(uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(JwksError::Fetch)?
+            .json::<JwkSet>()
+            .await
+            .map_err(JwksError::Fetch)?;
+
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = keys;
+
+        Ok(true)
+    }
+
+    /// Return the key `header` references
+    pub async fn decoding_key(&self, header: &Header) -> GateKeeperResult<DecodingKey> {
+        if let Some(key) = self.find(header)? {
+            return Ok(key);
+        }
+
+        if self.refresh().await? {
+            if let Some(key) = self.find(header)? {
+                return Ok(key);
+            }
+        }
+
+        Err(JwksError::UnknownKey(header.kid.clone()).into())
+    }
+
+    /// Verify `encoded` using the key and algorithm from its header
+    ///
+    /// `validation` is used for everything but the algorithm, which has to be
+    /// asymmetric and match the key's algorithm if the key specifies one.
+    pub async fn decode<C: DeserializeOwned>(
+        &self,
+        encoded: &str,
+        validation: &Validation,
+    ) -> GateKeeperResult<TokenData<C>> {
+        let header = jsonwebtoken::decode_header(encoded).map_err(JwksError::InvalidToken)?;
+
+        if !ASYMMETRIC_ALGORITHMS.contains(&header.alg) {
+            return Err(JwksError::UnsupportedAlgorithm(header.alg).into());
+        }
+
+        let key = self.decoding_key(&header).await?;
+        let mut validation = validation.clone();
+
+        validation.algorithms = vec![header.alg];
+
+        Ok(jsonwebtoken::decode::<C>(encoded, &key, &validation)
+            .map_err(JwksError::InvalidToken)?)
+    }
+
+    fn find(&self, header: &Header) -> GateKeeperResult<Option<DecodingKey>> {
+        let keys = self.keys.read().unwrap_or_else(|e| e.into_inner());
+        let jwk = match &header.kid {
+            Some(kid) => keys.find(kid),
+            // Without kid, only an unambiguous key can be used
+            None if keys.keys.len() == 1 => keys.keys.first(),
+            None => None,
+        };
+        let Some(jwk) = jwk else {
+            return Ok(None);
+        };
+
+        if let Some(alg) = jwk.common.key_algorithm {
+            if alg.to_string().parse::<Algorithm>().ok() != Some(header.alg) {
+                return Err(JwksError::UnsupportedAlgorithm(header.alg).into());
+            }
+        }
+
+        Ok(Some(
+            DecodingKey::from_jwk(jwk).map_err(JwksError::InvalidToken)?,
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "authentication"))]
+mod tests {
+    use super::KeySet;
+    use crate::test_support::{test_jwks, test_rsa_key};
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, Validation};
+    use serde_json::json;
+
+    fn sign(header: Header, key: &EncodingKey) -> anyhow::Result<String> {
+        let exp = chrono::Utc::now().timestamp() + 60;
+
+        Ok(jsonwebtoken::encode(
+            &header,
+            &json!({"sub": "alice", "exp": exp}),
+            key,
+        )?)
+    }
+
+    fn rs256(kid: Option<&str>) -> Header {
+        let mut header = Header::new(Algorithm::RS256);
+
+        header.kid = kid.map(str::to_string);
+        header
+    }
+
+    #[tokio::test]
+    async fn test_decode() -> anyhow::Result<()> {
+        let keys = KeySet::from_jwks(test_jwks());
+        let validation = Validation::new(Algorithm::RS256);
+        let encoded = sign(rs256(Some("test-key")), &test_rsa_key())?;
+        let token = keys
+            .decode::<serde_json::Value>(&encoded, &validation)
+            .await?;
+
+        assert_eq!(token.claims["sub"], "alice");
+
+        // A single key may be used without kid
+        let encoded = sign(rs256(None), &test_rsa_key())?;
+
+        assert!(keys
+            .decode::<serde_json::Value>(&encoded, &validation)
+            .await
+            .is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_decode_rejected() -> anyhow::Result<()> {
+        let keys = KeySet::from_jwks(test_jwks());
+        let validation = Validation::new(Algorithm::RS256);
+        let unknown_kid = sign(rs256(Some("other")), &test_rsa_key())?;
+        let symmetric = sign(
+            Header::new(Algorithm::HS256),
+            &EncodingKey::from_secret(b"secret"),
+        )?;
+        let tampered = format!("{}x", sign(rs256(Some("test-key")), &test_rsa_key())?);
+
+        for encoded in [unknown_kid, symmetric, tampered] {
+            assert!(keys
+                .decode::<serde_json::Value>(&encoded, &validation)
+                .await
+                .is_err());
+        }
+
+        Ok(())
+    }
+}
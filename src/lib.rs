@@ -1,16 +1,59 @@
 use axum::http::StatusCode;
 use std::fmt::{Display, Formatter};
 
+#[cfg(feature = "api-key")]
+pub mod api_key;
+#[cfg(feature = "authentication")]
+pub mod audit;
 #[cfg(feature = "authentication")]
 pub mod authentication;
 #[cfg(feature = "authorization")]
 pub mod authorization;
+#[cfg(feature = "basic")]
+pub mod basic;
+pub mod challenge;
+#[cfg(feature = "csrf")]
+pub mod csrf;
 pub mod error;
-mod model;
+#[cfg(feature = "external-jwt")]
+pub mod external_jwt;
+#[cfg(feature = "authentication")]
+mod gatekeeper;
+#[cfg(feature = "jwks")]
+pub mod jwks;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mfa")]
+pub mod mfa;
+pub mod model;
+#[cfg(feature = "oauth2")]
+pub mod oauth2;
+#[cfg(feature = "oidc")]
+pub mod oidc;
+#[cfg(feature = "password")]
+pub mod password;
+pub mod problem;
+#[cfg(feature = "authentication")]
+pub mod rate_limit;
+#[cfg(feature = "session")]
+pub mod session;
+#[cfg(all(test, feature = "authentication"))]
+mod test_support;
 pub mod tokens;
+#[cfg(any(
+    feature = "basic",
+    feature = "csrf",
+    feature = "mfa",
+    feature = "oauth2",
+    feature = "oidc"
+))]
+mod util;
 #[cfg(feature = "verification")]
 pub mod verification;
 
+#[cfg(feature = "authentication")]
+pub use gatekeeper::{GateKeeper, GateKeeperBuilder};
+
 pub type GateKeeperResult<T> = Result<T, error::GateKeeperError>;
 
 #[derive(serde::Deserialize, Debug)]
@@ -24,6 +67,16 @@ impl ErrorResponse {
     pub fn build() -> ErrorResponseBuilder {
         ErrorResponseBuilder::default()
     }
+
+    /// Return the status code describing the error
+    pub fn status_code(&self) -> u16 {
+        self.status_code
+    }
+
+    /// Return the error message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl Display for ErrorResponse {
@@ -0,0 +1,346 @@
+//! Module containing the metrics recorded using the `metrics` crate facade
+//!
+//! Install any `metrics` recorder, e.g. `metrics-exporter-prometheus`, to
+//! collect them. Use [`describe_metrics`] to register their descriptions.
+//! Failures are labeled with their `reason`, the error code also used in
+//! problem details, see [`GateKeeperError::code`].
+//!
+//! Only available on feature `metrics`
+use crate::error::GateKeeperError;
+use crate::GateKeeperResult;
+use metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+use std::time::Instant;
+
+/// Counter of encoded tokens, labeled by `token_type` and `outcome`
+pub const TOKENS_ENCODED: &str = "gatekeeper_tokens_encoded_total";
+/// Histogram of the time spent encoding tokens, labeled by `token_type`
+pub const TOKEN_ENCODE_DURATION: &str = "gatekeeper_token_encode_duration_seconds";
+/// Counter of decoded tokens, labeled by `token_type`, `outcome` and `reason`
+pub const TOKENS_DECODED: &str = "gatekeeper_tokens_decoded_total";
+/// Histogram of the time spent decoding tokens, labeled by `token_type`
+pub const TOKEN_DECODE_DURATION: &str = "gatekeeper_token_decode_duration_seconds";
+/// Counter of token cache lookups, labeled by `token_type` and `result`, one
+/// of `hit` and `miss`
+pub const TOKEN_CACHE_LOOKUPS: &str = "gatekeeper_token_cache_lookups_total";
+/// Counter of middleware decisions, labeled by `middleware`, `outcome` and
+/// `reason`
+pub const MIDDLEWARE_DECISIONS: &str = "gatekeeper_middleware_decisions_total";
+/// Histogram of the time middleware took to decide, labeled by `middleware`
+pub const MIDDLEWARE_DURATION: &str = "gatekeeper_middleware_duration_seconds";
+/// Counter of refresh token rotations, labeled by `outcome` and `reason`
+pub const REFRESH_ROTATIONS: &str = "gatekeeper_refresh_rotations_total";
+/// Histogram of the time refresh token rotations took
+pub const REFRESH_DURATION: &str = "gatekeeper_refresh_duration_seconds";
+/// Counter of revocation store lookups, labeled by `result`, one of
+/// `active`, `revoked` and `error`
+pub const REVOCATION_LOOKUPS: &str = "gatekeeper_revocation_lookups_total";
+/// Histogram of the time revocation store lookups took
+pub const REVOCATION_LOOKUP_DURATION: &str = "gatekeeper_revocation_lookup_duration_seconds";
+
+/// Register units and descriptions of all metrics with the installed recorder
+pub fn describe_metrics() {
+    describe_counter!(TOKENS_ENCODED, Unit::Count, "Tokens encoded");
+    describe_histogram!(
+        TOKEN_ENCODE_DURATION,
+        Unit::Seconds,
+        "Time spent encoding tokens"
+    );
+    describe_counter!(TOKENS_DECODED, Unit::Count, "Tokens decoded");
+    describe_histogram!(
+        TOKEN_DECODE_DURATION,
+        Unit::Seconds,
+        "Time spent decoding tokens"
+    );
+    describe_counter!(
+        TOKEN_CACHE_LOOKUPS,
+        Unit::Count,
+        "Lookups of verified tokens in the token cache"
+    );
+    describe_counter!(
+        MIDDLEWARE_DECISIONS,
+        Unit::Count,
+        "Requests accepted or rejected by middleware"
+    );
+    describe_histogram!(
+        MIDDLEWARE_DURATION,
+        Unit::Seconds,
+        "Time middleware took to accept or reject requests"
+    );
+    describe_counter!(
+        REFRESH_ROTATIONS,
+        Unit::Count,
+        "Refresh tokens exchanged for new token pairs"
+    );
+    describe_histogram!(
+        REFRESH_DURATION,
+        Unit::Seconds,
+        "Time refresh token rotations took"
+    );
+    describe_counter!(
+        REVOCATION_LOOKUPS,
+        Unit::Count,
+        "Lookups of token ids in the revocation store"
+    );
+    describe_histogram!(
+        REVOCATION_LOOKUP_DURATION,
+        Unit::Seconds,
+        "Time revocation store lookups took"
+    );
+}
+
+/// Return the `outcome` label of `result`
+fn outcome<T>(result: &GateKeeperResult<T>) -> &'static str {
+    match result {
+        Ok(_) => "success",
+        Err(_) => "failure",
+    }
+}
+
+/// Return the `reason` label of `result`
+fn reason<T>(result: &GateKeeperResult<T>) -> &'static str {
+    result.as_ref().err().map_or("none", GateKeeperError::code)
+}
+
+/// Record encoding a token of `token_type` started at `started`
+pub(crate) fn record_encode<T>(
+    token_type: &'static str,
+    started: Instant,
+    result: &GateKeeperResult<T>,
+) {
+    histogram!(TOKEN_ENCODE_DURATION, "token_type" => token_type)
+        .record(started.elapsed().as_secs_f64());
+    counter!(TOKENS_ENCODED, "token_type" => token_type, "outcome" => outcome(result)).increment(1);
+}
+
+/// Record decoding a token of `token_type` started at `started`
+pub(crate) fn record_decode<T>(
+    token_type: &'static str,
+    started: Instant,
+    result: &GateKeeperResult<T>,
+) {
+    histogram!(TOKEN_DECODE_DURATION, "token_type" => token_type)
+        .record(started.elapsed().as_secs_f64());
+    counter!(
+        TOKENS_DECODED,
+        "token_type" => token_type,
+        "outcome" => outcome(result),
+        "reason" => reason(result)
+    )
+    .increment(1);
+}
+
+/// Record a lookup of a token of `token_type` in the token cache
+#[cfg(feature = "token-cache")]
+pub(crate) fn record_cache_lookup(token_type: &'static str, hit: bool) {
+    let result = match hit {
+        true => "hit",
+        false => "miss",
+    };
+
+    counter!(TOKEN_CACHE_LOOKUPS, "token_type" => token_type, "result" => result).increment(1);
+}
+
+/// Record the decision of `middleware` started at `started`
+pub(crate) fn record_decision<T>(
+    middleware: &'static str,
+    started: Instant,
+    result: &GateKeeperResult<T>,
+) {
+    histogram!(MIDDLEWARE_DURATION, "middleware" => middleware)
+        .record(started.elapsed().as_secs_f64());
+    counter!(
+        MIDDLEWARE_DECISIONS,
+        "middleware" => middleware,
+        "outcome" => outcome(result),
+        "reason" => reason(result)
+    )
+    .increment(1);
+}
+
+/// Record a refresh token rotation started at `started`
+pub(crate) fn record_refresh<T>(started: Instant, result: &GateKeeperResult<T>) {
+    histogram!(REFRESH_DURATION).record(started.elapsed().as_secs_f64());
+    counter!(
+        REFRESH_ROTATIONS,
+        "outcome" => outcome(result),
+        "reason" => reason(result)
+    )
+    .increment(1);
+}
+
+/// Record a revocation store lookup started at `started`
+pub(crate) fn record_revocation_lookup(started: Instant, result: &GateKeeperResult<bool>) {
+    let result = match result {
+        Ok(true) => "revoked",
+        Ok(false) => "active",
+        Err(_) => "error",
+    };
+
+    histogram!(REVOCATION_LOOKUP_DURATION).record(started.elapsed().as_secs_f64());
+    counter!(REVOCATION_LOOKUPS, "result" => result).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MIDDLEWARE_DECISIONS, REVOCATION_LOOKUPS, TOKENS_DECODED, TOKENS_ENCODED};
+    use crate::authentication::{authenticate_user, AuthenticationToken};
+    use crate::model::GateKeeperModel;
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Token, TokenCodec};
+    use axum::body::Body;
+    use axum::http::header::AUTHORIZATION;
+    use axum::http::Request;
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use metrics::Key;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use tower::ServiceExt;
+
+    /// Return the values of all counters recorded so far
+    fn counters(snapshotter: &Snapshotter) -> Vec<(Key, u64)> {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter_map(|(key, .., value)| match value {
+                DebugValue::Counter(value) => Some((key.key().clone(), value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Return the value of counter `name` having all `labels`
+    fn counter(counters: &[(Key, u64)], name: &str, labels: &[(&str, &str)]) -> u64 {
+        counters
+            .iter()
+            .filter(|(key, _)| {
+                key.name() == name
+                    && labels.iter().all(|(label, value)| {
+                        key.labels()
+                            .any(|actual| actual.key() == *label && actual.value() == *value)
+                    })
+            })
+            .map(|(_, value)| value)
+            .sum()
+    }
+
+    #[test]
+    fn test_token_metrics() -> anyhow::Result<()> {
+        init_env();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let user = TestUser::new("alice", "");
+
+        metrics::with_local_recorder(&recorder, || -> anyhow::Result<()> {
+            let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+
+            AuthenticationToken::decode(
+                token.get_encoded(),
+                &TokenCodec::default(),
+                user.secret(),
+            )?;
+            assert!(AuthenticationToken::decode(
+                token.get_encoded(),
+                &TokenCodec::default(),
+                "wrong"
+            )
+            .is_err());
+
+            Ok(())
+        })?;
+
+        let counters = counters(&snapshotter);
+        let authentication = ("token_type", "authentication");
+
+        assert_eq!(
+            counter(
+                &counters,
+                TOKENS_ENCODED,
+                &[authentication, ("outcome", "success")]
+            ),
+            1
+        );
+        assert_eq!(
+            counter(
+                &counters,
+                TOKENS_DECODED,
+                &[authentication, ("outcome", "success")]
+            ),
+            1
+        );
+        assert_eq!(
+            counter(
+                &counters,
+                TOKENS_DECODED,
+                &[authentication, ("reason", "invalid_token_signature")]
+            ),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_middleware_metrics() -> anyhow::Result<()> {
+        init_env();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let user = TestUser::new("alice", "");
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user])).build()?;
+        let app =
+            Router::new()
+                .route("/", get(|| async { "OK" }))
+                .layer(middleware::from_fn_with_state(
+                    gatekeeper,
+                    authenticate_user::<TestUser>,
+                ));
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let authorization = format!("Bearer {}", token.get_encoded());
+
+                app.clone()
+                    .oneshot(Request::get("/").body(Body::empty())?)
+                    .await?;
+                app.oneshot(
+                    Request::get("/")
+                        .header(AUTHORIZATION, authorization)
+                        .body(Body::empty())?,
+                )
+                .await?;
+
+                anyhow::Ok(())
+            })
+        })?;
+
+        let counters = counters(&snapshotter);
+        let middleware = ("middleware", "authenticate_user");
+
+        assert_eq!(
+            counter(
+                &counters,
+                MIDDLEWARE_DECISIONS,
+                &[middleware, ("outcome", "success")]
+            ),
+            1
+        );
+        assert_eq!(
+            counter(
+                &counters,
+                MIDDLEWARE_DECISIONS,
+                &[middleware, ("reason", "missing_token")]
+            ),
+            1
+        );
+        assert_eq!(
+            counter(&counters, REVOCATION_LOOKUPS, &[("result", "active")]),
+            1
+        );
+
+        Ok(())
+    }
+}
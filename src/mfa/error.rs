@@ -0,0 +1,54 @@
+use crate::error::GateKeeperError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+#[derive(thiserror::Error, Debug)]
+pub enum MfaError {
+    #[error("Invalid or already used MFA code")]
+    InvalidCode,
+    #[error("MFA isn't enabled for user")]
+    NotEnabled,
+    #[error("Invalid TOTP secret: {0}")]
+    InvalidSecret(#[from] data_encoding::DecodeError),
+    #[error("Invalid TOTP configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Malformed MFA request: {0}")]
+    MalformedRequest(String),
+}
+
+impl MfaError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            MfaError::InvalidCode | MfaError::NotEnabled => "invalid_mfa_code",
+            MfaError::InvalidSecret(_) | MfaError::InvalidConfig(_) => "internal_error",
+            MfaError::MalformedRequest(_) => "malformed_mfa_request",
+        }
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            MfaError::InvalidCode | MfaError::NotEnabled => "Invalid MFA code",
+            MfaError::InvalidSecret(_) | MfaError::InvalidConfig(_) => "Error verifying MFA code",
+            MfaError::MalformedRequest(_) => "Malformed MFA request",
+        }
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            MfaError::InvalidCode | MfaError::NotEnabled => StatusCode::UNAUTHORIZED,
+            MfaError::InvalidSecret(_) | MfaError::InvalidConfig(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            MfaError::MalformedRequest(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for MfaError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
@@ -0,0 +1,335 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::error::GateKeeperError;
+use crate::mfa::{MfaError, MfaPendingToken, RecoveryCodes, Totp};
+use crate::password::PasswordModel;
+use crate::rate_limit::{ClientIp, RateLimitKey};
+use crate::tokens::{Acr, Token, TokenCodec};
+use crate::{GateKeeper, GateKeeperResult};
+use axum::extract::{FromRequest, Request, State};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// Second login step, providing either a TOTP or a recovery code
+#[derive(Debug, Deserialize)]
+pub struct MfaCredentials {
+    /// Encoded `MfaPendingToken` returned by the `login` handler
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequest<S> for MfaCredentials {
+    type Rejection = GateKeeperError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(crate::authentication::json_or_form(req, state)
+            .await
+            .map_err(MfaError::MalformedRequest)?)
+    }
+}
+
+/// Response body of the first login step for users having MFA enabled
+#[derive(Debug, Serialize)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+}
+
+/// Respond with an `MfaPendingToken` for `user`
+pub(crate) fn mfa_challenge(
+    user: &impl PasswordModel,
+    codec: &TokenCodec,
+) -> GateKeeperResult<Response> {
+    let token = MfaPendingToken::try_new_for_model(user, codec)?;
+
+    Ok(Json(MfaChallenge {
+        mfa_required: true,
+        mfa_token: token.get_encoded().to_string(),
+    })
+    .into_response())
+}
+
+/// Handler exchanging an `MfaPendingToken` and a valid TOTP or recovery code
+/// for a token pair
+///
+/// Responds like the `login` handler. The `MfaPendingToken` is revoked on
+/// success, and TOTP codes and recovery codes can only be used once, see
+/// [`MfaStore`](crate::mfa::MfaStore).
+pub async fn verify_mfa<U: PasswordModel + 'static>(
+    State(gatekeeper): State<GateKeeper<U>>,
+    client_ip: ClientIp,
+    audit: AuditContext,
+    credentials: MfaCredentials,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::verify_mfa");
+
+    let result = verify_second_factor(&gatekeeper, &client_ip, credentials).await;
+    let user = audit
+        .record_failure(AuditEventKind::MfaVerification, result)
+        .await?;
+    let response = crate::authentication::token_response(
+        &user,
+        Utc::now().timestamp() as usize,
+        Some(Acr::Mfa),
+        gatekeeper.token_codec(),
+    )?;
+
+    audit
+        .record(
+            audit
+                .event(AuditEventKind::MfaVerification)
+                .subject(user.id()),
+        )
+        .await;
+
+    Ok(response)
+}
+
+/// Decode the `MfaPendingToken` of `credentials` and verify its code
+///
+/// Decoding is only limited per client IP. The token's subject is limited once
+/// its signature was verified, so forged tokens can't lock out their subject.
+async fn verify_second_factor<U: PasswordModel + 'static>(
+    gatekeeper: &GateKeeper<U>,
+    client_ip: &ClientIp,
+    credentials: MfaCredentials,
+) -> GateKeeperResult<U> {
+    let (user, pending) = gatekeeper
+        .attempt(
+            &client_ip.keys_with([]),
+            gatekeeper.decode_for_user::<MfaPendingToken>(&credentials.mfa_token),
+        )
+        .await?;
+    let keys = client_ip.keys_with([RateLimitKey::Subject(user.id())]);
+
+    gatekeeper
+        .attempt(&keys, verify_code(gatekeeper, &user, credentials))
+        .await?;
+    // Pending tokens complete a single login only
+    gatekeeper.revoke_token(&pending).await?;
+
+    Ok(user)
+}
+
+/// Verify the TOTP or recovery code of `credentials` for `user`
+async fn verify_code<U: PasswordModel + 'static>(
+    gatekeeper: &GateKeeper<U>,
+    user: &U,
+    credentials: MfaCredentials,
+) -> GateKeeperResult<()> {
+    let secret = user.totp_secret().ok_or(MfaError::NotEnabled)?;
+    let store = gatekeeper.mfa_store();
+    let valid = match (credentials.code, credentials.recovery_code) {
+        (Some(code), _) => {
+            let now = Utc::now().timestamp() as u64;
+
+            match Totp::from_base32(secret)?.verify(&code, now) {
+                Some(step) => store.consume_totp_step(user.id(), step).await?,
+                None => false,
+            }
+        }
+        (None, Some(recovery_code)) => {
+            store
+                .consume_recovery_code(user.id(), &RecoveryCodes::hash(&recovery_code))
+                .await?
+        }
+        (None, None) => {
+            return Err(MfaError::MalformedRequest("Missing code".to_string()).into());
+        }
+    };
+
+    if !valid {
+        return Err(MfaError::InvalidCode.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify_mfa;
+    use crate::authentication::login;
+    use crate::mfa::{MemoryMfaStore, RecoveryCodes, Totp};
+    use crate::rate_limit::RateLimiter;
+    use crate::test_support::{init_env, test_gatekeeper, test_hasher, TestRepository, TestUser};
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    struct Setup {
+        app: Router,
+        totp: Totp,
+        recovery_code: String,
+    }
+
+    fn setup(rate_limiter: Option<RateLimiter>) -> anyhow::Result<Setup> {
+        init_env();
+
+        let secret = Totp::generate_secret();
+        let mut user = TestUser::new("alice", &test_hasher().hash("correct horse")?);
+        let store = Arc::new(MemoryMfaStore::new());
+        let recovery = RecoveryCodes::generate(1);
+
+        user.totp_secret = Some(secret.clone());
+        store.set_recovery_codes(user.id, recovery.hashes);
+
+        let mut gatekeeper = test_gatekeeper(TestRepository::with_users([user])).mfa_store(store);
+
+        if let Some(rate_limiter) = rate_limiter {
+            gatekeeper = gatekeeper.rate_limiter(rate_limiter);
+        }
+
+        let gatekeeper = gatekeeper.build()?;
+        let app = Router::new()
+            .route("/login", post(login::<TestUser>))
+            .route("/mfa", post(verify_mfa::<TestUser>))
+            .with_state(gatekeeper);
+
+        Ok(Setup {
+            app,
+            totp: Totp::from_base32(&secret)?,
+            recovery_code: recovery.codes[0].clone(),
+        })
+    }
+
+    async fn post_json(
+        app: &Router,
+        uri: &str,
+        body: serde_json::Value,
+    ) -> anyhow::Result<(StatusCode, serde_json::Value)> {
+        post_json_from(app, uri, body, "192.0.2.1:4711").await
+    }
+
+    async fn post_json_from(
+        app: &Router,
+        uri: &str,
+        body: serde_json::Value,
+        addr: &str,
+    ) -> anyhow::Result<(StatusCode, serde_json::Value)> {
+        let mut request = Request::post(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))?;
+
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(addr.parse::<SocketAddr>()?));
+
+        let response = app.clone().oneshot(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok((status, serde_json::from_slice(&body).unwrap_or_default()))
+    }
+
+    async fn first_step(app: &Router) -> anyhow::Result<String> {
+        let credentials = serde_json::json!({"login": "alice", "password": "correct horse"});
+        let (status, body) = post_json(app, "/login", credentials).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["mfa_required"], true);
+        assert!(body.get("encoded").is_none());
+
+        Ok(body["mfa_token"].as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_two_step_login() -> anyhow::Result<()> {
+        let setup = setup(None)?;
+        let mfa_token = first_step(&setup.app).await?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let code = setup.totp.generate(setup.totp.step_at(now));
+        let body = serde_json::json!({"mfa_token": mfa_token, "code": code});
+        let (status, response) = post_json(&setup.app, "/mfa", body.clone()).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(response["encoded"].is_string());
+
+        // Codes can't be replayed
+        let (status, _) = post_json(&setup.app, "/mfa", body).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_code() -> anyhow::Result<()> {
+        let setup = setup(None)?;
+        let mfa_token = first_step(&setup.app).await?;
+        let body = serde_json::json!({"mfa_token": mfa_token, "code": "000000x"});
+        let (status, _) = post_json(&setup.app, "/mfa", body).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recovery_code() -> anyhow::Result<()> {
+        let setup = setup(None)?;
+        let mfa_token = first_step(&setup.app).await?;
+        let body = serde_json::json!({
+            "mfa_token": mfa_token,
+            "recovery_code": setup.recovery_code.to_uppercase(),
+        });
+        let (status, _) = post_json(&setup.app, "/mfa", body.clone()).await?;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = post_json(&setup.app, "/mfa", body).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forged_tokens_dont_lock_out_subject() -> anyhow::Result<()> {
+        let setup = setup(Some(RateLimiter::build().free_attempts(1).build()))?;
+        let mfa_token = first_step(&setup.app).await?;
+        let forged = serde_json::json!({"mfa_token": format!("{mfa_token}x"), "code": "000000"});
+        let (status, _) = post_json_from(&setup.app, "/mfa", forged, "203.0.113.7:4711").await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let code = setup.totp.generate(setup.totp.step_at(now));
+        let body = serde_json::json!({"mfa_token": mfa_token, "code": code});
+        let (status, _) = post_json(&setup.app, "/mfa", body).await?;
+
+        assert_eq!(status, StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pending_token_single_use() -> anyhow::Result<()> {
+        let setup = setup(None)?;
+        let mfa_token = first_step(&setup.app).await?;
+        let body = serde_json::json!({
+            "mfa_token": mfa_token,
+            "recovery_code": setup.recovery_code,
+        });
+        let (status, _) = post_json(&setup.app, "/mfa", body).await?;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let code = setup.totp.generate(setup.totp.step_at(now));
+        let body = serde_json::json!({"mfa_token": mfa_token, "code": code});
+        let (status, _) = post_json(&setup.app, "/mfa", body).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+//! Module containing TOTP (RFC 6238) based multi-factor authentication
+//!
+//! If a user's [`PasswordModel::totp_secret`](crate::password::PasswordModel::totp_secret)
+//! is set, the `login` handler responds with an [`MfaPendingToken`] instead of
+//! the token pair. The `verify_mfa` handler exchanges it for an
+//! `AuthenticationToken` once a valid TOTP or recovery code was provided.
+//!
+//! Only available on feature `mfa`
+mod error;
+mod handler;
+mod recovery;
+mod store;
+mod token;
+mod totp;
+
+pub use error::*;
+pub use handler::*;
+pub use recovery::*;
+pub use store::*;
+pub use token::*;
+pub use totp::*;
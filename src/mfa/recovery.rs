@@ -0,0 +1,69 @@
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Single use codes allowing users to log in without their authenticator
+pub struct RecoveryCodes {
+    /// Codes to show the user once, e.g. `k3jd7-qm2xa`
+    pub codes: Vec<String>,
+    /// Hashes of `codes` to store with the user
+    pub hashes: Vec<String>,
+}
+
+impl RecoveryCodes {
+    /// Generate `count` random recovery codes with 50 bits of entropy each
+    pub fn generate(count: usize) -> Self {
+        let codes = (0..count)
+            .map(|_| {
+                let mut bytes = [0u8; 10];
+
+                OsRng.fill_bytes(&mut bytes);
+
+                let encoded = BASE32_NOPAD.encode(&bytes).to_lowercase();
+
+                format!("{}-{}", &encoded[..5], &encoded[5..10])
+            })
+            .collect::<Vec<_>>();
+        let hashes = codes.iter().map(|code| Self::hash(code)).collect();
+
+        Self { codes, hashes }
+    }
+
+    /// Hash `code` for storage or lookup, ignoring case, whitespace and dashes
+    ///
+    /// The codes are random enough for a fast hash, so they can be looked up
+    /// without trying every stored hash.
+    pub fn hash(code: &str) -> String {
+        let normalized = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        HEXLOWER.encode(&Sha256::digest(normalized.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecoveryCodes;
+
+    #[test]
+    fn test_generate() {
+        let recovery = RecoveryCodes::generate(10);
+
+        assert_eq!(recovery.codes.len(), 10);
+        assert_eq!(recovery.hashes.len(), 10);
+        assert_eq!(recovery.codes[0].len(), 11);
+        assert_eq!(recovery.hashes[0], RecoveryCodes::hash(&recovery.codes[0]));
+        assert_ne!(recovery.codes[0], recovery.codes[1]);
+    }
+
+    #[test]
+    fn test_hash_normalizes() {
+        assert_eq!(
+            RecoveryCodes::hash("k3jd7-qm2xa"),
+            RecoveryCodes::hash(" K3JD7QM2XA ")
+        );
+    }
+}
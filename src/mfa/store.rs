@@ -0,0 +1,129 @@
+use crate::GateKeeperResult;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// Storage for used TOTP time steps and recovery codes
+#[async_trait::async_trait]
+pub trait MfaStore: Send + Sync {
+    /// Remember `step` as used by `user_id`
+    ///
+    /// Returns `false` if `step` or a later one was already used, so a code
+    /// can't be replayed within its drift window.
+    async fn consume_totp_step(&self, user_id: uuid::Uuid, step: u64) -> GateKeeperResult<bool>;
+
+    /// Remove the recovery code with `hash` from `user_id`'s codes
+    ///
+    /// Returns `false` if the user has no such unused code.
+    async fn consume_recovery_code(
+        &self,
+        user_id: uuid::Uuid,
+        hash: &str,
+    ) -> GateKeeperResult<bool>;
+}
+
+/// Process local [`MfaStore`]
+///
+/// Recovery code hashes have to be added using
+/// [`MemoryMfaStore::set_recovery_codes`].
+#[derive(Debug, Default)]
+pub struct MemoryMfaStore {
+    steps: Mutex<HashMap<uuid::Uuid, u64>>,
+    recovery_codes: Mutex<HashMap<uuid::Uuid, HashSet<String>>>,
+}
+
+impl MemoryMfaStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace `user_id`'s recovery codes with `hashes`
+    pub fn set_recovery_codes(
+        &self,
+        user_id: uuid::Uuid,
+        hashes: impl IntoIterator<Item = String>,
+    ) {
+        let mut recovery_codes = self
+            .recovery_codes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        recovery_codes.insert(user_id, hashes.into_iter().collect());
+    }
+}
+
+#[async_trait::async_trait]
+impl MfaStore for MemoryMfaStore {
+    async fn consume_totp_step(&self, user_id: uuid::Uuid, step: u64) -> GateKeeperResult<bool> {
+        let mut steps = self.steps.lock().unwrap_or_else(|e| e.into_inner());
+
+        match steps.get(&user_id) {
+            Some(last) if *last >= step => Ok(false),
+            _ => {
+                steps.insert(user_id, step);
+
+                Ok(true)
+            }
+        }
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        user_id: uuid::Uuid,
+        hash: &str,
+    ) -> GateKeeperResult<bool> {
+        let mut recovery_codes = self
+            .recovery_codes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        Ok(recovery_codes
+            .get_mut(&user_id)
+            .is_some_and(|hashes| hashes.remove(hash)))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: MfaStore + ?Sized> MfaStore for std::sync::Arc<T> {
+    async fn consume_totp_step(&self, user_id: uuid::Uuid, step: u64) -> GateKeeperResult<bool> {
+        self.as_ref().consume_totp_step(user_id, step).await
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        user_id: uuid::Uuid,
+        hash: &str,
+    ) -> GateKeeperResult<bool> {
+        self.as_ref().consume_recovery_code(user_id, hash).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_totp_step() -> anyhow::Result<()> {
+        let store = MemoryMfaStore::new();
+        let user_id = uuid::Uuid::new_v4();
+
+        assert!(store.consume_totp_step(user_id, 10).await?);
+        assert!(!store.consume_totp_step(user_id, 10).await?);
+        assert!(!store.consume_totp_step(user_id, 9).await?);
+        assert!(store.consume_totp_step(user_id, 11).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_consume_recovery_code() -> anyhow::Result<()> {
+        let store = MemoryMfaStore::new();
+        let user_id = uuid::Uuid::new_v4();
+
+        store.set_recovery_codes(user_id, ["foo".to_string()]);
+
+        assert!(store.consume_recovery_code(user_id, "foo").await?);
+        assert!(!store.consume_recovery_code(user_id, "foo").await?);
+
+        Ok(())
+    }
+}
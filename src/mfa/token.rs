@@ -0,0 +1,60 @@
+use crate::tokens::{Claims, Token};
+use serde::Serialize;
+
+/// Short-lived token proving the password step of a two-step login
+///
+/// Uses its own `typ` header, so it's never accepted as `AuthenticationToken`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct MfaPendingToken {
+    encoded: String,
+    claims: Claims,
+}
+
+impl Token for MfaPendingToken {
+    const EXPIRE_SECS_VAR: &'static str = "MFA_PENDING_EXPIRE_SECS";
+    const TOKEN_TYPE: &'static str = "mfa-pending+jwt";
+    const NAME: &'static str = "mfa_pending";
+
+    fn new(encoded: String, claims: Claims) -> Self
+    where
+        Self: Sized,
+    {
+        Self { encoded, claims }
+    }
+
+    fn get_claims(&self) -> &Claims {
+        &self.claims
+    }
+
+    fn into_claims(self) -> Claims {
+        self.claims
+    }
+
+    fn get_encoded(&self) -> &str {
+        &self.encoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MfaPendingToken;
+    use crate::authentication::AuthenticationToken;
+    use crate::tokens::{Claims, Token, TokenCodec};
+
+    #[test]
+    fn test_not_accepted_as_authentication_token() -> anyhow::Result<()> {
+        let now = chrono::Utc::now().timestamp() as usize;
+        let claims = Claims {
+            exp: now + 1000,
+            iat: now,
+            sub: uuid::Uuid::new_v4().to_string(),
+            ..Default::default()
+        };
+        let encoded = MfaPendingToken::encode(&claims, &TokenCodec::default(), "test")?;
+
+        assert!(MfaPendingToken::decode(&encoded, &TokenCodec::default(), "test").is_ok());
+        assert!(AuthenticationToken::decode(&encoded, &TokenCodec::default(), "test").is_err());
+
+        Ok(())
+    }
+}
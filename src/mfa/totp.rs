@@ -0,0 +1,205 @@
+use crate::mfa::MfaError;
+use crate::util::constant_time_eq;
+use crate::GateKeeperResult;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand_core::{OsRng, RngCore};
+use sha1::Sha1;
+
+/// Time based one-time password generator and verifier (RFC 6238)
+///
+/// Uses HMAC-SHA1, which is what authenticator apps support universally.
+#[derive(Clone)]
+pub struct Totp {
+    secret: Vec<u8>,
+    mac: Hmac<Sha1>,
+    digits: u32,
+    period: u64,
+    skew: u64,
+}
+
+impl Totp {
+    /// Length of generated secrets in bytes, as recommended by RFC 4226
+    pub const SECRET_LEN: usize = 20;
+
+    /// Create a new random secret, base32 encoded to be stored with the user
+    pub fn generate_secret() -> String {
+        let mut secret = [0u8; Self::SECRET_LEN];
+
+        OsRng.fill_bytes(&mut secret);
+
+        BASE32_NOPAD.encode(&secret)
+    }
+
+    /// Create TOTP using a base32 encoded secret, with 6 digits, a period of
+    /// 30 seconds and a drift window of one period in both directions
+    pub fn from_base32(secret: &str) -> GateKeeperResult<Self> {
+        let normalized = secret
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=')
+            .collect::<String>()
+            .to_uppercase();
+        let secret = BASE32_NOPAD
+            .decode(normalized.as_bytes())
+            .map_err(MfaError::InvalidSecret)?;
+        let mac = Hmac::<Sha1>::new_from_slice(&secret)
+            .map_err(|e| MfaError::InvalidConfig(e.to_string()))?;
+
+        Ok(Self {
+            secret,
+            mac,
+            digits: 6,
+            period: 30,
+            skew: 1,
+        })
+    }
+
+    /// Set the number of digits of generated codes
+    ///
+    /// Fails for anything but 6 to 8 digits, the lengths allowed by RFC 6238.
+    pub fn digits(mut self, digits: u32) -> GateKeeperResult<Self> {
+        if !(6..=8).contains(&digits) {
+            return Err(
+                MfaError::InvalidConfig(format!("{digits} digits, expected 6 to 8")).into(),
+            );
+        }
+
+        self.digits = digits;
+        Ok(self)
+    }
+
+    /// Set the number of periods codes may be off in either direction
+    pub fn skew(mut self, skew: u64) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// Return the time step for unix `timestamp`
+    pub fn step_at(&self, timestamp: u64) -> u64 {
+        timestamp / self.period
+    }
+
+    /// Generate the code for time step `step`
+    pub fn generate(&self, step: u64) -> String {
+        let mut mac = self.mac.clone();
+
+        mac.update(&step.to_be_bytes());
+
+        let hash = mac.finalize().into_bytes();
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+        let code = u64::from(binary) % 10u64.pow(self.digits);
+
+        format!("{code:0width$}", width = self.digits as usize)
+    }
+
+    /// Verify `code` at unix `timestamp`, allowing for clock drift
+    ///
+    /// Returns the matching time step, which must be remembered to reject
+    /// replays, see [`MfaStore::consume_totp_step`](crate::mfa::MfaStore::consume_totp_step).
+    pub fn verify(&self, code: &str, timestamp: u64) -> Option<u64> {
+        let code = code.trim();
+        let current = self.step_at(timestamp);
+
+        (current.saturating_sub(self.skew)..=current.saturating_add(self.skew))
+            .find(|step| constant_time_eq(self.generate(*step).as_bytes(), code.as_bytes()))
+    }
+
+    /// Return the `otpauth://` URI used to set up authenticator apps, e.g. as QR code
+    pub fn provisioning_uri(&self, issuer: &str, account: &str) -> String {
+        let label = format!("{issuer}:{account}");
+
+        format!(
+            "otpauth://totp/{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            utf8_percent_encode(&label, NON_ALPHANUMERIC),
+            BASE32_NOPAD.encode(&self.secret),
+            utf8_percent_encode(issuer, NON_ALPHANUMERIC),
+            self.digits,
+            self.period,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Totp;
+    use data_encoding::BASE32_NOPAD;
+
+    fn rfc_totp() -> anyhow::Result<Totp> {
+        let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+
+        Ok(Totp::from_base32(&secret)?.digits(8)?)
+    }
+
+    #[test]
+    fn test_invalid_digits() -> anyhow::Result<()> {
+        let totp = Totp::from_base32(&Totp::generate_secret())?;
+
+        assert!(totp.clone().digits(5).is_err());
+        assert!(totp.clone().digits(9).is_err());
+        assert!(totp.digits(20).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rfc6238_vectors() -> anyhow::Result<()> {
+        let totp = rfc_totp()?;
+
+        assert_eq!(totp.generate(totp.step_at(59)), "94287082");
+        assert_eq!(totp.generate(totp.step_at(1111111109)), "07081804");
+        assert_eq!(totp.generate(totp.step_at(1234567890)), "89005924");
+        assert_eq!(totp.generate(totp.step_at(20000000000)), "65353130");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_drift_window() -> anyhow::Result<()> {
+        let totp = rfc_totp()?;
+        let now = 1111111109;
+        let step = totp.step_at(now);
+
+        assert_eq!(totp.verify(&totp.generate(step), now), Some(step));
+        assert_eq!(totp.verify(&totp.generate(step - 1), now), Some(step - 1));
+        assert_eq!(totp.verify(&totp.generate(step + 1), now), Some(step + 1));
+        assert_eq!(totp.verify(&totp.generate(step - 2), now), None);
+
+        let previous = totp.generate(step - 1);
+
+        assert_eq!(totp.skew(0).verify(&previous, now), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_secret() -> anyhow::Result<()> {
+        let secret = Totp::generate_secret();
+        let totp = Totp::from_base32(&secret.to_lowercase())?;
+
+        assert_eq!(totp.secret.len(), Totp::SECRET_LEN);
+        assert_ne!(secret, Totp::generate_secret());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_provisioning_uri() -> anyhow::Result<()> {
+        let totp = Totp::from_base32("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")?;
+
+        assert_eq!(
+            totp.provisioning_uri("Acme Corp", "alice@example.com"),
+            "otpauth://totp/Acme%20Corp%3Aalice%40example%2Ecom\
+             ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Acme%20Corp\
+             &algorithm=SHA1&digits=6&period=30"
+        );
+
+        Ok(())
+    }
+}
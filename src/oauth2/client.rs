@@ -0,0 +1,153 @@
+use crate::util::constant_time_eq;
+use crate::GateKeeperResult;
+use data_encoding::HEXLOWER;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Grant types supported by the token endpoint
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    AuthorizationCode,
+    RefreshToken,
+    ClientCredentials,
+}
+
+impl GrantType {
+    /// Return the value of the `grant_type` parameter
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrantType::AuthorizationCode => "authorization_code",
+            GrantType::RefreshToken => "refresh_token",
+            GrantType::ClientCredentials => "client_credentials",
+        }
+    }
+}
+
+/// Registered OAuth2 client
+#[derive(Debug, Clone)]
+pub struct OAuthClient {
+    pub client_id: String,
+    /// Hash of the client secret, see [`OAuthClient::hash_secret`]. Public
+    /// clients like SPAs and mobile apps don't have one.
+    pub secret_hash: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<GrantType>,
+    /// Scopes the client may request
+    pub scopes: Vec<String>,
+    /// User the client acts as in the `client_credentials` grant
+    pub service_account: Option<uuid::Uuid>,
+}
+
+impl OAuthClient {
+    /// Create a public client allowed to use the `authorization_code` and
+    /// `refresh_token` grants
+    pub fn public(client_id: &str, redirect_uri: &str) -> Self {
+        Self {
+            client_id: client_id.to_string(),
+            secret_hash: None,
+            redirect_uris: vec![redirect_uri.to_string()],
+            grant_types: vec![GrantType::AuthorizationCode, GrantType::RefreshToken],
+            scopes: Vec::new(),
+            service_account: None,
+        }
+    }
+
+    /// Hash `secret` for storage
+    ///
+    /// Client secrets should be generated randomly, so a fast hash suffices.
+    pub fn hash_secret(secret: &str) -> String {
+        HEXLOWER.encode(&Sha256::digest(secret.as_bytes()))
+    }
+
+    /// Check whether the client is confidential, i.e. has a secret
+    pub fn is_confidential(&self) -> bool {
+        self.secret_hash.is_some()
+    }
+
+    /// Verify `secret` in constant time
+    pub fn verify_secret(&self, secret: &str) -> bool {
+        self.secret_hash.as_ref().is_some_and(|hash| {
+            constant_time_eq(hash.as_bytes(), Self::hash_secret(secret).as_bytes())
+        })
+    }
+
+    /// Check whether the client may use `grant_type`
+    pub fn allows_grant(&self, grant_type: GrantType) -> bool {
+        self.grant_types.contains(&grant_type)
+    }
+
+    /// Check whether all space separated `scope`s may be requested
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        scope
+            .split_whitespace()
+            .all(|scope| self.scopes.iter().any(|allowed| allowed == scope))
+    }
+}
+
+/// Lookup of registered clients
+#[async_trait::async_trait]
+pub trait ClientRegistry: Send + Sync {
+    /// Return the client with `client_id`, if registered
+    async fn find_client(&self, client_id: &str) -> GateKeeperResult<Option<OAuthClient>>;
+}
+
+#[async_trait::async_trait]
+impl<T: ClientRegistry + ?Sized> ClientRegistry for std::sync::Arc<T> {
+    async fn find_client(&self, client_id: &str) -> GateKeeperResult<Option<OAuthClient>> {
+        self.as_ref().find_client(client_id).await
+    }
+}
+
+/// Process local [`ClientRegistry`]
+#[derive(Debug, Default)]
+pub struct MemoryClientRegistry {
+    clients: Mutex<HashMap<String, OAuthClient>>,
+}
+
+impl MemoryClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace `client`
+    pub fn register(&self, client: OAuthClient) {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+
+        clients.insert(client.client_id.clone(), client);
+    }
+}
+
+#[async_trait::async_trait]
+impl ClientRegistry for MemoryClientRegistry {
+    async fn find_client(&self, client_id: &str) -> GateKeeperResult<Option<OAuthClient>> {
+        let clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(clients.get(client_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GrantType, OAuthClient};
+
+    #[test]
+    fn test_client() {
+        let mut client = OAuthClient::public("spa", "https://app/callback");
+
+        assert!(!client.is_confidential());
+        assert!(!client.verify_secret(""));
+        assert!(client.allows_grant(GrantType::RefreshToken));
+        assert!(!client.allows_grant(GrantType::ClientCredentials));
+
+        client.secret_hash = Some(OAuthClient::hash_secret("s3cret"));
+        client.scopes = vec!["read".to_string(), "write".to_string()];
+
+        assert!(client.verify_secret("s3cret"));
+        assert!(!client.verify_secret("secret"));
+        assert!(client.allows_scope("read write"));
+        assert!(!client.allows_scope("read admin"));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::tokens::Acr;
+use crate::GateKeeperResult;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Authorization granted by a user to a client, redeemable once using the
+/// authorization code it was stored under
+#[derive(Debug, Clone)]
+pub struct AuthorizationGrant {
+    pub client_id: String,
+    /// Redirect URI of the authorization request, which has to be sent again
+    /// with the token request
+    pub redirect_uri: String,
+    /// Id of the user who granted access
+    pub subject: uuid::Uuid,
+    /// S256 PKCE code challenge of the authorization request
+    pub code_challenge: String,
+    pub scope: Option<String>,
+    /// Time the user authenticated as unix timestamp
+    pub auth_time: usize,
+    pub acr: Option<Acr>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AuthorizationGrant {
+    /// Check whether the code has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+/// Storage for issued authorization codes, keyed by their hash
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore: Send + Sync {
+    /// Store `grant` under `code_hash`
+    async fn insert(&self, code_hash: String, grant: AuthorizationGrant) -> GateKeeperResult<()>;
+
+    /// Remove and return the grant stored under `code_hash`
+    ///
+    /// Has to be atomic, so each code can be redeemed only once.
+    async fn take(&self, code_hash: &str) -> GateKeeperResult<Option<AuthorizationGrant>>;
+}
+
+#[async_trait::async_trait]
+impl<T: AuthorizationCodeStore + ?Sized> AuthorizationCodeStore for std::sync::Arc<T> {
+    async fn insert(&self, code_hash: String, grant: AuthorizationGrant) -> GateKeeperResult<()> {
+        self.as_ref().insert(code_hash, grant).await
+    }
+
+    async fn take(&self, code_hash: &str) -> GateKeeperResult<Option<AuthorizationGrant>> {
+        self.as_ref().take(code_hash).await
+    }
+}
+
+/// Process local [`AuthorizationCodeStore`]
+#[derive(Debug, Default)]
+pub struct MemoryAuthorizationCodeStore {
+    grants: Mutex<HashMap<String, AuthorizationGrant>>,
+}
+
+impl MemoryAuthorizationCodeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for MemoryAuthorizationCodeStore {
+    async fn insert(&self, code_hash: String, grant: AuthorizationGrant) -> GateKeeperResult<()> {
+        let mut grants = self.grants.lock().unwrap_or_else(|e| e.into_inner());
+
+        grants.retain(|_, grant| !grant.is_expired());
+        grants.insert(code_hash, grant);
+
+        Ok(())
+    }
+
+    async fn take(&self, code_hash: &str) -> GateKeeperResult<Option<AuthorizationGrant>> {
+        let mut grants = self.grants.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(grants.remove(code_hash))
+    }
+}
@@ -0,0 +1,96 @@
+use crate::error::GateKeeperError;
+use axum::http::header::{CACHE_CONTROL, PRAGMA, WWW_AUTHENTICATE};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+/// Error of the token endpoint, see RFC 6749, section 5.2
+#[derive(thiserror::Error, Debug)]
+pub enum OAuthError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Client authentication failed")]
+    InvalidClient,
+    #[error("Invalid grant: {0}")]
+    InvalidGrant(String),
+    #[error("Client isn't allowed to use grant type {0}")]
+    UnauthorizedClient(String),
+    #[error("Unsupported grant type {0}")]
+    UnsupportedGrantType(String),
+    #[error("Invalid scope: {0}")]
+    InvalidScope(String),
+}
+
+#[derive(Serialize)]
+struct OAuthErrorBody {
+    error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_description: Option<String>,
+}
+
+impl OAuthError {
+    /// Return the error code defined by RFC 6749
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant(_) => "invalid_grant",
+            OAuthError::UnauthorizedClient(_) => "unauthorized_client",
+            OAuthError::UnsupportedGrantType(_) => "unsupported_grant_type",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+        }
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "Invalid request",
+            OAuthError::InvalidClient => "Client authentication failed",
+            OAuthError::InvalidGrant(_) => "Invalid grant",
+            OAuthError::UnauthorizedClient(_) => "Unauthorized client",
+            OAuthError::UnsupportedGrantType(_) => "Unsupported grant type",
+            OAuthError::InvalidScope(_) => "Invalid scope",
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
+
+impl OAuthError {
+    /// Render the error as done by previous versions
+    pub(crate) fn legacy_response(&self) -> Response {
+        let error = self.code();
+        let no_cache = [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")];
+
+        match self {
+            OAuthError::InvalidClient => (
+                StatusCode::UNAUTHORIZED,
+                no_cache,
+                [(WWW_AUTHENTICATE, r#"Basic realm="oauth2""#)],
+                Json(OAuthErrorBody {
+                    error,
+                    error_description: None,
+                }),
+            )
+                .into_response(),
+            OAuthError::InvalidRequest(description)
+            | OAuthError::InvalidGrant(description)
+            | OAuthError::UnauthorizedClient(description)
+            | OAuthError::UnsupportedGrantType(description)
+            | OAuthError::InvalidScope(description) => (
+                StatusCode::BAD_REQUEST,
+                no_cache,
+                Json(OAuthErrorBody {
+                    error,
+                    error_description: Some(description.clone()),
+                }),
+            )
+                .into_response(),
+        }
+    }
+}
@@ -0,0 +1,632 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::authentication::{AuthenticationToken, RefreshToken};
+use crate::error::{GateKeeperError, TokenError};
+use crate::model::GateKeeperModel;
+use crate::oauth2::server::hash_code;
+use crate::oauth2::{GrantType, OAuthClient, OAuthError, OAuthServer};
+use crate::tokens::{Claims, Token, TokenCodec, TokenService};
+use crate::util::{constant_time_eq, pkce_challenge};
+use crate::GateKeeperResult;
+use axum::extract::rejection::FormRejection;
+use axum::extract::State;
+use axum::http::header::{CACHE_CONTROL, PRAGMA};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::{Form, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+
+/// Form parameters of a token request
+#[derive(Default, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+impl Debug for TokenRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenRequest")
+            .field("grant_type", &self.grant_type)
+            .field("redirect_uri", &self.redirect_uri)
+            .field("scope", &self.scope)
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Successful token response, see RFC 6749, section 5.1
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// OAuth2 token endpoint handler
+///
+/// Clients authenticate using HTTP Basic or the `client_id` and
+/// `client_secret` form parameters, public clients by sending their
+/// `client_id` only. Errors are responded as defined by RFC 6749.
+pub async fn token<U: GateKeeperModel + 'static>(
+    State(server): State<OAuthServer<U>>,
+    audit: AuditContext,
+    headers: HeaderMap,
+    request: Result<Form<TokenRequest>, FormRejection>,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::token");
+
+    let result = issue_token(&server, &headers, request).await;
+    let response = audit
+        .record_failure(AuditEventKind::TokenIssued, result)
+        .await?;
+    let mut event = audit.event(AuditEventKind::TokenIssued);
+
+    if let Ok(subject) = TokenService::get_subject_from_encoded(&response.access_token) {
+        event = event.subject(subject);
+    }
+
+    audit.record(event).await;
+
+    Ok((
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+        Json(response),
+    )
+        .into_response())
+}
+
+async fn issue_token<U: GateKeeperModel + 'static>(
+    server: &OAuthServer<U>,
+    headers: &HeaderMap,
+    request: Result<Form<TokenRequest>, FormRejection>,
+) -> GateKeeperResult<OAuthTokenResponse> {
+    let Form(request) = request.map_err(|e| OAuthError::InvalidRequest(e.body_text()))?;
+    let client = server
+        .authenticate_client(
+            headers,
+            request.client_id.as_deref(),
+            request.client_secret.as_deref(),
+        )
+        .await?;
+    let grant_type = match request.grant_type.as_deref() {
+        Some("authorization_code") => GrantType::AuthorizationCode,
+        Some("refresh_token") => GrantType::RefreshToken,
+        Some("client_credentials") => GrantType::ClientCredentials,
+        Some(other) => return Err(OAuthError::UnsupportedGrantType(other.to_string()).into()),
+        None => return Err(OAuthError::InvalidRequest("Missing grant_type".to_string()).into()),
+    };
+
+    if !client.allows_grant(grant_type) {
+        return Err(OAuthError::UnauthorizedClient(grant_type.as_str().to_string()).into());
+    }
+
+    match grant_type {
+        GrantType::AuthorizationCode => authorization_code_grant(server, &client, request).await,
+        GrantType::RefreshToken => refresh_token_grant(server, &client, request).await,
+        GrantType::ClientCredentials => client_credentials_grant(server, &client, request).await,
+    }
+}
+
+async fn authorization_code_grant<U: GateKeeperModel + 'static>(
+    server: &OAuthServer<U>,
+    client: &OAuthClient,
+    request: TokenRequest,
+) -> GateKeeperResult<OAuthTokenResponse> {
+    let (Some(code), Some(redirect_uri), Some(code_verifier)) =
+        (request.code, request.redirect_uri, request.code_verifier)
+    else {
+        return Err(OAuthError::InvalidRequest(
+            "Missing code, redirect_uri or code_verifier".to_string(),
+        )
+        .into());
+    };
+    let invalid = |reason: &str| OAuthError::InvalidGrant(reason.to_string());
+    let grant = server
+        .codes()
+        .take(&hash_code(&code))
+        .await?
+        .ok_or_else(|| invalid("Invalid authorization code"))?;
+
+    if grant.is_expired() || grant.client_id != client.client_id {
+        return Err(invalid("Invalid authorization code").into());
+    }
+
+    if grant.redirect_uri != redirect_uri {
+        return Err(invalid("Mismatching redirect_uri").into());
+    }
+
+    let challenge = pkce_challenge(&code_verifier);
+
+    if !constant_time_eq(challenge.as_bytes(), grant.code_challenge.as_bytes()) {
+        return Err(invalid("Invalid code_verifier").into());
+    }
+
+    let user = server
+        .gatekeeper()
+        .users()
+        .find_by_id(grant.subject)
+        .await?
+        .ok_or_else(|| invalid("Unknown subject"))?;
+    let template = Claims {
+        auth_time: grant.auth_time,
+        acr: grant.acr,
+        scope: grant.scope,
+        client_id: Some(client.client_id.clone()),
+        ..Default::default()
+    };
+
+    token_pair(&user, template, server.gatekeeper().token_codec())
+}
+
+async fn refresh_token_grant<U: GateKeeperModel + 'static>(
+    server: &OAuthServer<U>,
+    client: &OAuthClient,
+    request: TokenRequest,
+) -> GateKeeperResult<OAuthTokenResponse> {
+    let encoded = request
+        .refresh_token
+        .ok_or_else(|| OAuthError::InvalidRequest("Missing refresh_token".to_string()))?;
+    let (user, token) = server
+        .gatekeeper()
+        .decode_for_user::<RefreshToken>(&encoded)
+        .await
+        .map_err(|e| {
+            tracing::debug!("Invalid refresh token: {e}");
+            OAuthError::InvalidGrant("Invalid refresh_token".to_string())
+        })?;
+    let claims = token.get_claims();
+
+    if claims.client_id.as_deref() != Some(client.client_id.as_str()) {
+        return Err(OAuthError::InvalidGrant("Invalid refresh_token".to_string()).into());
+    }
+
+    // Clients may narrow the scope, but never widen it
+    let scope = match request.scope {
+        Some(requested) => {
+            let granted = claims.scope.as_deref().unwrap_or_default();
+
+            if !requested
+                .split_whitespace()
+                .all(|scope| granted.split_whitespace().any(|granted| granted == scope))
+            {
+                return Err(OAuthError::InvalidScope(requested).into());
+            }
+
+            Some(requested)
+        }
+        None => claims.scope.clone(),
+    };
+    let template = Claims {
+        auth_time: claims.auth_time,
+        acr: claims.acr,
+        scope,
+        client_id: claims.client_id.clone(),
+        ..Default::default()
+    };
+
+    // Rotate the refresh token, so it can't be used again
+    server
+        .gatekeeper()
+        .revoke_token(&token)
+        .await
+        .map_err(|e| match e {
+            GateKeeperError::Token(TokenError::Revoked) => {
+                OAuthError::InvalidGrant("Invalid refresh_token".to_string()).into()
+            }
+            e => e,
+        })?;
+
+    token_pair(&user, template, server.gatekeeper().token_codec())
+}
+
+async fn client_credentials_grant<U: GateKeeperModel + 'static>(
+    server: &OAuthServer<U>,
+    client: &OAuthClient,
+    request: TokenRequest,
+) -> GateKeeperResult<OAuthTokenResponse> {
+    let grant_type = GrantType::ClientCredentials.as_str().to_string();
+
+    // Public clients can't keep credentials, see RFC 6749, section 4.4
+    if !client.is_confidential() {
+        return Err(OAuthError::UnauthorizedClient(grant_type).into());
+    }
+
+    let account = client
+        .service_account
+        .ok_or_else(|| OAuthError::UnauthorizedClient(grant_type.clone()))?;
+    let scope = match request.scope {
+        Some(scope) if !client.allows_scope(&scope) => {
+            return Err(OAuthError::InvalidScope(scope).into());
+        }
+        Some(scope) => Some(scope),
+        None => (!client.scopes.is_empty()).then(|| client.scopes.join(" ")),
+    };
+    let user = server
+        .gatekeeper()
+        .users()
+        .find_by_id(account)
+        .await?
+        .ok_or(OAuthError::UnauthorizedClient(grant_type))?;
+    let template = Claims {
+        auth_time: Utc::now().timestamp() as usize,
+        scope,
+        client_id: Some(client.client_id.clone()),
+        ..Default::default()
+    };
+    let access_token = AuthenticationToken::try_new_from_template(
+        &user,
+        template,
+        server.gatekeeper().token_codec(),
+    )?;
+
+    // No refresh token, as the client can simply request a new access token
+    Ok(token_response(&access_token, None))
+}
+
+fn token_pair(
+    user: &impl GateKeeperModel,
+    template: Claims,
+    codec: &TokenCodec,
+) -> GateKeeperResult<OAuthTokenResponse> {
+    let access_token = AuthenticationToken::try_new_from_template(user, template.clone(), codec)?;
+    let refresh_token = RefreshToken::try_new_from_template(user, template, codec)?;
+
+    Ok(token_response(&access_token, Some(&refresh_token)))
+}
+
+fn token_response(
+    access_token: &AuthenticationToken,
+    refresh_token: Option<&RefreshToken>,
+) -> OAuthTokenResponse {
+    let claims = access_token.get_claims();
+
+    OAuthTokenResponse {
+        access_token: access_token.get_encoded().to_string(),
+        token_type: "Bearer".to_string(),
+        expires_in: claims.exp.saturating_sub(claims.iat),
+        refresh_token: refresh_token.map(|token| token.get_encoded().to_string()),
+        scope: claims.scope.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{token, OAuthTokenResponse};
+    use crate::authentication::{AuthenticationToken, RefreshToken};
+    use crate::oauth2::{
+        AuthorizationGrant, GrantType, MemoryClientRegistry, OAuthClient, OAuthServer,
+    };
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Acr, Token, TokenCodec};
+    use crate::util::pkce_challenge;
+    use axum::body::Body;
+    use axum::http::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    const REDIRECT_URI: &str = "https://app.example.com/callback";
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+    struct Setup {
+        server: OAuthServer<TestUser>,
+        app: Router,
+        user: TestUser,
+        service: TestUser,
+    }
+
+    fn setup() -> anyhow::Result<Setup> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let service = TestUser::new("reporting", "");
+        let clients = MemoryClientRegistry::new();
+        let mut spa = OAuthClient::public("spa", REDIRECT_URI);
+
+        spa.scopes = vec!["read".to_string(), "write".to_string()];
+        clients.register(spa);
+        clients.register(OAuthClient {
+            client_id: "reporting".to_string(),
+            secret_hash: Some(OAuthClient::hash_secret("s3cret")),
+            redirect_uris: Vec::new(),
+            grant_types: vec![GrantType::ClientCredentials],
+            scopes: vec!["read".to_string()],
+            service_account: Some(service.id),
+        });
+
+        let gatekeeper =
+            test_gatekeeper(TestRepository::with_users([user.clone(), service.clone()])).build()?;
+        let server = OAuthServer::build(gatekeeper, clients).build();
+        let app = Router::new()
+            .route("/token", post(token::<TestUser>))
+            .with_state(server.clone());
+
+        Ok(Setup {
+            server,
+            app,
+            user,
+            service,
+        })
+    }
+
+    async fn issue_code(setup: &Setup) -> anyhow::Result<String> {
+        let grant = AuthorizationGrant {
+            client_id: "spa".to_string(),
+            redirect_uri: REDIRECT_URI.to_string(),
+            subject: setup.user.id,
+            code_challenge: pkce_challenge(VERIFIER),
+            scope: Some("read write".to_string()),
+            auth_time: 1000,
+            acr: Some(Acr::Mfa),
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(60),
+        };
+
+        Ok(setup.server.issue_authorization_code(grant).await?)
+    }
+
+    async fn post_token(
+        app: &Router,
+        form: &[(&str, &str)],
+        authorization: Option<&str>,
+    ) -> anyhow::Result<(StatusCode, serde_json::Value)> {
+        let body = form
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut request =
+            Request::post("/token").header(CONTENT_TYPE, "application/x-www-form-urlencoded");
+
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        let response = app.clone().oneshot(request.body(Body::from(body))?).await?;
+        let status = response.status();
+
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok((status, serde_json::from_slice(&body)?))
+    }
+
+    #[tokio::test]
+    async fn test_authorization_code_grant() -> anyhow::Result<()> {
+        let setup = setup()?;
+        let code = issue_code(&setup).await?;
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("client_id", "spa"),
+            ("code", code.as_str()),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ];
+        let (status, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(status, StatusCode::OK);
+
+        let response = serde_json::from_value::<OAuthTokenResponse>(body)?;
+        let access_token =
+            AuthenticationToken::decode(&response.access_token, &TokenCodec::default(), "test")?;
+        let claims = access_token.get_claims();
+
+        assert_eq!(response.token_type, "Bearer");
+        assert_eq!(response.expires_in, 3600);
+        assert_eq!(response.scope.as_deref(), Some("read write"));
+        assert!(response.refresh_token.is_some());
+        assert_eq!(claims.sub, setup.user.id.to_string());
+        assert_eq!(claims.client_id.as_deref(), Some("spa"));
+        assert_eq!((claims.auth_time, claims.acr), (1000, Some(Acr::Mfa)));
+
+        // Codes can only be redeemed once
+        let (status, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_grant");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_authorization_code_grant_rejected() -> anyhow::Result<()> {
+        let setup = setup()?;
+
+        for (verifier, redirect_uri) in [
+            ("wrong-verifier-wrong-verifier-wrong-verifier", REDIRECT_URI),
+            (VERIFIER, "https://evil.example.com/callback"),
+        ] {
+            let code = issue_code(&setup).await?;
+            let form = [
+                ("grant_type", "authorization_code"),
+                ("client_id", "spa"),
+                ("code", code.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", verifier),
+            ];
+            let (status, body) = post_token(&setup.app, &form, None).await?;
+
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body["error"], "invalid_grant");
+        }
+
+        let form = [("grant_type", "authorization_code"), ("client_id", "spa")];
+        let (_, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(body["error"], "invalid_request");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_grant() -> anyhow::Result<()> {
+        let setup = setup()?;
+        let code = issue_code(&setup).await?;
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("client_id", "spa"),
+            ("code", code.as_str()),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ];
+        let (_, body) = post_token(&setup.app, &form, None).await?;
+        let refresh_token = body["refresh_token"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("client_id", "spa"),
+            ("refresh_token", refresh_token.as_str()),
+            ("scope", "read"),
+        ];
+        let (status, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["scope"], "read");
+
+        let refreshed = body["refresh_token"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let claims = RefreshToken::decode(&refreshed, &TokenCodec::default(), "test")?
+            .get_claims()
+            .clone();
+
+        assert_eq!(claims.auth_time, 1000);
+
+        // Refresh tokens are rotated, so they can't be replayed
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("client_id", "spa"),
+            ("refresh_token", refresh_token.as_str()),
+        ];
+        let (status, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_grant");
+
+        // Scopes can't be widened
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("client_id", "spa"),
+            ("refresh_token", refreshed.as_str()),
+            ("scope", "read write"),
+        ];
+        let (_, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(body["error"], "invalid_scope");
+
+        // Refresh tokens issued outside of OAuth2 aren't bound to the client
+        let foreign = RefreshToken::try_new_for_model(&setup.user, &TokenCodec::default())?;
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("client_id", "spa"),
+            ("refresh_token", foreign.get_encoded()),
+        ];
+        let (_, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(body["error"], "invalid_grant");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_grant_concurrent_reuse() -> anyhow::Result<()> {
+        let setup = setup()?;
+        let code = issue_code(&setup).await?;
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("client_id", "spa"),
+            ("code", code.as_str()),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ];
+        let (_, body) = post_token(&setup.app, &form, None).await?;
+        let refresh_token = body["refresh_token"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("client_id", "spa"),
+            ("refresh_token", refresh_token.as_str()),
+        ];
+        let (first, second) = tokio::join!(
+            post_token(&setup.app, &form, None),
+            post_token(&setup.app, &form, None)
+        );
+        let mut statuses = [first?.0, second?.0];
+
+        statuses.sort();
+
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::BAD_REQUEST]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials_grant() -> anyhow::Result<()> {
+        let setup = setup()?;
+        let form = [("grant_type", "client_credentials")];
+        // reporting:s3cret
+        let (status, body) =
+            post_token(&setup.app, &form, Some("Basic cmVwb3J0aW5nOnMzY3JldA==")).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["scope"], "read");
+        assert!(body.get("refresh_token").is_none());
+
+        let encoded = body["access_token"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let claims = AuthenticationToken::decode(&encoded, &TokenCodec::default(), "test")?
+            .get_claims()
+            .clone();
+
+        assert_eq!(claims.sub, setup.service.id.to_string());
+
+        let form = [
+            ("grant_type", "client_credentials"),
+            ("client_id", "reporting"),
+            ("client_secret", "wrong"),
+        ];
+        let (status, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "invalid_client");
+
+        // Public clients aren't allowed to use the grant
+        let form = [("grant_type", "client_credentials"), ("client_id", "spa")];
+        let (_, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(body["error"], "unauthorized_client");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_grant_type() -> anyhow::Result<()> {
+        let setup = setup()?;
+        let form = [("grant_type", "password"), ("client_id", "spa")];
+        let (status, body) = post_token(&setup.app, &form, None).await?;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "unsupported_grant_type");
+
+        Ok(())
+    }
+}
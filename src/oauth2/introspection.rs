@@ -0,0 +1,298 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::model::GateKeeperModel;
+use crate::oauth2::{OAuthError, OAuthServer, TokenKind};
+use crate::tokens::Claims;
+use crate::GateKeeperResult;
+use axum::extract::rejection::FormRejection;
+use axum::extract::State;
+use axum::http::header::{CACHE_CONTROL, PRAGMA};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::{Form, Json};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+
+/// Form parameters of an introspection request
+#[derive(Default, Deserialize)]
+pub struct IntrospectionRequest {
+    pub token: Option<String>,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+impl Debug for IntrospectionRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntrospectionRequest")
+            .field("token_type_hint", &self.token_type_hint)
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Introspection response, see RFC 7662, section 2.2
+///
+/// Inactive tokens are described by `active` only.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+}
+
+impl IntrospectionResponse {
+    fn active(claims: &Claims, kind: TokenKind) -> Self {
+        let token_type = match kind {
+            TokenKind::AccessToken => "Bearer",
+            TokenKind::RefreshToken => kind.as_str(),
+        };
+
+        Self {
+            active: true,
+            sub: Some(claims.sub.clone()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            scope: claims.scope.clone(),
+            client_id: claims.client_id.clone(),
+            token_type: Some(token_type.to_string()),
+        }
+    }
+}
+
+/// OAuth2 token introspection handler (RFC 7662)
+///
+/// Only confidential clients may introspect tokens. Access tokens are
+/// reported with `token_type` `Bearer`, refresh tokens with `refresh_token`.
+/// Invalid, expired and revoked tokens are reported as `{"active": false}`.
+pub async fn introspect<U: GateKeeperModel + 'static>(
+    State(server): State<OAuthServer<U>>,
+    audit: AuditContext,
+    headers: HeaderMap,
+    request: Result<Form<IntrospectionRequest>, FormRejection>,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::introspect");
+
+    let result = introspect_token(&server, &headers, request).await;
+    let decoded = audit
+        .record_failure(AuditEventKind::TokenIntrospection, result)
+        .await?;
+    let mut event = audit.event(AuditEventKind::TokenIntrospection);
+    let response = match decoded {
+        Some((kind, claims)) => {
+            if let Ok(subject) = claims.sub.parse() {
+                event = event.subject(subject);
+            }
+
+            event = event.jti(claims.jti.as_deref());
+            IntrospectionResponse::active(&claims, kind)
+        }
+        None => {
+            event = event.reason("inactive");
+            IntrospectionResponse::default()
+        }
+    };
+
+    audit.record(event).await;
+
+    Ok((
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+        Json(response),
+    )
+        .into_response())
+}
+
+/// Authenticate the client and decode the token, returning `None` for
+/// inactive tokens
+async fn introspect_token<U: GateKeeperModel + 'static>(
+    server: &OAuthServer<U>,
+    headers: &HeaderMap,
+    request: Result<Form<IntrospectionRequest>, FormRejection>,
+) -> GateKeeperResult<Option<(TokenKind, Claims)>> {
+    let Form(request) = request.map_err(|e| OAuthError::InvalidRequest(e.body_text()))?;
+    let client = server
+        .authenticate_client(
+            headers,
+            request.client_id.as_deref(),
+            request.client_secret.as_deref(),
+        )
+        .await?;
+
+    if !client.is_confidential() {
+        return Err(OAuthError::InvalidClient.into());
+    }
+
+    let encoded = request
+        .token
+        .ok_or_else(|| OAuthError::InvalidRequest("Missing token".to_string()))?;
+
+    Ok(server
+        .decode_token(&encoded, request.token_type_hint.as_deref())
+        .await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{introspect, IntrospectionResponse};
+    use crate::authentication::{AuthenticationToken, RefreshToken};
+    use crate::oauth2::{GrantType, MemoryClientRegistry, OAuthClient, OAuthServer};
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Token, TokenCodec};
+    use axum::body::Body;
+    use axum::http::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE};
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use base64::prelude::BASE64_STANDARD;
+    use base64::Engine;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn setup(user: &TestUser) -> anyhow::Result<(OAuthServer<TestUser>, Router)> {
+        init_env();
+
+        let clients = MemoryClientRegistry::new();
+
+        clients.register(OAuthClient::public(
+            "spa",
+            "https://app.example.com/callback",
+        ));
+        clients.register(OAuthClient {
+            client_id: "billing".to_string(),
+            secret_hash: Some(OAuthClient::hash_secret("s3cret")),
+            redirect_uris: Vec::new(),
+            grant_types: vec![GrantType::ClientCredentials],
+            scopes: Vec::new(),
+            service_account: None,
+        });
+
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user.clone()])).build()?;
+        let server = OAuthServer::build(gatekeeper, clients).build();
+        let app = Router::new()
+            .route("/introspect", post(introspect::<TestUser>))
+            .with_state(server.clone());
+
+        Ok((server, app))
+    }
+
+    async fn post_introspect(
+        app: &Router,
+        body: String,
+        authorization: Option<String>,
+    ) -> anyhow::Result<(StatusCode, serde_json::Value)> {
+        let mut request =
+            Request::post("/introspect").header(CONTENT_TYPE, "application/x-www-form-urlencoded");
+
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        let response = app.clone().oneshot(request.body(Body::from(body))?).await?;
+        let status = response.status();
+
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok((status, serde_json::from_slice(&body)?))
+    }
+
+    fn basic_auth() -> Option<String> {
+        Some(format!(
+            "Basic {}",
+            BASE64_STANDARD.encode("billing:s3cret")
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_introspect_access_token() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (_, app) = setup(&user)?;
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let (status, body) =
+            post_introspect(&app, format!("token={}", token.get_encoded()), basic_auth()).await?;
+        let response = serde_json::from_value::<IntrospectionResponse>(body)?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.active);
+        assert_eq!(response.sub, Some(user.id.to_string()));
+        assert_eq!(response.exp, Some(token.get_claims().exp));
+        assert_eq!(response.iat, Some(token.get_claims().iat));
+        assert_eq!(response.token_type.as_deref(), Some("Bearer"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_introspect_refresh_token() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (_, app) = setup(&user)?;
+        let token = RefreshToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let body = format!(
+            "token={}&client_id=billing&client_secret=s3cret",
+            token.get_encoded()
+        );
+        let (_, body) = post_introspect(&app, body, None).await?;
+        let response = serde_json::from_value::<IntrospectionResponse>(body)?;
+
+        assert!(response.active);
+        assert_eq!(response.token_type.as_deref(), Some("refresh_token"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_introspect_inactive_tokens() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (server, app) = setup(&user)?;
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let claims = token.get_claims();
+
+        server
+            .gatekeeper()
+            .revocation_store()
+            .revoke(claims.jti.as_deref().unwrap_or_default(), claims.exp)
+            .await?;
+
+        for encoded in [token.get_encoded(), "garbage"] {
+            let (status, body) =
+                post_introspect(&app, format!("token={encoded}"), basic_auth()).await?;
+
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body, serde_json::json!({ "active": false }));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_introspect_requires_confidential_client() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (_, app) = setup(&user)?;
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+
+        for body in [
+            format!(
+                "token={}&client_id=billing&client_secret=wrong",
+                token.get_encoded()
+            ),
+            format!("token={}&client_id=spa", token.get_encoded()),
+        ] {
+            let (status, body) = post_introspect(&app, body, None).await?;
+
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["error"], "invalid_client");
+        }
+
+        Ok(())
+    }
+}
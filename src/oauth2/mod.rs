@@ -0,0 +1,27 @@
+//! Module containing a minimal OAuth2 authorization server (RFC 6749)
+//!
+//! The [`token`] handler supports the `authorization_code` grant with
+//! mandatory PKCE (RFC 7636), the `refresh_token` and the
+//! `client_credentials` grant, issuing `AuthenticationToken`s as access tokens
+//! and `RefreshToken`s as refresh tokens. Clients are looked up in a
+//! [`ClientRegistry`]. Authorization codes are created by the application's
+//! own consent page using [`OAuthServer::issue_authorization_code`].
+//! Resource servers can check tokens using the [`introspect`] handler
+//! (RFC 7662), clients revoke them using the [`revoke`] handler (RFC 7009).
+//!
+//! Only available on feature `oauth2`
+mod client;
+mod code;
+mod error;
+mod handler;
+mod introspection;
+mod revocation;
+mod server;
+
+pub use client::*;
+pub use code::*;
+pub use error::*;
+pub use handler::*;
+pub use introspection::*;
+pub use revocation::*;
+pub use server::*;
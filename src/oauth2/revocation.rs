@@ -0,0 +1,294 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::model::GateKeeperModel;
+use crate::oauth2::{OAuthError, OAuthServer};
+use crate::tokens::Claims;
+use crate::GateKeeperResult;
+use axum::extract::rejection::FormRejection;
+use axum::extract::State;
+use axum::http::header::{CACHE_CONTROL, PRAGMA};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Form;
+use serde::Deserialize;
+use std::fmt::{Debug, Formatter};
+
+/// Form parameters of a revocation request
+#[derive(Default, Deserialize)]
+pub struct RevocationRequest {
+    pub token: Option<String>,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+impl Debug for RevocationRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RevocationRequest")
+            .field("token_type_hint", &self.token_type_hint)
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// OAuth2 token revocation handler (RFC 7009)
+///
+/// Records the token's `jti` in the gatekeeper's
+/// [`RevocationStore`](crate::authentication::RevocationStore) until it
+/// expires. Tokens can only be revoked by the client they were issued to, see
+/// RFC 7009, section 2.1, so tokens issued by the crate's own login handlers
+/// are ignored. Responds `200 OK` for invalid, expired, already revoked and
+/// ignored tokens as well.
+pub async fn revoke<U: GateKeeperModel + 'static>(
+    State(server): State<OAuthServer<U>>,
+    audit: AuditContext,
+    headers: HeaderMap,
+    request: Result<Form<RevocationRequest>, FormRejection>,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::revoke");
+
+    let result = revoke_token(&server, &headers, request).await;
+    let revoked = audit
+        .record_failure(AuditEventKind::TokenRevocation, result)
+        .await?;
+    let mut event = audit.event(AuditEventKind::TokenRevocation);
+
+    match revoked {
+        Some(claims) => {
+            if let Ok(subject) = claims.sub.parse() {
+                event = event.subject(subject);
+            }
+
+            event = event.jti(claims.jti.as_deref());
+        }
+        None => event = event.reason("inactive"),
+    }
+
+    audit.record(event).await;
+
+    Ok((
+        StatusCode::OK,
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+    )
+        .into_response())
+}
+
+/// Authenticate the client and revoke the token, returning the claims of
+/// valid tokens
+async fn revoke_token<U: GateKeeperModel + 'static>(
+    server: &OAuthServer<U>,
+    headers: &HeaderMap,
+    request: Result<Form<RevocationRequest>, FormRejection>,
+) -> GateKeeperResult<Option<Claims>> {
+    let Form(request) = request.map_err(|e| OAuthError::InvalidRequest(e.body_text()))?;
+    let client = server
+        .authenticate_client(
+            headers,
+            request.client_id.as_deref(),
+            request.client_secret.as_deref(),
+        )
+        .await?;
+    let encoded = request
+        .token
+        .ok_or_else(|| OAuthError::InvalidRequest("Missing token".to_string()))?;
+    let Some((kind, claims)) = server
+        .decode_token(&encoded, request.token_type_hint.as_deref())
+        .await
+    else {
+        return Ok(None);
+    };
+
+    match claims.client_id.as_deref() {
+        Some(client_id) if client_id == client.client_id => {}
+        Some(_) => {
+            return Err(
+                OAuthError::InvalidGrant("Token was issued to another client".to_string()).into(),
+            );
+        }
+        // Tokens of the crate's own login handlers weren't issued to any client
+        None => return Ok(None),
+    }
+
+    if let Some(jti) = &claims.jti {
+        tracing::debug!("Revoking {} {jti}", kind.as_str());
+
+        server.gatekeeper().revoke(jti, claims.exp).await?;
+    }
+
+    Ok(Some(claims))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::revoke;
+    use crate::authentication::{AuthenticationToken, RefreshToken};
+    use crate::oauth2::{MemoryClientRegistry, OAuthClient, OAuthServer};
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Claims, Token, TokenCodec};
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn setup(user: &TestUser) -> anyhow::Result<(OAuthServer<TestUser>, Router)> {
+        init_env();
+
+        let clients = MemoryClientRegistry::new();
+
+        clients.register(OAuthClient::public(
+            "spa",
+            "https://app.example.com/callback",
+        ));
+        clients.register(OAuthClient::public(
+            "other",
+            "https://other.example.com/callback",
+        ));
+
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user.clone()])).build()?;
+        let server = OAuthServer::build(gatekeeper, clients).build();
+        let app = Router::new()
+            .route("/revoke", post(revoke::<TestUser>))
+            .with_state(server.clone());
+
+        Ok((server, app))
+    }
+
+    async fn post_revoke(app: &Router, body: String) -> anyhow::Result<(StatusCode, String)> {
+        let request = Request::post("/revoke")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(body))?;
+        let response = app.clone().oneshot(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok((status, String::from_utf8(body.to_vec())?))
+    }
+
+    fn template(client_id: &str) -> Claims {
+        Claims {
+            client_id: Some(client_id.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revoke_refresh_token() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (server, app) = setup(&user)?;
+        let token =
+            RefreshToken::try_new_from_template(&user, template("spa"), &TokenCodec::default())?;
+        let body = format!(
+            "token={}&token_type_hint=refresh_token&client_id=spa",
+            token.get_encoded()
+        );
+        let (status, _) = post_revoke(&app, body).await?;
+        let decoded = server
+            .gatekeeper()
+            .decode_for_user::<RefreshToken>(token.get_encoded())
+            .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(decoded.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revoke_access_token() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (server, app) = setup(&user)?;
+        let token = AuthenticationToken::try_new_from_template(
+            &user,
+            template("spa"),
+            &TokenCodec::default(),
+        )?;
+
+        // Wrong hints are ignored
+        let body = format!(
+            "token={}&token_type_hint=refresh_token&client_id=spa",
+            token.get_encoded()
+        );
+        let (status, _) = post_revoke(&app, body).await?;
+        let jti = token.get_claims().jti.as_deref().unwrap_or_default();
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            server
+                .gatekeeper()
+                .revocation_store()
+                .is_revoked(jti)
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revoke_ignores_tokens_without_client() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (server, app) = setup(&user)?;
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let body = format!("token={}&client_id=spa", token.get_encoded());
+        let (status, _) = post_revoke(&app, body).await?;
+        let jti = token.get_claims().jti.as_deref().unwrap_or_default();
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            !server
+                .gatekeeper()
+                .revocation_store()
+                .is_revoked(jti)
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revoke_unknown_token() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (_, app) = setup(&user)?;
+        let (status, body) = post_revoke(&app, "token=garbage&client_id=spa".to_string()).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revoke_other_clients_token() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (server, app) = setup(&user)?;
+        let token =
+            RefreshToken::try_new_from_template(&user, template("spa"), &TokenCodec::default())?;
+        let body = format!("token={}&client_id=other", token.get_encoded());
+        let (status, body) = post_revoke(&app, body).await?;
+        let jti = token.get_claims().jti.as_deref().unwrap_or_default();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("invalid_grant"));
+        assert!(
+            !server
+                .gatekeeper()
+                .revocation_store()
+                .is_revoked(jti)
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revoke_requires_client() -> anyhow::Result<()> {
+        let user = TestUser::new("alice", "");
+        let (_, app) = setup(&user)?;
+        let (status, _) = post_revoke(&app, "token=garbage&client_id=unknown".to_string()).await?;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use crate::authentication::{AuthenticationToken, RefreshToken};
+use crate::basic::BasicCredentials;
+use crate::model::GateKeeperModel;
+use crate::oauth2::{
+    AuthorizationCodeStore, AuthorizationGrant, ClientRegistry, GrantType,
+    MemoryAuthorizationCodeStore, OAuthClient, OAuthError,
+};
+use crate::tokens::{Claims, Token};
+use crate::{GateKeeper, GateKeeperResult};
+use axum::http::HeaderMap;
+use data_encoding::BASE64URL_NOPAD;
+use rand_core::{OsRng, RngCore};
+use std::sync::Arc;
+
+/// State of the OAuth2 handlers
+///
+/// Uses the gatekeeper's user repository to look up the users tokens are
+/// issued for.
+pub struct OAuthServer<U> {
+    gatekeeper: GateKeeper<U>,
+    clients: Arc<dyn ClientRegistry>,
+    codes: Arc<dyn AuthorizationCodeStore>,
+}
+
+impl<U> OAuthServer<U> {
+    /// Create a builder for OAuthServer using `clients` to look up clients
+    pub fn build(
+        gatekeeper: GateKeeper<U>,
+        clients: impl ClientRegistry + 'static,
+    ) -> OAuthServerBuilder<U> {
+        OAuthServerBuilder {
+            gatekeeper,
+            clients: Arc::new(clients),
+            codes: None,
+        }
+    }
+
+    /// Return the gatekeeper
+    pub fn gatekeeper(&self) -> &GateKeeper<U> {
+        &self.gatekeeper
+    }
+
+    /// Return the client registry
+    pub fn clients(&self) -> &dyn ClientRegistry {
+        self.clients.as_ref()
+    }
+
+    /// Return the authorization code store
+    pub fn codes(&self) -> &dyn AuthorizationCodeStore {
+        self.codes.as_ref()
+    }
+
+    /// Authenticate the client of a request using HTTP Basic or, if there's no
+    /// `Authorization` header, the `client_id` and `client_secret` parameters
+    ///
+    /// Public clients authenticate by sending their `client_id` only.
+    pub async fn authenticate_client(
+        &self,
+        headers: &HeaderMap,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+    ) -> GateKeeperResult<OAuthClient> {
+        let (client_id, secret) = match BasicCredentials::from_headers(headers) {
+            Some(Ok(credentials)) => (credentials.username, Some(credentials.password)),
+            Some(Err(_)) => return Err(OAuthError::InvalidClient.into()),
+            None => (
+                client_id
+                    .ok_or_else(|| OAuthError::InvalidRequest("Missing client_id".to_string()))?
+                    .to_string(),
+                client_secret.map(str::to_string),
+            ),
+        };
+        let client = self
+            .clients
+            .find_client(&client_id)
+            .await?
+            .ok_or(OAuthError::InvalidClient)?;
+        let authenticated = match secret {
+            Some(secret) => client.verify_secret(&secret),
+            None => !client.is_confidential(),
+        };
+
+        if !authenticated {
+            return Err(OAuthError::InvalidClient.into());
+        }
+
+        Ok(client)
+    }
+
+    /// Store `grant` and return the authorization code to redirect the user
+    /// back to the client with
+    ///
+    /// Call it from the consent page once the user approved the client's
+    /// authorization request. Fails if the client isn't allowed to use the
+    /// `authorization_code` grant, the redirect URI isn't registered or a
+    /// scope isn't allowed.
+    pub async fn issue_authorization_code(
+        &self,
+        grant: AuthorizationGrant,
+    ) -> GateKeeperResult<String> {
+        let client = self
+            .clients
+            .find_client(&grant.client_id)
+            .await?
+            .ok_or(OAuthError::InvalidClient)?;
+
+        if !client.allows_grant(GrantType::AuthorizationCode) {
+            return Err(OAuthError::UnauthorizedClient(
+                GrantType::AuthorizationCode.as_str().to_string(),
+            )
+            .into());
+        }
+
+        if !client.redirect_uris.contains(&grant.redirect_uri) {
+            return Err(OAuthError::InvalidRequest("Unregistered redirect_uri".to_string()).into());
+        }
+
+        if let Some(scope) = &grant.scope {
+            if !client.allows_scope(scope) {
+                return Err(OAuthError::InvalidScope(scope.clone()).into());
+            }
+        }
+
+        let mut code = [0u8; 32];
+
+        OsRng.fill_bytes(&mut code);
+
+        let code = BASE64URL_NOPAD.encode(&code);
+
+        self.codes.insert(hash_code(&code), grant).await?;
+
+        Ok(code)
+    }
+}
+
+impl<U: GateKeeperModel + 'static> OAuthServer<U> {
+    /// Decode `encoded` as access or refresh token, trying the type given by
+    /// `token_type_hint` first
+    ///
+    /// Returns `None` for invalid, expired or revoked tokens.
+    pub(crate) async fn decode_token(
+        &self,
+        encoded: &str,
+        token_type_hint: Option<&str>,
+    ) -> Option<(TokenKind, Claims)> {
+        let kinds = match token_type_hint {
+            Some("refresh_token") => [TokenKind::RefreshToken, TokenKind::AccessToken],
+            _ => [TokenKind::AccessToken, TokenKind::RefreshToken],
+        };
+
+        for kind in kinds {
+            let claims = match kind {
+                TokenKind::AccessToken => self
+                    .gatekeeper
+                    .decode_for_user::<AuthenticationToken>(encoded)
+                    .await
+                    .map(|(_, token)| token.into_claims()),
+                TokenKind::RefreshToken => self
+                    .gatekeeper
+                    .decode_for_user::<RefreshToken>(encoded)
+                    .await
+                    .map(|(_, token)| token.into_claims()),
+            };
+
+            if let Ok(claims) = claims {
+                return Some((kind, claims));
+            }
+        }
+
+        None
+    }
+}
+
+impl<U> Clone for OAuthServer<U> {
+    fn clone(&self) -> Self {
+        Self {
+            gatekeeper: self.gatekeeper.clone(),
+            clients: self.clients.clone(),
+            codes: self.codes.clone(),
+        }
+    }
+}
+
+pub struct OAuthServerBuilder<U> {
+    gatekeeper: GateKeeper<U>,
+    clients: Arc<dyn ClientRegistry>,
+    codes: Option<Arc<dyn AuthorizationCodeStore>>,
+}
+
+impl<U> OAuthServerBuilder<U> {
+    /// Set field `codes`, defaults to [`MemoryAuthorizationCodeStore`]
+    pub fn code_store(mut self, codes: impl AuthorizationCodeStore + 'static) -> Self {
+        self.codes = Some(Arc::new(codes));
+        self
+    }
+
+    /// Actually create the OAuthServer
+    pub fn build(self) -> OAuthServer<U> {
+        OAuthServer {
+            gatekeeper: self.gatekeeper,
+            clients: self.clients,
+            codes: self
+                .codes
+                .unwrap_or_else(|| Arc::new(MemoryAuthorizationCodeStore::new())),
+        }
+    }
+}
+
+/// Kind of the tokens issued by the [`token`](crate::oauth2::token) handler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// An `AuthenticationToken`
+    AccessToken,
+    /// A `RefreshToken`
+    RefreshToken,
+}
+
+impl TokenKind {
+    /// Return the token type name used by RFC 7009 and RFC 7662
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenKind::AccessToken => "access_token",
+            TokenKind::RefreshToken => "refresh_token",
+        }
+    }
+}
+
+/// Hash authorization `code` for storage or lookup
+pub(crate) fn hash_code(code: &str) -> String {
+    OAuthClient::hash_secret(code)
+}
@@ -0,0 +1,41 @@
+use crate::tokens::Acr;
+use serde::{Deserialize, Serialize};
+
+/// Claims of a validated ID token
+///
+/// `iss`, `aud` and `exp` are checked during validation, all claims not
+/// listed here are available in [`IdTokenClaims::other`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    /// Identifier of the user at the provider, unique per issuer
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    #[serde(default)]
+    pub auth_time: Option<usize>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub azp: Option<String>,
+    #[serde(default)]
+    pub amr: Vec<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl IdTokenClaims {
+    /// Return the authentication context class to issue local tokens with
+    ///
+    /// Providers signal multi-factor logins using the `amr` claim values of
+    /// RFC 8176, see [`Acr::from_amr`].
+    pub fn acr(&self) -> Acr {
+        Acr::from_amr(self.amr.iter().map(String::as_str))
+    }
+}
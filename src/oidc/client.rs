@@ -0,0 +1,295 @@
+use crate::jwks::KeySet;
+use crate::oidc::{IdTokenClaims, OidcError, ProviderMetadata};
+use crate::util::pkce_challenge;
+use crate::GateKeeperResult;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{Algorithm, Validation};
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+
+/// Client of a single OpenID Connect provider
+pub struct OidcClient {
+    http: reqwest::Client,
+    metadata: ProviderMetadata,
+    keys: KeySet,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    scopes: Vec<String>,
+}
+
+/// Parameters of a started login, which have to be remembered until the
+/// provider redirects back
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AuthorizationRequest {
+    /// URL to redirect the user to
+    pub url: String,
+    pub state: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+}
+
+/// Successful response of the provider's token endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub id_token: Option<String>,
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OidcErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl OidcClient {
+    /// Create a builder for OidcClient
+    pub fn build(issuer: &str, client_id: &str, redirect_uri: &str) -> OidcClientBuilder {
+        OidcClientBuilder {
+            issuer: issuer.to_string(),
+            client_id: client_id.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+            client_secret: None,
+            scopes: None,
+            http: None,
+        }
+    }
+
+    /// Return the provider's metadata
+    pub fn metadata(&self) -> &ProviderMetadata {
+        &self.metadata
+    }
+
+    /// Start a login with a fresh state, nonce and PKCE verifier
+    pub fn authorization_request(&self) -> GateKeeperResult<AuthorizationRequest> {
+        let state = random_string();
+        let nonce = random_string();
+        let pkce_verifier = random_string();
+        let challenge = pkce_challenge(&pkce_verifier);
+        let scope = self.scopes.join(" ");
+        let url = reqwest::Url::parse_with_params(
+            &self.metadata.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_uri),
+                ("scope", &scope),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::Discovery(format!("Invalid authorization endpoint: {e}")))?;
+
+        Ok(AuthorizationRequest {
+            url: url.to_string(),
+            state,
+            nonce,
+            pkce_verifier,
+        })
+    }
+
+    /// Exchange the authorization `code` at the token endpoint
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        pkce_verifier: &str,
+    ) -> GateKeeperResult<OidcTokenResponse> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_uri),
+            ("client_id", &self.client_id),
+            ("code_verifier", pkce_verifier),
+        ];
+
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+
+        let response = self
+            .http
+            .post(&self.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(OidcError::Http)?;
+
+        if !response.status().is_success() {
+            let error = response
+                .json::<OidcErrorResponse>()
+                .await
+                .map_err(OidcError::Http)?;
+
+            return Err(OidcError::Provider {
+                error: error.error,
+                description: error.error_description,
+            }
+            .into());
+        }
+
+        Ok(response
+            .json::<OidcTokenResponse>()
+            .await
+            .map_err(OidcError::Http)?)
+    }
+
+    /// Validate `id_token` against the provider's key set, issuer, this
+    /// client's id and the `nonce` sent with the authorization request
+    pub async fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> GateKeeperResult<IdTokenClaims> {
+        let mut validation = Validation::new(Algorithm::RS256);
+
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = self
+            .keys
+            .decode::<IdTokenClaims>(id_token, &validation)
+            .await?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidIdToken("Nonce mismatch".to_string()).into());
+        }
+
+        if claims
+            .azp
+            .as_ref()
+            .is_some_and(|azp| *azp != self.client_id)
+        {
+            return Err(OidcError::InvalidIdToken("Unexpected azp".to_string()).into());
+        }
+
+        Ok(claims)
+    }
+}
+
+pub struct OidcClientBuilder {
+    issuer: String,
+    client_id: String,
+    redirect_uri: String,
+    client_secret: Option<String>,
+    scopes: Option<Vec<String>>,
+    http: Option<reqwest::Client>,
+}
+
+impl OidcClientBuilder {
+    /// Set field `client_secret`, sent to the token endpoint as form parameter
+    pub fn client_secret(mut self, client_secret: &str) -> Self {
+        self.client_secret = Some(client_secret.to_string());
+        self
+    }
+
+    /// Set field `scopes`, defaults to `openid email profile`
+    pub fn scopes(mut self, scopes: &[&str]) -> Self {
+        self.scopes = Some(scopes.iter().map(|scope| scope.to_string()).collect());
+        self
+    }
+
+    /// Set field `http`, the client used to contact the provider
+    pub fn http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// Create the client by fetching the provider's discovery document
+    pub async fn discover(self) -> GateKeeperResult<OidcClient> {
+        let http = self.http.clone().unwrap_or_default();
+        let metadata = ProviderMetadata::discover(&http, &self.issuer).await?;
+
+        Ok(self.build(metadata))
+    }
+
+    /// Actually create the client using already known provider `metadata`
+    pub fn build(self, metadata: ProviderMetadata) -> OidcClient {
+        let http = self.http.unwrap_or_default();
+        let mut scopes = self
+            .scopes
+            .unwrap_or_else(|| ["openid", "email", "profile"].map(str::to_string).to_vec());
+
+        if !scopes.iter().any(|scope| scope == "openid") {
+            scopes.insert(0, "openid".to_string());
+        }
+
+        OidcClient {
+            keys: KeySet::remote_with_client(http.clone(), &metadata.jwks_uri),
+            http,
+            metadata,
+            client_id: self.client_id,
+            client_secret: self.client_secret,
+            redirect_uri: self.redirect_uri,
+            scopes,
+        }
+    }
+}
+
+/// Return 256 random bits, base64url encoded
+fn random_string() -> String {
+    let mut bytes = [0u8; 32];
+
+    OsRng.fill_bytes(&mut bytes);
+
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OidcClient;
+    use crate::oidc::ProviderMetadata;
+    use crate::util::pkce_challenge;
+
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata {
+            issuer: "https://idp.example.com".to_string(),
+            authorization_endpoint: "https://idp.example.com/authorize".to_string(),
+            token_endpoint: "https://idp.example.com/token".to_string(),
+            jwks_uri: "https://idp.example.com/jwks".to_string(),
+            userinfo_endpoint: None,
+            id_token_signing_alg_values_supported: vec!["RS256".to_string()],
+            code_challenge_methods_supported: vec!["S256".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_authorization_request() -> anyhow::Result<()> {
+        let client = OidcClient::build("https://idp.example.com", "app", "https://app/callback")
+            .scopes(&["email"])
+            .build(metadata());
+        let request = client.authorization_request()?;
+        let url = reqwest::Url::parse(&request.url)?;
+        let params = url
+            .query_pairs()
+            .into_owned()
+            .collect::<std::collections::HashMap<_, _>>();
+
+        assert!(request
+            .url
+            .starts_with("https://idp.example.com/authorize?"));
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], "app");
+        assert_eq!(params["redirect_uri"], "https://app/callback");
+        assert_eq!(params["scope"], "openid email");
+        assert_eq!(params["state"], request.state);
+        assert_eq!(params["nonce"], request.nonce);
+        assert_eq!(
+            params["code_challenge"],
+            pkce_challenge(&request.pkce_verifier)
+        );
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_ne!(request, client.authorization_request()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use crate::error::GateKeeperError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+#[derive(thiserror::Error, Debug)]
+pub enum OidcError {
+    #[error("Error requesting provider: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid discovery document: {0}")]
+    Discovery(String),
+    #[error("Provider returned error {error}: {description:?}")]
+    Provider {
+        error: String,
+        description: Option<String>,
+    },
+    #[error("Missing or mismatching login state")]
+    InvalidState,
+    #[error("Invalid ID token: {0}")]
+    InvalidIdToken(String),
+    #[error("No local user for identity {0}")]
+    UnknownIdentity(String),
+}
+
+impl OidcError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            OidcError::Http(_) | OidcError::Discovery(_) => "identity_provider_unavailable",
+            OidcError::Provider { .. } | OidcError::InvalidState | OidcError::InvalidIdToken(_) => {
+                "identity_provider_login_failed"
+            }
+            OidcError::UnknownIdentity(_) => "unknown_identity",
+        }
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            OidcError::Http(_) | OidcError::Discovery(_) => "Error contacting identity provider",
+            OidcError::Provider { .. } | OidcError::InvalidState | OidcError::InvalidIdToken(_) => {
+                "Login with identity provider failed"
+            }
+            OidcError::UnknownIdentity(_) => "Unknown identity",
+        }
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            OidcError::Http(_) | OidcError::Discovery(_) => StatusCode::BAD_GATEWAY,
+            OidcError::Provider { .. } | OidcError::InvalidState | OidcError::InvalidIdToken(_) => {
+                StatusCode::UNAUTHORIZED
+            }
+            OidcError::UnknownIdentity(_) => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+impl IntoResponse for OidcError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
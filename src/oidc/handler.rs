@@ -0,0 +1,469 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::model::GateKeeperModel;
+use crate::oidc::{IdTokenClaims, OidcClient, OidcError};
+use crate::tokens::TokenCodec;
+use crate::GateKeeperResult;
+use axum::extract::{Query, State};
+use axum::http::header::{COOKIE, LOCATION, SET_COOKIE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use cookie::time::Duration;
+use cookie::Cookie;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Map an external identity to a local user
+#[async_trait::async_trait]
+pub trait OidcUserMapper: Send + Sync {
+    type User: GateKeeperModel;
+
+    /// Return the local user for the validated ID token `claims`, e.g. by
+    /// looking it up by `iss` and `sub` or creating it on first login
+    ///
+    /// Returning `None` rejects the login.
+    async fn map_user(&self, claims: &IdTokenClaims) -> GateKeeperResult<Option<Self::User>>;
+}
+
+#[async_trait::async_trait]
+impl<T: OidcUserMapper + ?Sized> OidcUserMapper for Arc<T> {
+    type User = T::User;
+
+    async fn map_user(&self, claims: &IdTokenClaims) -> GateKeeperResult<Option<Self::User>> {
+        self.as_ref().map_user(claims).await
+    }
+}
+
+/// State of the [`oidc_login`] and [`oidc_callback`] handlers
+pub struct OidcLogin<U> {
+    client: Arc<OidcClient>,
+    mapper: Arc<dyn OidcUserMapper<User = U>>,
+    token_codec: Arc<TokenCodec>,
+}
+
+impl<U> OidcLogin<U> {
+    /// Name of the cookie holding state, nonce and PKCE verifier during login
+    pub const COOKIE_NAME: &'static str = "oidc_login";
+
+    pub fn new(client: OidcClient, mapper: impl OidcUserMapper<User = U> + 'static) -> Self {
+        Self {
+            client: Arc::new(client),
+            mapper: Arc::new(mapper),
+            token_codec: Default::default(),
+        }
+    }
+
+    /// Set field `token_codec`, usually to the gatekeeper's
+    /// [`token_codec`](crate::GateKeeper::token_codec), defaults to [`TokenCodec::default`]
+    pub fn token_codec(mut self, token_codec: Arc<TokenCodec>) -> Self {
+        self.token_codec = token_codec;
+        self
+    }
+
+    /// Return the provider's client
+    pub fn client(&self) -> &OidcClient {
+        &self.client
+    }
+}
+
+impl<U> Clone for OidcLogin<U> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            token_codec: self.token_codec.clone(),
+            mapper: self.mapper.clone(),
+        }
+    }
+}
+
+/// Query parameters the provider redirects back with
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Handler redirecting the user to the provider's authorization endpoint
+pub async fn oidc_login<U: GateKeeperModel + 'static>(
+    State(login): State<OidcLogin<U>>,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::oidc_login");
+
+    let request = login.client.authorization_request()?;
+    // Lax, as the cookie has to be sent on the top-level redirect back
+    let cookie = Cookie::build((
+        OidcLogin::<U>::COOKIE_NAME,
+        format!(
+            "{}.{}.{}",
+            request.state, request.nonce, request.pkce_verifier
+        ),
+    ))
+    .path("/")
+    .max_age(Duration::minutes(10))
+    .secure(true)
+    .http_only(true)
+    .same_site(cookie::SameSite::Lax)
+    .build();
+
+    Ok((
+        StatusCode::FOUND,
+        [(LOCATION, request.url), (SET_COOKIE, cookie.to_string())],
+    )
+        .into_response())
+}
+
+/// Handler completing the login once the provider redirected back
+///
+/// Responds like the `login` handler, with the `AuthenticationToken` as JSON
+/// body and the `RefreshToken` as cookie. `auth_time` is taken from the ID
+/// token, the `acr` from its `amr` claim, see [`IdTokenClaims::acr`].
+pub async fn oidc_callback<U: GateKeeperModel + 'static>(
+    State(login): State<OidcLogin<U>>,
+    audit: AuditContext,
+    Query(params): Query<OidcCallbackParams>,
+    headers: HeaderMap,
+) -> GateKeeperResult<Response> {
+    tracing::debug!("Using handler::oidc_callback");
+
+    let result = authenticate_callback(&login, params, &headers).await;
+    let (user, claims) = audit.record_failure(AuditEventKind::Login, result).await?;
+    let auth_time = claims
+        .auth_time
+        .unwrap_or_else(|| Utc::now().timestamp() as usize);
+    let mut response = crate::authentication::token_response(
+        &user,
+        auth_time,
+        Some(claims.acr()),
+        &login.token_codec,
+    )?;
+    let removal = Cookie::build((OidcLogin::<U>::COOKIE_NAME, ""))
+        .path("/")
+        .max_age(Duration::ZERO)
+        .build();
+
+    if let Ok(value) = removal.to_string().parse() {
+        response.headers_mut().append(SET_COOKIE, value);
+    }
+
+    audit
+        .record(audit.event(AuditEventKind::Login).subject(user.id()))
+        .await;
+
+    Ok(response)
+}
+
+/// Check the state, exchange the code and map the ID token to a local user
+async fn authenticate_callback<U: GateKeeperModel + 'static>(
+    login: &OidcLogin<U>,
+    params: OidcCallbackParams,
+    headers: &HeaderMap,
+) -> GateKeeperResult<(U, IdTokenClaims)> {
+    if let Some(error) = params.error {
+        return Err(OidcError::Provider {
+            error,
+            description: params.error_description,
+        }
+        .into());
+    }
+
+    let cookie =
+        login_cookie(headers, OidcLogin::<U>::COOKIE_NAME).ok_or(OidcError::InvalidState)?;
+    let mut parts = cookie.splitn(3, '.');
+    let (Some(state), Some(nonce), Some(pkce_verifier)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(OidcError::InvalidState.into());
+    };
+
+    if params.state.as_deref() != Some(state) {
+        return Err(OidcError::InvalidState.into());
+    }
+
+    let code = params.code.ok_or_else(|| OidcError::Provider {
+        error: "invalid_request".to_string(),
+        description: Some("Missing code".to_string()),
+    })?;
+    let tokens = login.client.exchange_code(&code, pkce_verifier).await?;
+    let id_token = tokens
+        .id_token
+        .ok_or_else(|| OidcError::InvalidIdToken("Missing ID token".to_string()))?;
+    let claims = login.client.validate_id_token(&id_token, nonce).await?;
+    let user = login
+        .mapper
+        .map_user(&claims)
+        .await?
+        .ok_or_else(|| OidcError::UnknownIdentity(claims.sub.clone()))?;
+
+    Ok((user, claims))
+}
+
+/// Read cookie `name` from the request's cookies
+fn login_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(Cookie::split_parse)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == name)
+        .map(|cookie| cookie.value().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{oidc_callback, oidc_login, OidcLogin, OidcUserMapper};
+    use crate::authentication::{AuthenticationToken, UserRepository};
+    use crate::oidc::{IdTokenClaims, OidcClient};
+    use crate::test_support::{init_env, test_jwks, test_rsa_key, TestRepository, TestUser};
+    use crate::tokens::{Acr, Token, TokenCodec};
+    use crate::util::pkce_challenge;
+    use crate::GateKeeperResult;
+    use axum::body::Body;
+    use axum::extract::{Query, State};
+    use axum::http::header::{COOKIE, LOCATION, SET_COOKIE};
+    use axum::http::{Request, StatusCode};
+    use axum::response::{IntoResponse, Redirect, Response};
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use http_body_util::BodyExt;
+    use jsonwebtoken::{Algorithm, Header};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    /// Codes issued by the mock provider with their PKCE challenge and nonce
+    type Codes = Arc<Mutex<HashMap<String, (String, String)>>>;
+
+    #[derive(Clone)]
+    struct MockIdp {
+        issuer: String,
+        codes: Codes,
+    }
+
+    /// Serve a minimal OpenID provider on a random local port
+    async fn mock_idp() -> anyhow::Result<String> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let issuer = format!("http://{}", listener.local_addr()?);
+        let idp = MockIdp {
+            issuer: issuer.clone(),
+            codes: Codes::default(),
+        };
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(|| async { Json(test_jwks()) }))
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .with_state(idp);
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Ok(issuer)
+    }
+
+    async fn discovery(State(idp): State<MockIdp>) -> Json<serde_json::Value> {
+        Json(json!({
+            "issuer": idp.issuer,
+            "authorization_endpoint": format!("{}/authorize", idp.issuer),
+            "token_endpoint": format!("{}/token", idp.issuer),
+            "jwks_uri": format!("{}/jwks", idp.issuer),
+            "id_token_signing_alg_values_supported": ["RS256"],
+            "code_challenge_methods_supported": ["S256"],
+        }))
+    }
+
+    async fn authorize(
+        State(idp): State<MockIdp>,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> Redirect {
+        let code = uuid::Uuid::new_v4().to_string();
+
+        idp.codes.lock().unwrap().insert(
+            code.clone(),
+            (params["code_challenge"].clone(), params["nonce"].clone()),
+        );
+
+        Redirect::to(&format!(
+            "{}?code={code}&state={}",
+            params["redirect_uri"], params["state"]
+        ))
+    }
+
+    async fn token(
+        State(idp): State<MockIdp>,
+        Form(params): Form<HashMap<String, String>>,
+    ) -> Response {
+        let Some((challenge, nonce)) = idp.codes.lock().unwrap().remove(&params["code"]) else {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_grant"})),
+            )
+                .into_response();
+        };
+
+        if pkce_challenge(&params["code_verifier"]) != challenge {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_grant"})),
+            )
+                .into_response();
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let mut header = Header::new(Algorithm::RS256);
+
+        header.kid = Some("test-key".to_string());
+
+        let claims = json!({
+            "iss": idp.issuer,
+            "sub": "idp-alice",
+            "aud": params["client_id"],
+            "exp": now + 60,
+            "iat": now,
+            "auth_time": now - 5,
+            "nonce": nonce,
+            "email": "alice@example.com",
+            "amr": ["pwd", "otp"],
+        });
+        let id_token = jsonwebtoken::encode(&header, &claims, &test_rsa_key()).unwrap();
+
+        Json(json!({
+            "access_token": "idp-access-token",
+            "token_type": "Bearer",
+            "id_token": id_token,
+        }))
+        .into_response()
+    }
+
+    /// Map identities by email to users' login
+    struct EmailMapper(TestRepository);
+
+    #[async_trait::async_trait]
+    impl OidcUserMapper for EmailMapper {
+        type User = TestUser;
+
+        async fn map_user(&self, claims: &IdTokenClaims) -> GateKeeperResult<Option<TestUser>> {
+            match &claims.email {
+                Some(email) => self.0.find_by_login(email).await,
+                None => Ok(None),
+            }
+        }
+    }
+
+    async fn app(login: &str) -> anyhow::Result<Router> {
+        init_env();
+
+        let issuer = mock_idp().await?;
+        let client = OidcClient::build(&issuer, "app", "https://app.example.com/callback")
+            .discover()
+            .await?;
+        let users = TestRepository::with_users([TestUser::new(login, "")]);
+
+        Ok(Router::new()
+            .route("/login", get(oidc_login::<TestUser>))
+            .route("/callback", get(oidc_callback::<TestUser>))
+            .with_state(OidcLogin::new(client, EmailMapper(users))))
+    }
+
+    /// Run the login up to the provider's redirect back, returning the login
+    /// cookie and the callback's query string
+    async fn authorize_at_idp(app: &Router) -> anyhow::Result<(String, String)> {
+        let response = app
+            .clone()
+            .oneshot(Request::get("/login").body(Body::empty())?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let cookie = response.headers()[SET_COOKIE].to_str()?;
+        let cookie = cookie.split(';').next().unwrap_or_default().to_string();
+        let location = response.headers()[LOCATION].to_str()?;
+        let idp = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let redirect = idp.get(location).send().await?;
+        let callback = reqwest::Url::parse(redirect.headers()[LOCATION].to_str()?)?;
+
+        Ok((cookie, callback.query().unwrap_or_default().to_string()))
+    }
+
+    async fn callback(app: &Router, cookie: &str, query: &str) -> anyhow::Result<Response> {
+        let request = Request::get(format!("/callback?{query}"))
+            .header(COOKIE, cookie)
+            .body(Body::empty())?;
+
+        Ok(app.clone().oneshot(request).await?)
+    }
+
+    #[tokio::test]
+    async fn test_oidc_login() -> anyhow::Result<()> {
+        let app = app("alice@example.com").await?;
+        let (cookie, query) = authorize_at_idp(&app).await?;
+        let response = callback(&app, &cookie, &query).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let cookies = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+
+        assert!(cookies.iter().any(|c| c.starts_with("refresh_token=")));
+        assert!(cookies.iter().any(|c| c.starts_with("oidc_login=;")));
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let body = serde_json::from_slice::<serde_json::Value>(&body)?;
+        let encoded = body["encoded"].as_str().unwrap_or_default().to_string();
+        let token = AuthenticationToken::decode(&encoded, &TokenCodec::default(), "test")?;
+
+        assert_eq!(token.get_claims().acr, Some(Acr::Mfa));
+
+        // Codes can only be exchanged once
+        let response = callback(&app, &cookie, &query).await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oidc_state_mismatch() -> anyhow::Result<()> {
+        let app = app("alice@example.com").await?;
+        let (_, query) = authorize_at_idp(&app).await?;
+        let (other_cookie, _) = authorize_at_idp(&app).await?;
+
+        for cookie in [other_cookie.as_str(), ""] {
+            let response = callback(&app, cookie, &query).await?;
+
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oidc_unknown_identity() -> anyhow::Result<()> {
+        let app = app("bob@example.com").await?;
+        let (cookie, query) = authorize_at_idp(&app).await?;
+        let response = callback(&app, &cookie, &query).await?;
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oidc_provider_error() -> anyhow::Result<()> {
+        let app = app("alice@example.com").await?;
+        let (cookie, _) = authorize_at_idp(&app).await?;
+        let response = callback(&app, &cookie, "error=access_denied").await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+//! Module containing an OpenID Connect relying party for "Sign in with ..."
+//!
+//! The [`oidc_login`] handler redirects users to the provider using the
+//! authorization code flow with PKCE, remembering state, nonce and code
+//! verifier in a short-lived cookie. The [`oidc_callback`] handler exchanges
+//! the code, validates the ID token against the provider's JWKS, maps the
+//! identity to a local user using an [`OidcUserMapper`] and responds like the
+//! `login` handler.
+//!
+//! Only available on feature `oidc`
+mod claims;
+mod client;
+mod error;
+mod handler;
+mod provider;
+
+pub use claims::*;
+pub use client::*;
+pub use error::*;
+pub use handler::*;
+pub use provider::*;
@@ -0,0 +1,60 @@
+use crate::oidc::OidcError;
+use crate::GateKeeperResult;
+use serde::{Deserialize, Serialize};
+
+/// Relevant parts of a provider's discovery document
+///
+/// See [OpenID Connect Discovery](https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    #[serde(default)]
+    pub code_challenge_methods_supported: Vec<String>,
+}
+
+impl ProviderMetadata {
+    /// Fetch the discovery document of `issuer`
+    ///
+    /// Fails if the document names another issuer, as required by the spec.
+    pub async fn discover(client: &reqwest::Client, issuer: &str) -> GateKeeperResult<Self> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let metadata = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(OidcError::Http)?
+            .json::<Self>()
+            .await
+            .map_err(OidcError::Http)?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(OidcError::Discovery(format!(
+                "Expected issuer {issuer}, got {}",
+                metadata.issuer
+            ))
+            .into());
+        }
+
+        if !metadata.code_challenge_methods_supported.is_empty()
+            && !metadata
+                .code_challenge_methods_supported
+                .iter()
+                .any(|method| method == "S256")
+        {
+            tracing::warn!("Provider {issuer} doesn't announce support for PKCE with S256");
+        }
+
+        Ok(metadata)
+    }
+}
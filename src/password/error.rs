@@ -0,0 +1,31 @@
+use crate::error::GateKeeperError;
+use axum::response::{IntoResponse, Response};
+
+#[derive(thiserror::Error, Debug)]
+pub enum PasswordError {
+    #[error("Error hashing password: {0}")]
+    Hash(#[from] argon2::password_hash::Error),
+    #[error("Invalid Argon2 parameters: {0}")]
+    Params(#[from] argon2::Error),
+}
+
+impl PasswordError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        "internal_error"
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            PasswordError::Hash(_) => "Error hashing password",
+            PasswordError::Params(_) => "Invalid password hashing parameters",
+        }
+    }
+}
+
+impl IntoResponse for PasswordError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
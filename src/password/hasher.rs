@@ -0,0 +1,179 @@
+use crate::password::PasswordError;
+use crate::GateKeeperResult;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier as _, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+
+/// Argon2id password hasher producing and verifying PHC strings
+#[derive(Debug, Clone)]
+pub struct PasswordHasher {
+    params: Params,
+}
+
+impl PasswordHasher {
+    /// Create a builder for PasswordHasher
+    pub fn build() -> PasswordHasherBuilder {
+        PasswordHasherBuilder::default()
+    }
+
+    /// Return the Argon2 parameters used for new hashes
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Hash `password` using Argon2id and a random salt, returning a PHC string
+    pub fn hash(&self, password: &str) -> GateKeeperResult<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = self
+            .argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(PasswordError::Hash)?;
+
+        Ok(hash.to_string())
+    }
+
+    /// Verify `password` against the PHC string `phc`
+    ///
+    /// The parameters stored in `phc` are used, so hashes created with outdated
+    /// parameters still verify.
+    pub fn verify(&self, password: &str, phc: &str) -> GateKeeperResult<bool> {
+        let hash = PasswordHash::new(phc).map_err(PasswordError::Hash)?;
+
+        match self.argon2().verify_password(password.as_bytes(), &hash) {
+            Ok(()) => Ok(true),
+            Err(argon2::password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(PasswordError::Hash(e).into()),
+        }
+    }
+
+    /// Test if `phc` was created with another algorithm, version or parameters
+    /// than this hasher uses and should therefore be replaced
+    pub fn needs_rehash(&self, phc: &str) -> GateKeeperResult<bool> {
+        let hash = PasswordHash::new(phc).map_err(PasswordError::Hash)?;
+
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+        {
+            return Ok(true);
+        }
+
+        let params = Params::try_from(&hash).map_err(PasswordError::Hash)?;
+        let output_len = |p: &Params| p.output_len().unwrap_or(Params::DEFAULT_OUTPUT_LEN);
+
+        Ok(params.m_cost() != self.params.m_cost()
+            || params.t_cost() != self.params.t_cost()
+            || params.p_cost() != self.params.p_cost()
+            || output_len(&params) != output_len(&self.params))
+    }
+
+    fn argon2(&self) -> Argon2<'_> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self {
+            params: Params::DEFAULT,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PasswordHasherBuilder {
+    memory_cost: Option<u32>,
+    time_cost: Option<u32>,
+    parallelism: Option<u32>,
+    output_len: Option<usize>,
+}
+
+impl PasswordHasherBuilder {
+    /// Set field `memory_cost` in KiB
+    pub fn memory_cost(mut self, memory_cost: u32) -> Self {
+        self.memory_cost = Some(memory_cost);
+        self
+    }
+
+    /// Set field `time_cost`, the number of iterations
+    pub fn time_cost(mut self, time_cost: u32) -> Self {
+        self.time_cost = Some(time_cost);
+        self
+    }
+
+    /// Set field `parallelism`, the degree of parallelism
+    pub fn parallelism(mut self, parallelism: u32) -> Self {
+        self.parallelism = Some(parallelism);
+        self
+    }
+
+    /// Set field `output_len`, the length of the hash in bytes
+    pub fn output_len(mut self, output_len: usize) -> Self {
+        self.output_len = Some(output_len);
+        self
+    }
+
+    /// Actually create the hasher, failing on invalid parameters
+    pub fn build(self) -> GateKeeperResult<PasswordHasher> {
+        let params = Params::new(
+            self.memory_cost.unwrap_or(Params::DEFAULT_M_COST),
+            self.time_cost.unwrap_or(Params::DEFAULT_T_COST),
+            self.parallelism.unwrap_or(Params::DEFAULT_P_COST),
+            self.output_len,
+        )
+        .map_err(PasswordError::Params)?;
+
+        Ok(PasswordHasher { params })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PasswordHasher;
+
+    fn hasher(time_cost: u32) -> anyhow::Result<PasswordHasher> {
+        Ok(PasswordHasher::build()
+            .memory_cost(1024)
+            .time_cost(time_cost)
+            .build()?)
+    }
+
+    #[test]
+    fn test_hash_and_verify() -> anyhow::Result<()> {
+        let hasher = hasher(1)?;
+        let phc = hasher.hash("correct horse")?;
+
+        assert!(phc.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+        assert!(hasher.verify("correct horse", &phc)?);
+        assert!(!hasher.verify("battery staple", &phc)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_malformed_hash() -> anyhow::Result<()> {
+        let hasher = hasher(1)?;
+
+        assert!(hasher.verify("correct horse", "not a phc string").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_needs_rehash() -> anyhow::Result<()> {
+        let old = hasher(1)?;
+        let new = hasher(2)?;
+        let phc = old.hash("correct horse")?;
+
+        assert!(!old.needs_rehash(&phc)?);
+        assert!(new.needs_rehash(&phc)?);
+        // Outdated hashes must still verify
+        assert!(new.verify("correct horse", &phc)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        assert!(PasswordHasher::build().parallelism(0).build().is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use crate::authentication::{AuthenticationError, AuthenticationToken, RefreshToken};
+use crate::password::{PasswordHasher, PasswordModel};
+use crate::tokens::{Acr, Token, TokenCodec};
+use crate::GateKeeperResult;
+use chrono::Utc;
+
+/// Outcome of a successful password login
+#[derive(Debug)]
+pub struct PasswordLogin {
+    pub tokens: LoginTokens,
+    /// New password hash, set if the stored one should be upgraded
+    pub rehash: Option<String>,
+}
+
+/// Tokens issued by a successful password login
+#[derive(Debug)]
+pub enum LoginTokens {
+    /// New token pair of a completed login
+    Issued {
+        authentication_token: AuthenticationToken,
+        refresh_token: RefreshToken,
+    },
+    /// Token to complete the login of a user having MFA enabled, see
+    /// [`verify_mfa`](crate::mfa::verify_mfa)
+    #[cfg(feature = "mfa")]
+    MfaRequired(crate::mfa::MfaPendingToken),
+}
+
+/// Verify `password` for `user` and issue new tokens, encoded using `codec`,
+/// on success
+///
+/// With feature `mfa`, users having a TOTP secret only get an
+/// [`LoginTokens::MfaRequired`] token, so MFA can't be skipped.
+///
+/// If the stored hash uses outdated parameters, a fresh hash is returned in
+/// [`PasswordLogin::rehash`] and should be persisted by the caller.
+pub fn login(
+    hasher: &PasswordHasher,
+    user: &impl PasswordModel,
+    password: &str,
+    codec: &TokenCodec,
+) -> GateKeeperResult<PasswordLogin> {
+    let rehash = check_password(hasher, user, password)?;
+
+    #[cfg(feature = "mfa")]
+    if user.totp_secret().is_some() {
+        return Ok(PasswordLogin {
+            tokens: LoginTokens::MfaRequired(crate::mfa::MfaPendingToken::try_new_for_model(
+                user, codec,
+            )?),
+            rehash,
+        });
+    }
+
+    let auth_time = Utc::now().timestamp() as usize;
+
+    Ok(PasswordLogin {
+        tokens: LoginTokens::Issued {
+            authentication_token: AuthenticationToken::try_new_for_authentication(
+                user,
+                auth_time,
+                Some(Acr::Password),
+                codec,
+            )?,
+            refresh_token: RefreshToken::try_new_for_authentication(
+                user,
+                auth_time,
+                Some(Acr::Password),
+                codec,
+            )?,
+        },
+        rehash,
+    })
+}
+
+/// Verify `password` for `user` without issuing tokens
+///
+/// Returns a fresh hash if the stored one should be upgraded, see [`login`].
+pub fn check_password(
+    hasher: &PasswordHasher,
+    user: &impl PasswordModel,
+    password: &str,
+) -> GateKeeperResult<Option<String>> {
+    let phc = user.password_hash();
+
+    if !hasher.verify(password, phc)? {
+        return Err(AuthenticationError::InvalidCredentials.into());
+    }
+
+    if hasher.needs_rehash(phc)? {
+        Ok(Some(hasher.hash(password)?))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{login, LoginTokens};
+    use crate::authentication::AuthenticationError;
+    use crate::error::GateKeeperError;
+    use crate::password::PasswordHasher;
+    use crate::test_support::{init_env, test_hasher, TestUser};
+    use crate::tokens::{Acr, Token, TokenCodec};
+
+    #[test]
+    fn test_login() -> anyhow::Result<()> {
+        init_env();
+
+        let hasher = test_hasher();
+        let user = TestUser::new("alice", &hasher.hash("correct horse")?);
+        let result = login(&hasher, &user, "correct horse", &TokenCodec::default())?;
+        let (authentication_token, refresh_token) = match result.tokens {
+            LoginTokens::Issued {
+                authentication_token,
+                refresh_token,
+            } => (authentication_token, refresh_token),
+            #[cfg(feature = "mfa")]
+            LoginTokens::MfaRequired(_) => anyhow::bail!("login should issue a token pair"),
+        };
+
+        assert_eq!(authentication_token.get_claims().sub, user.id.to_string());
+        assert_eq!(refresh_token.get_claims().sub, user.id.to_string());
+        assert_eq!(authentication_token.get_claims().acr, Some(Acr::Password));
+        assert!(result.rehash.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "mfa")]
+    #[test]
+    fn test_login_mfa_required() -> anyhow::Result<()> {
+        init_env();
+
+        let hasher = test_hasher();
+        let mut user = TestUser::new("alice", &hasher.hash("correct horse")?);
+
+        user.totp_secret = Some(crate::mfa::Totp::generate_secret());
+
+        let result = login(&hasher, &user, "correct horse", &TokenCodec::default())?;
+
+        assert!(matches!(result.tokens, LoginTokens::MfaRequired(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_login_invalid_password() -> anyhow::Result<()> {
+        init_env();
+
+        let hasher = test_hasher();
+        let user = TestUser::new("alice", &hasher.hash("correct horse")?);
+        let result = login(&hasher, &user, "battery staple", &TokenCodec::default());
+
+        assert!(matches!(
+            result,
+            Err(GateKeeperError::Authentication(
+                AuthenticationError::InvalidCredentials
+            ))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_login_rehash() -> anyhow::Result<()> {
+        init_env();
+
+        let old = test_hasher();
+        let new = PasswordHasher::build()
+            .memory_cost(2048)
+            .time_cost(1)
+            .build()?;
+        let user = TestUser::new("alice", &old.hash("correct horse")?);
+        let result = login(&new, &user, "correct horse", &TokenCodec::default())?;
+        let rehash = result.rehash.expect("hash should be upgraded");
+
+        assert!(!new.needs_rehash(&rehash)?);
+        assert!(new.verify("correct horse", &rehash)?);
+
+        Ok(())
+    }
+}
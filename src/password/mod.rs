@@ -0,0 +1,24 @@
+//! Module containing password hashing and credential verification
+//!
+//! Only available on feature `password`
+mod error;
+mod hasher;
+mod login;
+
+pub use error::*;
+pub use hasher::*;
+pub use login::*;
+
+use crate::model::GateKeeperModel;
+
+/// Model which can be authenticated using a password
+pub trait PasswordModel: GateKeeperModel {
+    /// Return the model object's stored password hash as PHC string
+    fn password_hash(&self) -> &str;
+
+    /// Return the model object's base32 encoded TOTP secret, if MFA is enabled
+    #[cfg(feature = "mfa")]
+    fn totp_secret(&self) -> Option<&str> {
+        None
+    }
+}
@@ -0,0 +1,535 @@
+//! Module containing RFC 7807 problem details for error responses
+//!
+//! By default every [`GateKeeperError`](crate::error::GateKeeperError) is
+//! rendered as `application/problem+json`, e.g.
+//!
+//! ```json
+//! {
+//!   "type": "urn:axum-gatekeeper:error:invalid_token",
+//!   "title": "Invalid token",
+//!   "status": 401,
+//!   "code": "invalid_token",
+//!   "detail": "Couldn't decode token claims"
+//! }
+//! ```
+//!
+//! `code` is stable across releases and meant for programmatic handling,
+//! `title` and `detail` are safe to show to users. Errors of the OAuth2
+//! endpoints always keep the format defined by RFC 6749. Use the
+//! [`problem_trace_id`] middleware to add the request's trace id to problems.
+//!
+//! To change how error responses are rendered, e.g. to switch back to the
+//! previous plain text and JSON bodies or to localize them using an
+//! [`ErrorRenderer`], wrap the routes in the [`render_errors`] middleware:
+//!
+//! ```ignore
+//! let errors = ErrorConfig::build().format(ErrorFormat::Legacy).build();
+//! let app = Router::new()
+//!     .route("/", get(handler))
+//!     .layer(axum::middleware::from_fn_with_state(errors, render_errors));
+//! ```
+use crate::challenge::ChallengeConfig;
+use crate::error::{ErrorDetails, RenderedError};
+use axum::body::{Body, HttpBody};
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Content type of problem details
+pub const PROBLEM_JSON: &str = "application/problem+json";
+/// Prefix of the problems' `type` URIs, followed by the error code
+pub const PROBLEM_TYPE_PREFIX: &str = "urn:axum-gatekeeper:error:";
+
+/// Maximum size of problem bodies read by [`problem_trace_id`]
+const MAX_PROBLEM_LEN: usize = 2 * 1024 * 1024;
+
+/// Body format of error responses
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ErrorFormat {
+    /// RFC 7807 problem details
+    #[default]
+    Problem,
+    /// Plain text messages and `ErrorResponse` JSON for token errors, as
+    /// rendered by previous versions
+    Legacy,
+}
+
+/// Configuration of error responses, applied by the [`render_errors`] middleware
+#[derive(Clone, Default)]
+pub struct ErrorConfig {
+    format: ErrorFormat,
+    renderer: Option<Arc<dyn ErrorRenderer>>,
+    challenge: ChallengeConfig,
+}
+
+impl ErrorConfig {
+    /// Create a builder for ErrorConfig
+    pub fn build() -> ErrorConfigBuilder {
+        ErrorConfigBuilder::default()
+    }
+
+    /// Return the body format of error responses
+    pub fn format(&self) -> ErrorFormat {
+        self.format
+    }
+
+    /// Return the renderer of error responses, if any
+    pub fn renderer(&self) -> Option<&dyn ErrorRenderer> {
+        self.renderer.as_deref()
+    }
+
+    /// Return the realm and schemes of `WWW-Authenticate` challenges
+    pub fn challenge(&self) -> &ChallengeConfig {
+        &self.challenge
+    }
+}
+
+#[derive(Default)]
+pub struct ErrorConfigBuilder {
+    format: ErrorFormat,
+    renderer: Option<Arc<dyn ErrorRenderer>>,
+    challenge: ChallengeConfig,
+}
+
+impl ErrorConfigBuilder {
+    /// Set field `format`, defaults to [`ErrorFormat::Problem`]
+    pub fn format(mut self, format: ErrorFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set field `renderer`, building the final error responses
+    pub fn renderer(mut self, renderer: impl ErrorRenderer + 'static) -> Self {
+        self.renderer = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Set field `challenge`, defaults to `Bearer` challenges using realm
+    /// [`ChallengeConfig::DEFAULT_REALM`]
+    pub fn challenge(mut self, challenge: ChallengeConfig) -> Self {
+        self.challenge = challenge;
+        self
+    }
+
+    /// Actually create the ErrorConfig
+    pub fn build(self) -> ErrorConfig {
+        ErrorConfig {
+            format: self.format,
+            renderer: self.renderer,
+            challenge: self.challenge,
+        }
+    }
+}
+
+/// Renderer building the responses of gatekeeper errors
+pub trait ErrorRenderer: Send + Sync {
+    /// Build the response for `error`
+    ///
+    /// `default` is the response rendered using the configured
+    /// [`ErrorFormat`], including headers required by the protocol, e.g.
+    /// `WWW-Authenticate` or `Retry-After`. Return it unchanged to keep the
+    /// default rendering.
+    fn render(&self, error: &ErrorDetails, default: Response) -> Response;
+}
+
+impl<T: ErrorRenderer + ?Sized> ErrorRenderer for Arc<T> {
+    fn render(&self, error: &ErrorDetails, default: Response) -> Response {
+        self.as_ref().render(error, default)
+    }
+}
+
+/// Renderer keeping the default responses
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultErrorRenderer;
+
+impl ErrorRenderer for DefaultErrorRenderer {
+    fn render(&self, _error: &ErrorDetails, default: Response) -> Response {
+        default
+    }
+}
+
+/// Render `response` using `renderer` if it's the response of a gatekeeper
+/// error, i.e. carries [`ErrorDetails`]
+pub fn render_with(renderer: &dyn ErrorRenderer, response: Response) -> Response {
+    let Some(details) = response.extensions().get::<ErrorDetails>().cloned() else {
+        return response;
+    };
+    let mut response = renderer.render(&details, response);
+
+    response.extensions_mut().insert(details);
+    response
+}
+
+/// Middleware rendering the error responses of the wrapped routes as
+/// configured by the [`ErrorConfig`] in its state
+///
+/// Errors are rendered again from the original [`GateKeeperError`](crate::error::GateKeeperError),
+/// so if the middleware is nested, the innermost config wins. Use it with
+/// `axum::middleware::from_fn_with_state(config, render_errors)`.
+pub async fn render_errors(
+    State(config): State<ErrorConfig>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+
+    match response.extensions_mut().remove::<RenderedError>() {
+        Some(RenderedError(error)) => error.render(&config),
+        None => response,
+    }
+}
+
+/// Problem details as defined by RFC 7807
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Problem {
+    /// URI identifying the problem type
+    #[serde(rename = "type")]
+    pub type_uri: String,
+    /// Short summary of the problem type
+    pub title: String,
+    pub status: u16,
+    /// Machine-readable error code
+    pub code: String,
+    /// Explanation specific to this occurrence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Id of the trace the error occurred in, see [`problem_trace_id`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+impl Problem {
+    /// Create problem with `code` and `title`, using the `type` URI derived
+    /// from `code`
+    pub fn new(code: &str, title: &str, status: StatusCode) -> Self {
+        Self {
+            type_uri: format!("{PROBLEM_TYPE_PREFIX}{code}"),
+            title: title.to_string(),
+            status: status.as_u16(),
+            code: code.to_string(),
+            detail: None,
+            trace_id: None,
+        }
+    }
+
+    /// Replace the body of `response` with this problem, keeping its status
+    /// and headers, e.g. `WWW-Authenticate` or `Retry-After`
+    pub fn render_into(self, response: Response) -> Response {
+        let (mut parts, _) = response.into_parts();
+
+        match serde_json::to_vec(&self) {
+            Ok(body) => {
+                parts
+                    .headers
+                    .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+                parts.headers.remove(CONTENT_LENGTH);
+
+                Response::from_parts(parts, Body::from(body))
+            }
+            Err(e) => {
+                tracing::error!("Error serializing problem: {e:?}");
+
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        self.render_into(status.into_response())
+    }
+}
+
+/// Middleware adding the request's trace id to problem responses
+///
+/// The id is taken from the W3C `traceparent` header or, if missing, the
+/// `X-Request-Id` header. Use it with `axum::middleware::from_fn(problem_trace_id)`.
+/// Responses which can't be read as problems of up to 2 MiB are passed
+/// through unchanged.
+pub async fn problem_trace_id(req: Request, next: Next) -> Response {
+    let trace_id = trace_id_from_headers(req.headers());
+    let response = next.run(req).await;
+    let Some(trace_id) = trace_id else {
+        return response;
+    };
+    let is_problem = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value == PROBLEM_JSON);
+
+    if !is_problem {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+
+    if body
+        .size_hint()
+        .upper()
+        .is_none_or(|len| len > MAX_PROBLEM_LEN as u64)
+    {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = match axum::body::to_bytes(body, MAX_PROBLEM_LEN).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Error reading problem body: {e:?}");
+
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    match serde_json::from_slice::<Problem>(&bytes) {
+        Ok(problem) => Problem {
+            trace_id: Some(trace_id),
+            ..problem
+        }
+        .render_into(Response::from_parts(parts, Body::empty())),
+        Err(_) => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+fn trace_id_from_headers(headers: &HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    // traceparent: version-trace_id-parent_id-flags
+    header("traceparent")
+        .and_then(|value| value.split('-').nth(1))
+        .or_else(|| header("x-request-id"))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        problem_trace_id, render_errors, ErrorConfig, ErrorFormat, ErrorRenderer, Problem,
+        PROBLEM_JSON,
+    };
+    use crate::challenge::ChallengeConfig;
+    use crate::error::{ErrorDetails, ErrorKind, GateKeeperError};
+    use crate::tokens::TokenError;
+    use axum::body::Body;
+    use axum::http::header::{CONTENT_TYPE, RETRY_AFTER, WWW_AUTHENTICATE};
+    use axum::http::{Request, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use http_body_util::BodyExt;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    struct GermanRenderer;
+
+    impl ErrorRenderer for GermanRenderer {
+        fn render(&self, error: &ErrorDetails, default: Response) -> Response {
+            let title = match error.code {
+                "missing_token" => "Token fehlt",
+                _ => return default,
+            };
+
+            Problem::new(error.code, title, error.status).render_into(default)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render_errors() -> anyhow::Result<()> {
+        let config = ErrorConfig::build().renderer(GermanRenderer).build();
+        let app = Router::new()
+            .route(
+                "/token",
+                get(|| async { GateKeeperError::from(TokenError::MissingTokenString) }),
+            )
+            .route(
+                "/limited",
+                get(|| async { GateKeeperError::RateLimited(Duration::from_secs(3)) }),
+            )
+            .layer(middleware::from_fn_with_state(config, render_errors));
+        let response = app
+            .clone()
+            .oneshot(Request::get("/token").body(Body::empty())?)
+            .await?;
+        let details = response.extensions().get::<ErrorDetails>().cloned();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(details.map(|details| details.kind), Some(ErrorKind::Token));
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let problem = serde_json::from_slice::<Problem>(&body)?;
+
+        assert_eq!(problem.title, "Token fehlt");
+        assert_eq!(problem.code, "missing_token");
+
+        // Errors the renderer doesn't handle keep the default response
+        let response = app
+            .oneshot(Request::get("/limited").body(Body::empty())?)
+            .await?;
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "3");
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let problem = serde_json::from_slice::<Problem>(&body)?;
+
+        assert_eq!(problem.title, "Too many attempts");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_render_errors_format() -> anyhow::Result<()> {
+        let legacy = ErrorConfig::build().format(ErrorFormat::Legacy).build();
+        let app = Router::new()
+            .route(
+                "/legacy",
+                get(|| async { GateKeeperError::RateLimited(Duration::from_secs(3)) }),
+            )
+            .layer(middleware::from_fn_with_state(legacy, render_errors))
+            .route(
+                "/problem",
+                get(|| async { GateKeeperError::RateLimited(Duration::from_secs(3)) }),
+            );
+
+        for (uri, content_type) in [
+            ("/legacy", "text/plain; charset=utf-8"),
+            ("/problem", PROBLEM_JSON),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty())?)
+                .await?;
+
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers()[RETRY_AFTER], "3");
+            assert_eq!(response.headers()[CONTENT_TYPE], content_type);
+        }
+
+        // The innermost config wins
+        let problem = ErrorConfig::default();
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async { GateKeeperError::RateLimited(Duration::from_secs(3)) }),
+            )
+            .layer(middleware::from_fn_with_state(problem, render_errors))
+            .layer(middleware::from_fn_with_state(
+                ErrorConfig::build().format(ErrorFormat::Legacy).build(),
+                render_errors,
+            ));
+        let response = app.oneshot(Request::get("/").body(Body::empty())?).await?;
+
+        assert_eq!(response.headers()[CONTENT_TYPE], PROBLEM_JSON);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_render_errors_challenge() -> anyhow::Result<()> {
+        let challenge = ChallengeConfig::build()
+            .realm("api")
+            .scheme("Bearer")
+            .scheme("DPoP")
+            .build();
+        let config = ErrorConfig::build().challenge(challenge).build();
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async { GateKeeperError::from(TokenError::Expired) }),
+            )
+            .layer(middleware::from_fn_with_state(config, render_errors));
+        let response = app.oneshot(Request::get("/").body(Body::empty())?).await?;
+        let challenges = response
+            .headers()
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            challenges,
+            [
+                r#"Bearer realm="api", error="invalid_token", error_description="Token has expired""#,
+                r#"DPoP realm="api", error="invalid_token", error_description="Token has expired""#,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_errors_ignores_other_responses() {
+        let response = super::render_with(&GermanRenderer, StatusCode::NOT_FOUND.into_response());
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.extensions().get::<ErrorDetails>().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_problem_trace_id() -> anyhow::Result<()> {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    Problem::new("invalid_token", "Invalid token", StatusCode::UNAUTHORIZED)
+                }),
+            )
+            .layer(middleware::from_fn(problem_trace_id));
+        let request = Request::get("/")
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[CONTENT_TYPE], PROBLEM_JSON);
+
+        let body = response.into_body().collect().await?.to_bytes();
+        let problem = serde_json::from_slice::<Problem>(&body)?;
+
+        assert_eq!(problem.type_uri, "urn:axum-gatekeeper:error:invalid_token");
+        assert_eq!(
+            problem.trace_id.as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_problem_trace_id_keeps_other_bodies() -> anyhow::Result<()> {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    (
+                        StatusCode::BAD_GATEWAY,
+                        [(CONTENT_TYPE, PROBLEM_JSON)],
+                        "upstream error",
+                    )
+                }),
+            )
+            .layer(middleware::from_fn(problem_trace_id));
+        let request = Request::get("/")
+            .header("x-request-id", "request-1")
+            .body(Body::empty())?;
+        let response = app.oneshot(request).await?;
+
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            response.into_body().collect().await?.to_bytes(),
+            "upstream error"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+use crate::error::GateKeeperError;
+use crate::rate_limit::{AttemptRecord, MemoryRateLimitStore, RateLimitKey, RateLimitStore};
+use crate::GateKeeperResult;
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Applies backoff and lockout policy to failed attempts
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    free_attempts: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    lockout_threshold: u32,
+    lockout_duration: Duration,
+    window: Duration,
+}
+
+impl RateLimiter {
+    /// Create a builder for RateLimiter
+    pub fn build() -> RateLimiterBuilder {
+        RateLimiterBuilder::default()
+    }
+
+    /// Return how long a key with `record` has to wait before the next attempt
+    pub fn retry_after(&self, record: &AttemptRecord, now: DateTime<Utc>) -> Option<Duration> {
+        let wait = if record.failures >= self.lockout_threshold {
+            self.lockout_duration
+        } else if record.failures >= self.free_attempts {
+            let exponent = (record.failures - self.free_attempts).min(31);
+
+            self.base_backoff
+                .saturating_mul(1 << exponent)
+                .min(self.max_backoff)
+        } else {
+            return None;
+        };
+        let elapsed = now
+            .signed_duration_since(record.last_failure)
+            .to_std()
+            .unwrap_or_default();
+
+        wait.checked_sub(elapsed)
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Fail with [`GateKeeperError::RateLimited`] if any of `keys` has to wait
+    pub async fn check(&self, keys: &[RateLimitKey]) -> GateKeeperResult<()> {
+        let now = Utc::now();
+        let mut retry_after = None;
+
+        for key in keys {
+            if let Some(record) = self.store.get(key).await? {
+                retry_after = retry_after.max(self.retry_after(&record, now));
+            }
+        }
+
+        match retry_after {
+            Some(retry_after) => {
+                tracing::warn!("Rate limited attempt for {keys:?}");
+
+                Err(GateKeeperError::RateLimited(retry_after))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Count a failed attempt for all `keys`
+    pub async fn record_failure(&self, keys: &[RateLimitKey]) -> GateKeeperResult<()> {
+        let ttl = self.window.max(self.lockout_duration);
+
+        for key in keys {
+            self.store.increment(key, ttl).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Forget failed attempts of all `keys` except IP addresses
+    ///
+    /// IPs may be shared by many users, so a single success doesn't clear them.
+    pub async fn record_success(&self, keys: &[RateLimitKey]) -> GateKeeperResult<()> {
+        for key in keys {
+            if !matches!(key, RateLimitKey::Ip(_)) {
+                self.store.reset(key).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run `attempt` if none of `keys` is limited and record its outcome
+    ///
+    /// Only authentication, token, MFA code and verification errors count as
+    /// failed attempts, server side errors are passed through.
+    pub async fn attempt<T>(
+        &self,
+        keys: &[RateLimitKey],
+        attempt: impl Future<Output = GateKeeperResult<T>>,
+    ) -> GateKeeperResult<T> {
+        self.check(keys).await?;
+
+        let result = attempt.await;
+
+        match &result {
+            Ok(_) => self.record_success(keys).await?,
+            Err(e) if is_failed_attempt(e) => self.record_failure(keys).await?,
+            Err(_) => {}
+        }
+
+        result
+    }
+}
+
+fn is_failed_attempt(e: &GateKeeperError) -> bool {
+    match e {
+        GateKeeperError::Authentication(_) | GateKeeperError::Token(_) => true,
+        #[cfg(feature = "mfa")]
+        GateKeeperError::Mfa(crate::mfa::MfaError::InvalidCode) => true,
+        #[cfg(feature = "verification")]
+        GateKeeperError::Verification(_) => true,
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+pub struct RateLimiterBuilder {
+    store: Option<Arc<dyn RateLimitStore>>,
+    free_attempts: Option<u32>,
+    base_backoff: Option<Duration>,
+    max_backoff: Option<Duration>,
+    lockout_threshold: Option<u32>,
+    lockout_duration: Option<Duration>,
+    window: Option<Duration>,
+}
+
+impl RateLimiterBuilder {
+    /// Set field `store`, defaults to [`MemoryRateLimitStore`]
+    pub fn store(mut self, store: impl RateLimitStore + 'static) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    /// Set field `free_attempts`, the number of failures allowed without backoff
+    pub fn free_attempts(mut self, free_attempts: u32) -> Self {
+        self.free_attempts = Some(free_attempts);
+        self
+    }
+
+    /// Set field `base_backoff`, doubled on every failure after the free ones
+    pub fn base_backoff(mut self, base_backoff: Duration) -> Self {
+        self.base_backoff = Some(base_backoff);
+        self
+    }
+
+    /// Set field `max_backoff`
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = Some(max_backoff);
+        self
+    }
+
+    /// Set field `lockout_threshold`, the number of failures locking a key out
+    pub fn lockout_threshold(mut self, lockout_threshold: u32) -> Self {
+        self.lockout_threshold = Some(lockout_threshold);
+        self
+    }
+
+    /// Set field `lockout_duration`
+    pub fn lockout_duration(mut self, lockout_duration: Duration) -> Self {
+        self.lockout_duration = Some(lockout_duration);
+        self
+    }
+
+    /// Set field `window`, the time failures are remembered after the last one
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// Actually create the rate limiter
+    pub fn build(self) -> RateLimiter {
+        RateLimiter {
+            store: self
+                .store
+                .unwrap_or_else(|| Arc::new(MemoryRateLimitStore::new())),
+            free_attempts: self.free_attempts.unwrap_or(5),
+            base_backoff: self.base_backoff.unwrap_or(Duration::from_secs(1)),
+            max_backoff: self.max_backoff.unwrap_or(Duration::from_secs(5 * 60)),
+            lockout_threshold: self.lockout_threshold.unwrap_or(20),
+            lockout_duration: self
+                .lockout_duration
+                .unwrap_or(Duration::from_secs(15 * 60)),
+            window: self.window.unwrap_or(Duration::from_secs(15 * 60)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authentication::AuthenticationError;
+
+    fn record(failures: u32, seconds_ago: i64) -> AttemptRecord {
+        AttemptRecord {
+            failures,
+            last_failure: Utc::now() - chrono::Duration::seconds(seconds_ago),
+        }
+    }
+
+    #[test]
+    fn test_retry_after_backoff() {
+        let limiter = RateLimiter::build()
+            .free_attempts(3)
+            .base_backoff(Duration::from_secs(10))
+            .max_backoff(Duration::from_secs(60))
+            .lockout_threshold(10)
+            .lockout_duration(Duration::from_secs(600))
+            .build();
+        let now = Utc::now();
+        let wait = |failures| limiter.retry_after(&record(failures, 0), now);
+
+        assert_eq!(wait(2), None);
+        assert!(wait(3).unwrap() <= Duration::from_secs(10));
+        assert!(wait(4).unwrap() > Duration::from_secs(10));
+        assert!(wait(9).unwrap() <= Duration::from_secs(60));
+        assert!(wait(10).unwrap() > Duration::from_secs(60));
+        assert_eq!(limiter.retry_after(&record(3, 11), now), None);
+    }
+
+    #[tokio::test]
+    async fn test_attempt_limits_after_failures() -> anyhow::Result<()> {
+        let limiter = RateLimiter::build().free_attempts(2).build();
+        let keys = [RateLimitKey::login("alice")];
+        let fail = || async { Err::<(), _>(AuthenticationError::InvalidCredentials.into()) };
+
+        assert!(limiter.attempt(&keys, fail()).await.is_err());
+        assert!(limiter.attempt(&keys, fail()).await.is_err());
+
+        let result = limiter.attempt(&keys, async { Ok(()) }).await;
+
+        assert!(matches!(result, Err(GateKeeperError::RateLimited(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_success_resets_all_but_ip() -> anyhow::Result<()> {
+        let store = Arc::new(MemoryRateLimitStore::new());
+        let limiter = RateLimiter::build().store(store.clone()).build();
+        let keys = [
+            RateLimitKey::Ip([127, 0, 0, 1].into()),
+            RateLimitKey::login("alice"),
+        ];
+
+        limiter.record_failure(&keys).await?;
+        limiter.record_success(&keys).await?;
+
+        assert!(store.get(&keys[0]).await?.is_some());
+        assert!(store.get(&keys[1]).await?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! Module containing brute-force protection for login, refresh and verification
+//!
+//! Failed attempts are counted per [`RateLimitKey`]. After a number of free
+//! attempts every further try has to wait for an exponentially growing
+//! backoff, and too many failures lock the key out temporarily.
+//!
+//! Only available on feature `authentication`
+mod limiter;
+mod store;
+
+pub use limiter::*;
+pub use store::*;
+
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+/// Key failed attempts are counted for
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub enum RateLimitKey {
+    /// Client IP address
+    Ip(IpAddr),
+    /// ID of the user a token was presented for
+    Subject(uuid::Uuid),
+    /// Login name used in a login attempt, case-insensitive
+    Login(String),
+}
+
+impl RateLimitKey {
+    /// Create key for `login`, normalizing it to lower case
+    pub fn login(login: &str) -> Self {
+        Self::Login(login.to_lowercase())
+    }
+}
+
+/// Extractor for the client's IP address
+///
+/// Read from `ConnectInfo<SocketAddr>`, so the app has to be served using
+/// `into_make_service_with_connect_info::<SocketAddr>()` for it to be set.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(
+            parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+        ))
+    }
+}
+
+impl ClientIp {
+    /// Return the rate limit keys for this IP together with `other`
+    pub fn keys_with(&self, other: impl IntoIterator<Item = RateLimitKey>) -> Vec<RateLimitKey> {
+        self.0
+            .map(RateLimitKey::Ip)
+            .into_iter()
+            .chain(other)
+            .collect()
+    }
+}
@@ -0,0 +1,164 @@
+use crate::rate_limit::RateLimitKey;
+use crate::GateKeeperResult;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Failed attempts recorded for a key
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct AttemptRecord {
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+}
+
+impl AttemptRecord {
+    /// Test if the record is older than `ttl` and should be forgotten
+    pub fn is_expired(&self, ttl: Duration, now: DateTime<Utc>) -> bool {
+        now.signed_duration_since(self.last_failure)
+            .to_std()
+            .unwrap_or_default()
+            >= ttl
+    }
+}
+
+/// Storage for failed attempt counters
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Return the current record for `key`, if any
+    async fn get(&self, key: &RateLimitKey) -> GateKeeperResult<Option<AttemptRecord>>;
+
+    /// Atomically count a failure for `key`
+    ///
+    /// Records whose last failure is older than `ttl` are started over.
+    async fn increment(&self, key: &RateLimitKey, ttl: Duration)
+        -> GateKeeperResult<AttemptRecord>;
+
+    /// Forget all failures for `key`
+    async fn reset(&self, key: &RateLimitKey) -> GateKeeperResult<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: RateLimitStore + ?Sized> RateLimitStore for std::sync::Arc<T> {
+    async fn get(&self, key: &RateLimitKey) -> GateKeeperResult<Option<AttemptRecord>> {
+        self.as_ref().get(key).await
+    }
+
+    async fn increment(
+        &self,
+        key: &RateLimitKey,
+        ttl: Duration,
+    ) -> GateKeeperResult<AttemptRecord> {
+        self.as_ref().increment(key, ttl).await
+    }
+
+    async fn reset(&self, key: &RateLimitKey) -> GateKeeperResult<()> {
+        self.as_ref().reset(key).await
+    }
+}
+
+/// Process local [`RateLimitStore`]
+///
+/// Counters aren't shared between instances, so deployments with several
+/// replicas should use a shared store instead.
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitStore {
+    records: Mutex<HashMap<RateLimitKey, AttemptRecord>>,
+}
+
+impl MemoryRateLimitStore {
+    /// Number of records after which expired ones are purged on increment
+    const PURGE_THRESHOLD: usize = 10_000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn get(&self, key: &RateLimitKey) -> GateKeeperResult<Option<AttemptRecord>> {
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(records.get(key).copied())
+    }
+
+    async fn increment(
+        &self,
+        key: &RateLimitKey,
+        ttl: Duration,
+    ) -> GateKeeperResult<AttemptRecord> {
+        let now = Utc::now();
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+
+        if records.len() >= Self::PURGE_THRESHOLD {
+            records.retain(|_, record| !record.is_expired(ttl, now));
+        }
+
+        let record = records
+            .entry(key.clone())
+            .and_modify(|record| {
+                if record.is_expired(ttl, now) {
+                    record.failures = 0;
+                }
+
+                record.failures += 1;
+                record.last_failure = now;
+            })
+            .or_insert(AttemptRecord {
+                failures: 1,
+                last_failure: now,
+            });
+
+        Ok(*record)
+    }
+
+    async fn reset(&self, key: &RateLimitKey) -> GateKeeperResult<()> {
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+
+        records.remove(key);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_increment_and_reset() -> anyhow::Result<()> {
+        let store = MemoryRateLimitStore::new();
+        let key = RateLimitKey::login("Alice");
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(store.increment(&key, ttl).await?.failures, 1);
+        assert_eq!(store.increment(&key, ttl).await?.failures, 2);
+        assert_eq!(
+            store
+                .get(&RateLimitKey::login("alice"))
+                .await?
+                .unwrap()
+                .failures,
+            2
+        );
+
+        store.reset(&key).await?;
+
+        assert!(store.get(&key).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_record_starts_over() -> anyhow::Result<()> {
+        let store = MemoryRateLimitStore::new();
+        let key = RateLimitKey::Subject(uuid::Uuid::new_v4());
+
+        store.increment(&key, Duration::ZERO).await?;
+
+        assert_eq!(store.increment(&key, Duration::ZERO).await?.failures, 1);
+
+        Ok(())
+    }
+}
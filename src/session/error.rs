@@ -0,0 +1,36 @@
+use crate::error::GateKeeperError;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SessionError {
+    #[error("Missing session cookie")]
+    Missing,
+    #[error("Unknown session")]
+    Unknown,
+    #[error("Expired session")]
+    Expired,
+}
+
+impl SessionError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        "invalid_session"
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        "Invalid session"
+    }
+
+    /// Return the HTTP status to respond with
+    pub fn status(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
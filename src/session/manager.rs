@@ -0,0 +1,218 @@
+use crate::authentication::AuthenticatedSubject;
+use crate::session::{MemorySessionStore, SessionError, SessionRecord, SessionStore};
+use crate::tokens::Acr;
+use crate::GateKeeperResult;
+use axum::http::header::COOKIE;
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use cookie::Cookie;
+use data_encoding::{BASE64URL_NOPAD, HEXLOWER};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Issues, checks and ends server-side sessions
+#[derive(Clone)]
+pub struct SessionManager {
+    store: Arc<dyn SessionStore>,
+    cookie_name: Arc<str>,
+    idle_timeout: Duration,
+    absolute_timeout: Duration,
+}
+
+impl SessionManager {
+    /// Name of the session cookie if none is set
+    pub const DEFAULT_COOKIE_NAME: &'static str = "session_id";
+    /// Number of random bytes of a session id
+    const ID_LEN: usize = 32;
+
+    /// Create a builder for SessionManager
+    pub fn build() -> SessionManagerBuilder {
+        SessionManagerBuilder::default()
+    }
+
+    /// Return the session store
+    pub fn store(&self) -> &dyn SessionStore {
+        self.store.as_ref()
+    }
+
+    /// Start a session for `subject` and return the cookie to set
+    ///
+    /// Any session the request already has is ended, so ids are regenerated on
+    /// every login and can't be fixated by an attacker.
+    pub async fn start(
+        &self,
+        headers: &HeaderMap,
+        subject: uuid::Uuid,
+        auth_time: usize,
+        acr: Option<Acr>,
+    ) -> GateKeeperResult<Cookie<'static>> {
+        if let Some(id) = self.session_id(headers) {
+            self.store.remove(&hash_session_id(&id)).await?;
+        }
+
+        let mut id = [0u8; Self::ID_LEN];
+
+        OsRng.fill_bytes(&mut id);
+
+        let id = BASE64URL_NOPAD.encode(&id);
+        let record = SessionRecord::new(subject, auth_time, acr);
+
+        self.store.insert(&hash_session_id(&id), record).await?;
+
+        Ok(Cookie::build((self.cookie_name.to_string(), id))
+            .path("/")
+            .max_age(cookie::time::Duration::seconds(
+                self.absolute_timeout.as_secs() as i64,
+            ))
+            .secure(true)
+            .http_only(true)
+            .same_site(cookie::SameSite::Lax)
+            .build())
+    }
+
+    /// End the request's session, if any, and return the cookie removing it
+    pub async fn end(&self, headers: &HeaderMap) -> GateKeeperResult<Cookie<'static>> {
+        if let Some(id) = self.session_id(headers) {
+            self.store.remove(&hash_session_id(&id)).await?;
+        }
+
+        Ok(Cookie::build((self.cookie_name.to_string(), ""))
+            .path("/")
+            .removal()
+            .build())
+    }
+
+    /// Look up the request's session and return its subject
+    ///
+    /// Sessions exceeding the idle or absolute timeout are removed, otherwise
+    /// the time of last use is updated.
+    pub async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+    ) -> GateKeeperResult<AuthenticatedSubject> {
+        let id = self.session_id(headers).ok_or(SessionError::Missing)?;
+        let hash = hash_session_id(&id);
+        let record = self.store.find(&hash).await?.ok_or(SessionError::Unknown)?;
+        let now = Utc::now();
+
+        if self.is_expired(&record, now) {
+            self.store.remove(&hash).await?;
+
+            return Err(SessionError::Expired.into());
+        }
+
+        self.store.touch(&hash, now).await?;
+
+        Ok(AuthenticatedSubject::from_session(&record))
+    }
+
+    /// Check whether the session of `record` exceeded a timeout at `now`
+    pub fn is_expired(&self, record: &SessionRecord, now: DateTime<Utc>) -> bool {
+        let elapsed = |since: DateTime<Utc>| {
+            now.signed_duration_since(since)
+                .to_std()
+                .unwrap_or_default()
+        };
+
+        elapsed(record.created_at) >= self.absolute_timeout
+            || elapsed(record.last_seen_at) >= self.idle_timeout
+    }
+
+    /// Read the session id from the request's cookies
+    fn session_id(&self, headers: &HeaderMap) -> Option<String> {
+        headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(Cookie::split_parse)
+            .filter_map(Result::ok)
+            .find(|cookie| cookie.name() == &*self.cookie_name)
+            .map(|cookie| cookie.value().to_string())
+    }
+}
+
+/// Hash session `id` for storage or lookup
+fn hash_session_id(id: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(id.as_bytes()))
+}
+
+#[derive(Default)]
+pub struct SessionManagerBuilder {
+    store: Option<Arc<dyn SessionStore>>,
+    cookie_name: Option<String>,
+    idle_timeout: Option<Duration>,
+    absolute_timeout: Option<Duration>,
+}
+
+impl SessionManagerBuilder {
+    /// Set field `store`, defaults to [`MemorySessionStore`]
+    pub fn store(mut self, store: impl SessionStore + 'static) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    /// Set field `cookie_name`, defaults to [`SessionManager::DEFAULT_COOKIE_NAME`]
+    pub fn cookie_name(mut self, cookie_name: &str) -> Self {
+        self.cookie_name = Some(cookie_name.to_string());
+        self
+    }
+
+    /// Set field `idle_timeout`, defaults to 30 minutes
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Set field `absolute_timeout`, defaults to 12 hours
+    pub fn absolute_timeout(mut self, absolute_timeout: Duration) -> Self {
+        self.absolute_timeout = Some(absolute_timeout);
+        self
+    }
+
+    /// Actually create the SessionManager
+    pub fn build(self) -> SessionManager {
+        SessionManager {
+            store: self
+                .store
+                .unwrap_or_else(|| Arc::new(MemorySessionStore::new())),
+            cookie_name: self
+                .cookie_name
+                .as_deref()
+                .unwrap_or(SessionManager::DEFAULT_COOKIE_NAME)
+                .into(),
+            idle_timeout: self.idle_timeout.unwrap_or(Duration::from_secs(30 * 60)),
+            absolute_timeout: self
+                .absolute_timeout
+                .unwrap_or(Duration::from_secs(12 * 60 * 60)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionManager;
+    use crate::session::SessionRecord;
+    use chrono::Utc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_is_expired() {
+        let manager = SessionManager::build()
+            .idle_timeout(Duration::from_secs(60))
+            .absolute_timeout(Duration::from_secs(600))
+            .build();
+        let mut record = SessionRecord::new(uuid::Uuid::new_v4(), 1000, None);
+        let now = Utc::now();
+
+        assert!(!manager.is_expired(&record, now));
+        assert!(manager.is_expired(&record, now + chrono::Duration::seconds(61)));
+
+        // Activity doesn't extend a session beyond the absolute timeout
+        record.last_seen_at = now + chrono::Duration::seconds(590);
+
+        assert!(!manager.is_expired(&record, now + chrono::Duration::seconds(599)));
+        assert!(manager.is_expired(&record, now + chrono::Duration::seconds(601)));
+    }
+}
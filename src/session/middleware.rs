@@ -0,0 +1,182 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::session::SessionManager;
+use crate::GateKeeperResult;
+use axum::extract::State;
+use axum::{body::Body, extract::Request, middleware::Next, response::Response};
+
+/// Middleware authenticating requests using the session cookie
+///
+/// Inserts the [`AuthenticatedSubject`](crate::authentication::AuthenticatedSubject)
+/// into the request's extensions. Use it with
+/// `axum::middleware::from_fn_with_state(sessions, authenticate_session)`.
+pub async fn authenticate_session(
+    State(sessions): State<SessionManager>,
+    mut req: Request,
+    next: Next,
+) -> GateKeeperResult<Response<Body>> {
+    tracing::debug!("Using middleware::authenticate_session");
+
+    #[cfg(feature = "metrics")]
+    let started = std::time::Instant::now();
+    let audit = AuditContext::from_request(&req);
+    let result = sessions.authenticate(req.headers()).await;
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decision("authenticate_session", started, &result);
+    let subject = audit
+        .record_failure(AuditEventKind::Authentication, result)
+        .await?;
+
+    req.extensions_mut().insert(subject);
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::authenticate_session;
+    use crate::authentication::{AuthenticatedSubject, AuthenticationMethod};
+    use crate::session::SessionManager;
+    use crate::tokens::Acr;
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::header::{COOKIE, SET_COOKIE};
+    use axum::http::{HeaderMap, Request, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::{get, post};
+    use axum::{middleware, Router};
+    use http_body_util::BodyExt;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn app(sessions: SessionManager, user: uuid::Uuid) -> Router {
+        let protected = Router::new()
+            .route(
+                "/",
+                get(|subject: AuthenticatedSubject| async move {
+                    format!("{} {:?} {:?}", subject.id, subject.method, subject.acr)
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                sessions.clone(),
+                authenticate_session,
+            ));
+
+        Router::new()
+            .route(
+                "/login",
+                post(
+                    move |State(sessions): State<SessionManager>, headers: HeaderMap| async move {
+                        let cookie = sessions
+                            .start(&headers, user, 1000, Some(Acr::Password))
+                            .await?;
+
+                        crate::GateKeeperResult::Ok([(SET_COOKIE, cookie.to_string())])
+                    },
+                ),
+            )
+            .route(
+                "/logout",
+                post(
+                    |State(sessions): State<SessionManager>, headers: HeaderMap| async move {
+                        let cookie = sessions.end(&headers).await?;
+
+                        crate::GateKeeperResult::Ok(
+                            [(SET_COOKIE, cookie.to_string())].into_response(),
+                        )
+                    },
+                ),
+            )
+            .with_state(sessions)
+            .merge(protected)
+    }
+
+    async fn call(
+        app: &Router,
+        request: axum::http::request::Builder,
+        session: Option<&str>,
+    ) -> anyhow::Result<(StatusCode, Option<String>, String)> {
+        let request = match session {
+            Some(session) => request.header(COOKIE, format!("session_id={session}")),
+            None => request,
+        };
+        let response = app.clone().oneshot(request.body(Body::empty())?).await?;
+        let status = response.status();
+        let session = response
+            .headers()
+            .get(SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| cookie::Cookie::parse(value.to_string()).ok())
+            .map(|cookie| cookie.value().to_string());
+        let body = response.into_body().collect().await?.to_bytes();
+
+        Ok((status, session, String::from_utf8(body.to_vec())?))
+    }
+
+    #[tokio::test]
+    async fn test_session_lifecycle() -> anyhow::Result<()> {
+        let user = uuid::Uuid::new_v4();
+        let app = app(SessionManager::build().build(), user);
+        let (_, session, _) = call(&app, Request::post("/login"), None).await?;
+        let session = session.unwrap_or_default();
+        let (status, _, body) = call(&app, Request::get("/"), Some(&session)).await?;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            format!(
+                "{user} {:?} {:?}",
+                AuthenticationMethod::Session,
+                Some(Acr::Password)
+            )
+        );
+
+        let (_, removal, _) = call(&app, Request::post("/logout"), Some(&session)).await?;
+        let (status, _, _) = call(&app, Request::get("/"), Some(&session)).await?;
+
+        assert_eq!(removal.as_deref(), Some(""));
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_regenerates_id() -> anyhow::Result<()> {
+        let app = app(SessionManager::build().build(), uuid::Uuid::new_v4());
+        let (_, first, _) = call(&app, Request::post("/login"), None).await?;
+        let first = first.unwrap_or_default();
+        let (_, second, _) = call(&app, Request::post("/login"), Some(&first)).await?;
+        let second = second.unwrap_or_default();
+
+        assert_ne!(first, second);
+        assert_eq!(
+            call(&app, Request::get("/"), Some(&first)).await?.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(&app, Request::get("/"), Some(&second)).await?.0,
+            StatusCode::OK
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected_sessions() -> anyhow::Result<()> {
+        let sessions = SessionManager::build().idle_timeout(Duration::ZERO).build();
+        let app = app(sessions, uuid::Uuid::new_v4());
+        let (_, session, _) = call(&app, Request::post("/login"), None).await?;
+
+        for session in [session.as_deref(), Some("unknown"), None] {
+            let (status, _, body) = call(&app, Request::get("/"), session).await?;
+
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&body)?["code"],
+                "invalid_session"
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+//! Module containing server-side sessions for server rendered applications
+//!
+//! Instead of JWTs, clients get an opaque random session id in a cookie. The
+//! session's data is kept in a [`SessionStore`], keyed by the id's hash, and
+//! expires after an idle and an absolute timeout. Call
+//! [`SessionManager::start`] from the login handler, which always issues a
+//! new id, and protect routes using the [`authenticate_session`] middleware,
+//! which inserts the same
+//! [`AuthenticatedSubject`](crate::authentication::AuthenticatedSubject) as
+//! for JWTs.
+//!
+//! As a stateless alternative, [`SessionCookie`] keeps small typed payloads in
+//! a signed or encrypted cookie, which handlers read using the
+//! [`CookieSession`] extractor.
+//!
+//! Only available on feature `session`
+mod error;
+mod manager;
+mod middleware;
+mod stateless;
+mod store;
+
+pub use error::*;
+pub use manager::*;
+pub use middleware::*;
+pub use stateless::*;
+pub use store::*;
+
+pub use cookie::Key;
@@ -0,0 +1,393 @@
+use crate::error::{GateKeeperError, TokenError};
+use crate::GateKeeperResult;
+use axum::extract::{FromRef, FromRequestParts, OptionalFromRequestParts};
+use axum::http::header::COOKIE;
+use axum::http::request::Parts;
+use axum::http::HeaderMap;
+use chrono::Utc;
+use cookie::time::OffsetDateTime;
+use cookie::{Cookie, CookieJar, Key};
+use data_encoding::BASE64URL_NOPAD;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How the payload of a [`SessionCookie`] is protected
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum CookieProtection {
+    /// Signed, so clients can read but not modify the payload
+    Signed,
+    /// Encrypted and authenticated, so clients can neither read nor modify it
+    #[default]
+    Private,
+}
+
+/// Payload stored in the cookie, bounding its lifetime even if a client
+/// ignores the cookie's expiration
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    exp: usize,
+    data: T,
+}
+
+/// Encodes small, typed session payloads into signed or encrypted cookies
+///
+/// New cookies are always protected using the current key. Cookies protected
+/// using one of the previous keys are still accepted, so keys can be rotated
+/// without logging out all users. Handlers read the payload using the
+/// [`CookieSession`] extractor, which requires `SessionCookie: FromRef<S>`.
+#[derive(Clone)]
+pub struct SessionCookie {
+    /// The current key followed by the previous ones
+    keys: Arc<[Key]>,
+    protection: CookieProtection,
+    cookie_name: Arc<str>,
+    max_age: Duration,
+}
+
+impl SessionCookie {
+    /// Name of the cookie if none is set
+    pub const DEFAULT_COOKIE_NAME: &'static str = "session";
+
+    /// Create a builder for SessionCookie protecting new cookies using `key`
+    pub fn build(key: Key) -> SessionCookieBuilder {
+        SessionCookieBuilder {
+            keys: vec![key],
+            protection: None,
+            cookie_name: None,
+            max_age: None,
+        }
+    }
+
+    /// Encode `session` into a cookie to set
+    ///
+    /// Uses the same cookie policy as `RefreshToken::try_as_cookie`.
+    pub fn encode<T: Serialize>(&self, session: &T) -> GateKeeperResult<Cookie<'static>> {
+        let exp = Utc::now().timestamp() as usize + self.max_age.as_secs() as usize;
+        let json = serde_json::to_vec(&Envelope { exp, data: session }).map_err(|e| {
+            TokenError::Encode(
+                crate::ErrorResponse::build()
+                    .message(format!("Error serializing session: {e}"))
+                    .build(),
+            )
+        })?;
+        // JSON isn't a valid cookie value, so encode it first
+        let value = BASE64URL_NOPAD.encode(&json);
+        let cookie = Cookie::build((self.cookie_name.to_string(), value))
+            .path("/")
+            .expires(
+                OffsetDateTime::from_unix_timestamp(exp as i64)
+                    .map_err(TokenError::ReadingExpiration)?,
+            )
+            .secure(true)
+            .http_only(true)
+            .same_site(cookie::SameSite::None)
+            .build();
+        let mut jar = CookieJar::new();
+
+        match self.protection {
+            CookieProtection::Signed => jar.signed_mut(&self.keys[0]).add(cookie),
+            CookieProtection::Private => jar.private_mut(&self.keys[0]).add(cookie),
+        }
+
+        jar.get(&self.cookie_name)
+            .cloned()
+            .ok_or_else(|| TokenError::MissingTokenString.into())
+    }
+
+    /// Decode the session from the request's cookies
+    ///
+    /// Returns `None` if there's no session cookie and fails with
+    /// [`TokenError::InvalidSignature`] if it was tampered with or
+    /// [`TokenError::Expired`] if it has expired.
+    pub fn decode<T: DeserializeOwned>(&self, headers: &HeaderMap) -> GateKeeperResult<Option<T>> {
+        let Some(raw) = self.raw_cookie(headers) else {
+            return Ok(None);
+        };
+        let value = self
+            .keys
+            .iter()
+            .find_map(|key| {
+                let mut jar = CookieJar::new();
+
+                jar.add_original(raw.clone());
+
+                match self.protection {
+                    CookieProtection::Signed => jar.signed(key).get(&self.cookie_name),
+                    CookieProtection::Private => jar.private(key).get(&self.cookie_name),
+                }
+            })
+            .ok_or(TokenError::InvalidSignature)?;
+        let envelope = BASE64URL_NOPAD
+            .decode(value.value().as_bytes())
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Envelope<T>>(&json).ok())
+            .ok_or_else(|| TokenError::Malformed("Malformed session cookie".to_string()))?;
+
+        if envelope.exp <= Utc::now().timestamp() as usize {
+            return Err(TokenError::Expired.into());
+        }
+
+        Ok(Some(envelope.data))
+    }
+
+    /// Return the cookie removing the session
+    pub fn removal(&self) -> Cookie<'static> {
+        Cookie::build((self.cookie_name.to_string(), ""))
+            .path("/")
+            .removal()
+            .build()
+    }
+
+    fn raw_cookie(&self, headers: &HeaderMap) -> Option<Cookie<'static>> {
+        headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(Cookie::split_parse)
+            .filter_map(Result::ok)
+            .find(|cookie| cookie.name() == &*self.cookie_name)
+            .map(Cookie::into_owned)
+    }
+}
+
+pub struct SessionCookieBuilder {
+    keys: Vec<Key>,
+    protection: Option<CookieProtection>,
+    cookie_name: Option<String>,
+    max_age: Option<Duration>,
+}
+
+impl SessionCookieBuilder {
+    /// Accept cookies protected using `key`, which was replaced by the current
+    /// key
+    pub fn previous_key(mut self, key: Key) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    /// Set field `protection`, defaults to [`CookieProtection::Private`]
+    pub fn protection(mut self, protection: CookieProtection) -> Self {
+        self.protection = Some(protection);
+        self
+    }
+
+    /// Set field `cookie_name`, defaults to [`SessionCookie::DEFAULT_COOKIE_NAME`]
+    pub fn cookie_name(mut self, cookie_name: &str) -> Self {
+        self.cookie_name = Some(cookie_name.to_string());
+        self
+    }
+
+    /// Set field `max_age`, defaults to 12 hours
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Actually create the SessionCookie
+    pub fn build(self) -> SessionCookie {
+        SessionCookie {
+            keys: self.keys.into(),
+            protection: self.protection.unwrap_or_default(),
+            cookie_name: self
+                .cookie_name
+                .as_deref()
+                .unwrap_or(SessionCookie::DEFAULT_COOKIE_NAME)
+                .into(),
+            max_age: self.max_age.unwrap_or(Duration::from_secs(12 * 60 * 60)),
+        }
+    }
+}
+
+/// Extractor decoding the payload of the request's [`SessionCookie`]
+///
+/// Rejects requests without session cookie with
+/// [`TokenError::MissingTokenString`] and tampered or expired cookies as
+/// described by [`SessionCookie::decode`]. Extract `Option<CookieSession<T>>` to allow
+/// requests without session.
+#[derive(Debug, Clone)]
+pub struct CookieSession<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for CookieSession<T>
+where
+    SessionCookie: FromRef<S>,
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = GateKeeperError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        SessionCookie::from_ref(state)
+            .decode(&parts.headers)?
+            .map(CookieSession)
+            .ok_or_else(|| TokenError::MissingTokenString.into())
+    }
+}
+
+impl<S, T> OptionalFromRequestParts<S> for CookieSession<T>
+where
+    SessionCookie: FromRef<S>,
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = GateKeeperError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(SessionCookie::from_ref(state)
+            .decode(&parts.headers)?
+            .map(CookieSession))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CookieProtection, CookieSession, SessionCookie};
+    use axum::body::Body;
+    use axum::http::header::COOKIE;
+    use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use cookie::{Cookie, Key};
+    use http_body_util::BodyExt;
+    use serde::{Deserialize, Serialize};
+    use tower::ServiceExt;
+
+    #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+    struct Cart {
+        user: String,
+        items: Vec<u32>,
+    }
+
+    fn cart() -> Cart {
+        Cart {
+            user: "alice".to_string(),
+            items: vec![1, 2],
+        }
+    }
+
+    fn headers(cookie: &Cookie) -> anyhow::Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        let value = format!("{}={}", cookie.name(), cookie.value());
+
+        headers.insert(COOKIE, HeaderValue::from_str(&value)?);
+
+        Ok(headers)
+    }
+
+    #[test]
+    fn test_roundtrip() -> anyhow::Result<()> {
+        for protection in [CookieProtection::Signed, CookieProtection::Private] {
+            let sessions = SessionCookie::build(Key::generate())
+                .protection(protection)
+                .build();
+            let cookie = sessions.encode(&cart())?;
+
+            assert!(cookie.secure().unwrap_or_default());
+            assert!(cookie.http_only().unwrap_or_default());
+            assert_eq!(sessions.decode::<Cart>(&headers(&cookie)?)?, Some(cart()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_tampered_cookies() -> anyhow::Result<()> {
+        for protection in [CookieProtection::Signed, CookieProtection::Private] {
+            let sessions = SessionCookie::build(Key::generate())
+                .protection(protection)
+                .build();
+            let cookie = sessions.encode(&cart())?;
+            let mut value = cookie.value().to_string();
+            let middle = value.len() / 2;
+            let replacement = if &value[middle..=middle] == "A" {
+                "B"
+            } else {
+                "A"
+            };
+
+            value.replace_range(middle..=middle, replacement);
+
+            let tampered = Cookie::new(cookie.name().to_string(), value);
+
+            assert!(sessions.decode::<Cart>(&headers(&tampered)?).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_rotation() -> anyhow::Result<()> {
+        let old = Key::generate();
+        let cookie = SessionCookie::build(old.clone()).build().encode(&cart())?;
+        let rotated = SessionCookie::build(Key::generate())
+            .previous_key(old)
+            .build();
+        let dropped = SessionCookie::build(Key::generate()).build();
+
+        assert_eq!(rotated.decode::<Cart>(&headers(&cookie)?)?, Some(cart()));
+        assert!(dropped.decode::<Cart>(&headers(&cookie)?).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_expired_cookies() -> anyhow::Result<()> {
+        let sessions = SessionCookie::build(Key::generate())
+            .max_age(std::time::Duration::ZERO)
+            .build();
+        let cookie = sessions.encode(&cart())?;
+
+        assert!(sessions.decode::<Cart>(&headers(&cookie)?).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extractor() -> anyhow::Result<()> {
+        let sessions = SessionCookie::build(Key::generate()).build();
+        let cookie = sessions.encode(&cart())?;
+        let app = Router::new()
+            .route(
+                "/",
+                get(|CookieSession(cart): CookieSession<Cart>| async move { cart.user }),
+            )
+            .route(
+                "/optional",
+                get(|cart: Option<CookieSession<Cart>>| async move { cart.is_some().to_string() }),
+            )
+            .with_state(sessions);
+        let call = |uri: &str, cookie: Option<String>| {
+            let mut request = Request::get(uri);
+
+            if let Some(cookie) = cookie {
+                request = request.header(COOKIE, cookie);
+            }
+
+            app.clone()
+                .oneshot(request.body(Body::empty()).unwrap_or_default())
+        };
+        let valid = format!("session={}", cookie.value());
+        let response = call("/", Some(valid)).await?;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body().collect().await?.to_bytes(), "alice");
+
+        let response = call("/", None).await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = call("/", Some("session=forged".to_string())).await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = call("/optional", None).await?;
+
+        assert_eq!(response.into_body().collect().await?.to_bytes(), "false");
+
+        Ok(())
+    }
+}
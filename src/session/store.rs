@@ -0,0 +1,147 @@
+use crate::tokens::Acr;
+use crate::GateKeeperResult;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Stored data of a session, looked up by the session id's hash
+#[derive(Debug, Clone)]
+pub struct SessionRecord {
+    /// Id of the user the session belongs to
+    pub subject: uuid::Uuid,
+    /// Time the user actually authenticated as unix timestamp
+    pub auth_time: usize,
+    pub acr: Option<Acr>,
+    pub created_at: DateTime<Utc>,
+    /// Time of the last request using the session
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl SessionRecord {
+    /// Create a record for a session of `subject` starting now
+    pub fn new(subject: uuid::Uuid, auth_time: usize, acr: Option<Acr>) -> Self {
+        let now = Utc::now();
+
+        Self {
+            subject,
+            auth_time,
+            acr,
+            created_at: now,
+            last_seen_at: now,
+        }
+    }
+}
+
+/// Storage for sessions, keyed by the hash of their id
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Add or replace the session with `hash`
+    async fn insert(&self, hash: &str, record: SessionRecord) -> GateKeeperResult<()>;
+
+    /// Return the session with `hash`, if it exists
+    async fn find(&self, hash: &str) -> GateKeeperResult<Option<SessionRecord>>;
+
+    /// Record that the session with `hash` was used at `last_seen_at`
+    async fn touch(&self, hash: &str, last_seen_at: DateTime<Utc>) -> GateKeeperResult<()>;
+
+    /// Remove the session with `hash`, if it exists
+    async fn remove(&self, hash: &str) -> GateKeeperResult<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: SessionStore + ?Sized> SessionStore for std::sync::Arc<T> {
+    async fn insert(&self, hash: &str, record: SessionRecord) -> GateKeeperResult<()> {
+        self.as_ref().insert(hash, record).await
+    }
+
+    async fn find(&self, hash: &str) -> GateKeeperResult<Option<SessionRecord>> {
+        self.as_ref().find(hash).await
+    }
+
+    async fn touch(&self, hash: &str, last_seen_at: DateTime<Utc>) -> GateKeeperResult<()> {
+        self.as_ref().touch(hash, last_seen_at).await
+    }
+
+    async fn remove(&self, hash: &str) -> GateKeeperResult<()> {
+        self.as_ref().remove(hash).await
+    }
+}
+
+/// Process local [`SessionStore`]
+///
+/// Expired sessions are only removed when they're used again, so prefer a
+/// store with expiration, e.g. Redis, for production.
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<HashMap<String, SessionRecord>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn insert(&self, hash: &str, record: SessionRecord) -> GateKeeperResult<()> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+
+        sessions.insert(hash.to_string(), record);
+
+        Ok(())
+    }
+
+    async fn find(&self, hash: &str) -> GateKeeperResult<Option<SessionRecord>> {
+        let sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+
+        Ok(sessions.get(hash).cloned())
+    }
+
+    async fn touch(&self, hash: &str, last_seen_at: DateTime<Utc>) -> GateKeeperResult<()> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(record) = sessions.get_mut(hash) {
+            record.last_seen_at = last_seen_at;
+        }
+
+        Ok(())
+    }
+
+    async fn remove(&self, hash: &str) -> GateKeeperResult<()> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+
+        sessions.remove(hash);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemorySessionStore, SessionRecord, SessionStore};
+    use chrono::{Duration, Utc};
+
+    #[tokio::test]
+    async fn test_memory_store() -> anyhow::Result<()> {
+        let store = MemorySessionStore::new();
+        let subject = uuid::Uuid::new_v4();
+        let later = Utc::now() + Duration::seconds(10);
+
+        store
+            .insert("hash", SessionRecord::new(subject, 1000, None))
+            .await?;
+        store.touch("hash", later).await?;
+
+        let record = store.find("hash").await?;
+
+        assert_eq!(record.as_ref().map(|record| record.subject), Some(subject));
+        assert_eq!(record.map(|record| record.last_seen_at), Some(later));
+
+        store.remove("hash").await?;
+
+        assert!(store.find("hash").await?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! Models and repositories shared by the crate's unit tests
+use crate::authentication::{MemoryRevocationStore, RevocationStore, UserRepository};
+use crate::model::GateKeeperModel;
+use crate::{GateKeeper, GateKeeperBuilder, GateKeeperResult};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+pub fn init_env() {
+    std::env::set_var("AUTH_EXPIRE_SECS", "3600");
+    std::env::set_var("REFRESH_EXPIRE_SECS", "3600");
+    std::env::set_var("VERIFICATION_EXPIRE_SECS", "6000");
+    std::env::set_var("MFA_PENDING_EXPIRE_SECS", "300");
+}
+
+/// Password hasher with cheap parameters to keep tests fast
+#[cfg(feature = "password")]
+pub fn test_hasher() -> crate::password::PasswordHasher {
+    crate::password::PasswordHasher::build()
+        .memory_cost(1024)
+        .time_cost(1)
+        .build()
+        .expect("valid test parameters")
+}
+
+/// Builder for a gatekeeper using `users` and cheap test parameters
+pub fn test_gatekeeper(
+    users: impl UserRepository<User = TestUser> + 'static,
+) -> GateKeeperBuilder<TestUser> {
+    let gatekeeper = GateKeeper::build(users).revocation_store(YieldingRevocationStore::default());
+
+    #[cfg(feature = "password")]
+    let gatekeeper = gatekeeper.hasher(test_hasher());
+
+    gatekeeper
+}
+
+/// Revocation store yielding after each lookup, so concurrent requests
+/// interleave between checking and revoking tokens
+#[derive(Default)]
+pub struct YieldingRevocationStore(MemoryRevocationStore);
+
+#[async_trait::async_trait]
+impl RevocationStore for YieldingRevocationStore {
+    async fn revoke(&self, jti: &str, exp: usize) -> GateKeeperResult<bool> {
+        self.0.revoke(jti, exp).await
+    }
+
+    async fn is_revoked(&self, jti: &str) -> GateKeeperResult<bool> {
+        let revoked = self.0.is_revoked(jti).await;
+
+        tokio::task::yield_now().await;
+        revoked
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestUser {
+    pub id: uuid::Uuid,
+    pub login: String,
+    pub password_hash: String,
+    pub verified: bool,
+    pub totp_secret: Option<String>,
+}
+
+impl TestUser {
+    pub fn new(login: &str, password_hash: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4(),
+            login: login.to_string(),
+            password_hash: password_hash.to_string(),
+            verified: false,
+            totp_secret: None,
+        }
+    }
+}
+
+impl GateKeeperModel for TestUser {
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn secret(&self) -> &'static str {
+        "test"
+    }
+}
+
+#[cfg(feature = "password")]
+impl crate::password::PasswordModel for TestUser {
+    fn password_hash(&self) -> &str {
+        &self.password_hash
+    }
+
+    #[cfg(feature = "mfa")]
+    fn totp_secret(&self) -> Option<&str> {
+        self.totp_secret.as_deref()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TestRepository {
+    pub users: Mutex<Vec<TestUser>>,
+}
+
+impl TestRepository {
+    pub fn with_users(users: impl IntoIterator<Item = TestUser>) -> Self {
+        Self {
+            users: Mutex::new(users.into_iter().collect()),
+        }
+    }
+
+    pub fn get(&self, id: uuid::Uuid) -> Option<TestUser> {
+        let users = self.users.lock().unwrap();
+
+        users.iter().find(|user| user.id == id).cloned()
+    }
+
+    #[cfg(any(feature = "password", feature = "verification"))]
+    fn update(&self, id: uuid::Uuid, update: impl FnOnce(&mut TestUser)) {
+        let mut users = self.users.lock().unwrap();
+
+        if let Some(user) = users.iter_mut().find(|user| user.id == id) {
+            update(user);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserRepository for TestRepository {
+    type User = TestUser;
+
+    async fn find_by_login(&self, login: &str) -> GateKeeperResult<Option<TestUser>> {
+        let users = self.users.lock().unwrap();
+
+        Ok(users.iter().find(|user| user.login == login).cloned())
+    }
+
+    async fn find_by_id(&self, id: uuid::Uuid) -> GateKeeperResult<Option<TestUser>> {
+        Ok(self.get(id))
+    }
+
+    #[cfg(feature = "password")]
+    async fn update_password_hash(&self, user: &TestUser, hash: String) -> GateKeeperResult<()> {
+        self.update(user.id, |user| user.password_hash = hash);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "verification")]
+    async fn mark_verified(&self, user: &TestUser) -> GateKeeperResult<()> {
+        self.update(user.id, |user| user.verified = true);
+
+        Ok(())
+    }
+}
+
+/// RSA key matching the `test-key` entry of [`test_jwks`]
+#[cfg(feature = "jwks")]
+pub fn test_rsa_key() -> jsonwebtoken::EncodingKey {
+    jsonwebtoken::EncodingKey::from_rsa_pem(include_bytes!("../tests/fixtures/rsa_private.pem"))
+        .expect("valid test key")
+}
+
+/// Key set containing the public part of [`test_rsa_key`] with kid `test-key`
+#[cfg(feature = "jwks")]
+pub fn test_jwks() -> jsonwebtoken::jwk::JwkSet {
+    serde_json::from_str(include_str!("../tests/fixtures/rsa_jwks.json"))
+        .expect("valid test key set")
+}
@@ -0,0 +1,330 @@
+use crate::tokens::{Claims, Token, TokenCodec};
+use crate::GateKeeperResult;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+type CacheKey = [u8; 32];
+
+/// Bounded cache of verified token claims, skipping repeated signature checks
+///
+/// Entries are keyed by a hash of the token type, the secret and the encoded
+/// token, so rotating a user's secret invalidates their cached tokens as well.
+/// They expire after the configured TTL, but no later than the token's `exp`.
+/// Revocation is still checked on every use by the gatekeeper, see
+/// [`GateKeeper::revoke`](crate::GateKeeper::revoke) for dropping entries early.
+///
+/// Only available on feature `token-cache`
+pub struct TokenCache {
+    state: Mutex<CacheState>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Keys in insertion order, evicted first when the cache is full
+    order: VecDeque<CacheKey>,
+}
+
+struct CacheEntry {
+    claims: Claims,
+    expires_at: usize,
+}
+
+impl TokenCache {
+    /// Create a builder for TokenCache
+    pub fn build() -> TokenCacheBuilder {
+        TokenCacheBuilder::default()
+    }
+
+    /// Decode `encoded` like [`Token::decode`], using cached claims if the
+    /// token was verified using `secret` before
+    pub fn decode<T: Token>(
+        &self,
+        encoded: &str,
+        codec: &TokenCodec,
+        secret: &str,
+    ) -> GateKeeperResult<T> {
+        let key = Self::key(T::TOKEN_TYPE, secret, encoded);
+
+        if let Some(claims) = self.get(&key) {
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_cache_lookup(T::NAME, true);
+
+            return Ok(T::new(encoded.to_string(), claims));
+        }
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_cache_lookup(T::NAME, false);
+
+        let token = T::decode(encoded, codec, secret)?;
+
+        self.insert(key, token.get_claims());
+
+        Ok(token)
+    }
+
+    /// Drop all entries of tokens having `jti`, e.g. when revoking it
+    pub fn invalidate(&self, jti: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        state
+            .entries
+            .retain(|_, entry| entry.claims.jti.as_deref() != Some(jti));
+    }
+
+    /// Drop all entries
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    /// Return the number of entries, including expired ones not dropped yet
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .len()
+    }
+
+    /// Return if the cache has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn key(token_type: &str, secret: &str, encoded: &str) -> CacheKey {
+        let mut hasher = Sha256::new();
+
+        for part in [token_type, secret, encoded] {
+            hasher.update((part.len() as u64).to_be_bytes());
+            hasher.update(part.as_bytes());
+        }
+
+        hasher.finalize().into()
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<Claims> {
+        let now = Utc::now().timestamp() as usize;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = state.entries.get(key)?;
+
+        if entry.expires_at <= now {
+            state.entries.remove(key);
+
+            return None;
+        }
+
+        Some(entry.claims.clone())
+    }
+
+    fn insert(&self, key: CacheKey, claims: &Claims) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let now = Utc::now().timestamp() as usize;
+        let expires_at = claims
+            .exp
+            .min(now.saturating_add(self.ttl.as_secs() as usize));
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        while state.entries.len() >= self.capacity {
+            let Some(oldest) = state.order.pop_front() else {
+                break;
+            };
+
+            state.entries.remove(&oldest);
+        }
+
+        // Keys of removed entries stay queued, drop them before they pile up
+        if state.order.len() >= 2 * self.capacity {
+            let CacheState { entries, order } = &mut *state;
+
+            order.retain(|key| entries.contains_key(key));
+        }
+
+        let entry = CacheEntry {
+            claims: claims.clone(),
+            expires_at,
+        };
+
+        if state.entries.insert(key, entry).is_none() {
+            state.order.push_back(key);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TokenCacheBuilder {
+    capacity: Option<usize>,
+    ttl: Option<Duration>,
+}
+
+impl TokenCacheBuilder {
+    /// Set field `capacity`, the maximum number of entries, defaults to 10000
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Set field `ttl`, the maximum time entries are kept, defaults to 5 minutes
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Actually create the TokenCache
+    pub fn build(self) -> TokenCache {
+        TokenCache {
+            state: Mutex::new(CacheState::default()),
+            capacity: self.capacity.unwrap_or(10_000),
+            ttl: self.ttl.unwrap_or(Duration::from_secs(5 * 60)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenCache;
+    use crate::authentication::{AuthenticationToken, RefreshToken};
+    use crate::model::GateKeeperModel;
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Token, TokenCodec};
+    use std::time::Duration;
+
+    #[test]
+    fn test_caches_verified_tokens() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let cache = TokenCache::build().build();
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let encoded = token.get_encoded();
+
+        assert!(cache
+            .decode::<AuthenticationToken>(encoded, &TokenCodec::default(), "wrong")
+            .is_err());
+        assert!(cache.is_empty());
+
+        let decoded =
+            cache.decode::<AuthenticationToken>(encoded, &TokenCodec::default(), user.secret())?;
+        let cached =
+            cache.decode::<AuthenticationToken>(encoded, &TokenCodec::default(), user.secret())?;
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(decoded.get_claims(), token.get_claims());
+        assert_eq!(cached.get_claims(), token.get_claims());
+        // Neither another secret nor another token type hit the entry
+        assert!(cache
+            .decode::<AuthenticationToken>(encoded, &TokenCodec::default(), "wrong")
+            .is_err());
+        assert!(cache
+            .decode::<RefreshToken>(encoded, &TokenCodec::default(), user.secret())
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expiry_and_invalidation() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let encoded = token.get_encoded();
+        let expired = TokenCache::build().ttl(Duration::ZERO).build();
+
+        expired.decode::<AuthenticationToken>(encoded, &TokenCodec::default(), user.secret())?;
+
+        assert_eq!(expired.len(), 1);
+        assert!(expired
+            .get(&TokenCache::key(
+                AuthenticationToken::TOKEN_TYPE,
+                user.secret(),
+                encoded
+            ))
+            .is_none());
+        assert!(expired.is_empty());
+
+        let cache = TokenCache::build().build();
+        let jti = token.get_claims().jti.clone().unwrap_or_default();
+
+        cache.decode::<AuthenticationToken>(encoded, &TokenCodec::default(), user.secret())?;
+        cache.invalidate(&jti);
+
+        assert!(cache.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounded() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let cache = TokenCache::build().capacity(2).build();
+        let tokens = (0..3)
+            .map(|_| AuthenticationToken::try_new_for_model(&user, &TokenCodec::default()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for token in &tokens {
+            cache.decode::<AuthenticationToken>(
+                token.get_encoded(),
+                &TokenCodec::default(),
+                user.secret(),
+            )?;
+        }
+
+        let key = |token: &AuthenticationToken| {
+            TokenCache::key(
+                AuthenticationToken::TOKEN_TYPE,
+                user.secret(),
+                token.get_encoded(),
+            )
+        };
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(&tokens[0])).is_none());
+        assert!(cache.get(&key(&tokens[2])).is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gatekeeper_revocation() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let claims = token.get_claims();
+        let gatekeeper = test_gatekeeper(TestRepository::with_users([user]))
+            .token_cache(TokenCache::build().build())
+            .build()?;
+        let cache = gatekeeper.token_cache().cloned().unwrap();
+
+        gatekeeper
+            .decode_for_user::<AuthenticationToken>(token.get_encoded())
+            .await?;
+
+        assert_eq!(cache.len(), 1);
+
+        gatekeeper
+            .revoke(claims.jti.as_deref().unwrap_or_default(), claims.exp)
+            .await?;
+
+        assert!(cache.is_empty());
+        assert!(gatekeeper
+            .decode_for_user::<AuthenticationToken>(token.get_encoded())
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
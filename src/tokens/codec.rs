@@ -0,0 +1,272 @@
+use crate::error::GateKeeperError;
+use crate::tokens::{Claims, Token, TokenError, TokenKeys};
+use crate::GateKeeperResult;
+use jsonwebtoken::{Algorithm, Header, Validation};
+use std::borrow::Cow;
+
+/// Signs and verifies tokens of all types
+///
+/// Set on the [`GateKeeper`](crate::GateKeeper), which shares it with all
+/// handlers and middleware.
+///
+/// Holds the header template, the validation rules and optionally the keys,
+/// all built once, so encoding and decoding only sign and verify. Without
+/// keys, tokens are signed using the HMAC keys of their user's secret.
+pub struct TokenCodec {
+    header: Header,
+    validation: Validation,
+    keys: Option<TokenKeys>,
+}
+
+impl Default for TokenCodec {
+    /// Codec using `HS512` and the users' secrets
+    fn default() -> Self {
+        Self {
+            header: Header::new(Algorithm::HS512),
+            validation: Validation::new(Algorithm::HS512),
+            keys: None,
+        }
+    }
+}
+
+impl TokenCodec {
+    /// Create a builder for TokenCodec
+    pub fn build() -> TokenCodecBuilder {
+        TokenCodecBuilder::default()
+    }
+
+    /// Return the algorithm tokens are signed with
+    pub fn algorithm(&self) -> Algorithm {
+        self.header.alg
+    }
+
+    /// Return the seconds of clock skew allowed when validating `exp`
+    pub fn leeway(&self) -> u64 {
+        self.validation.leeway
+    }
+
+    /// Encode `claims` to a token of type `T`, signed using `secret` unless
+    /// the codec has its own keys
+    pub fn encode<T: Token>(&self, claims: &Claims, secret: &str) -> GateKeeperResult<String> {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let mut header = self.header.clone();
+
+        header.typ = Some(T::TOKEN_TYPE.to_string());
+        header.kid = Some(claims.sub.clone());
+
+        let result =
+            jsonwebtoken::encode(&header, claims, self.keys(secret).encoding()).map_err(|e| {
+                tracing::error!("Couldn't encode token claims: {}", e);
+
+                let response = crate::ErrorResponse::build()
+                    .message("Couldn't encode token claims".to_string())
+                    .build();
+
+                GateKeeperError::Token(TokenError::Encode(response))
+            });
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_encode(T::NAME, started, &result);
+
+        result
+    }
+
+    /// Decode `encoded` to a token of type `T`, verified using `secret` unless
+    /// the codec has its own keys
+    pub fn decode<T: Token>(&self, encoded: &str, secret: &str) -> GateKeeperResult<T> {
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let result = self.decode_claims(encoded, secret, T::TOKEN_TYPE);
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_decode(T::NAME, started, &result);
+
+        Ok(T::new(encoded.to_string(), result?))
+    }
+
+    /// Return the codec's keys, or build the keys of `secret` if it has none
+    fn keys(&self, secret: &str) -> Cow<'_, TokenKeys> {
+        match &self.keys {
+            Some(keys) => Cow::Borrowed(keys),
+            None => Cow::Owned(TokenKeys::from_secret(secret.as_bytes())),
+        }
+    }
+
+    /// Decode and validate the claims of `encoded`, expecting `typ` `token_type`
+    fn decode_claims(
+        &self,
+        encoded: &str,
+        secret: &str,
+        token_type: &str,
+    ) -> GateKeeperResult<Claims> {
+        let data =
+            jsonwebtoken::decode::<Claims>(encoded, self.keys(secret).decoding(), &self.validation)
+                .map_err(|e| {
+                    tracing::error!("Couldn't decode token claims: {}", e);
+
+                    GateKeeperError::Token(TokenError::from_jwt_error(&e))
+                })?;
+
+        if data.header.typ.as_deref() != Some(token_type) {
+            tracing::error!("Unexpected token type: {:?}", data.header.typ);
+
+            return Err(TokenError::UnexpectedType(data.header.typ).into());
+        }
+
+        Ok(data.claims)
+    }
+}
+
+#[derive(Default)]
+pub struct TokenCodecBuilder {
+    algorithm: Option<Algorithm>,
+    keys: Option<TokenKeys>,
+    leeway: Option<u64>,
+}
+
+impl TokenCodecBuilder {
+    /// Set field `algorithm`, defaults to `HS512`
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Set field `keys`, used for all tokens instead of the users' secrets
+    ///
+    /// Required for algorithms other than HMAC.
+    pub fn keys(mut self, keys: TokenKeys) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Set field `leeway`, the seconds of clock skew allowed when validating
+    /// `exp`, defaults to 60
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.leeway = Some(leeway);
+        self
+    }
+
+    /// Actually create the TokenCodec
+    ///
+    /// Fails for algorithms other than HMAC if no keys are set.
+    pub fn build(self) -> GateKeeperResult<TokenCodec> {
+        let algorithm = self.algorithm.unwrap_or(Algorithm::HS512);
+        let is_hmac = matches!(
+            algorithm,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        );
+
+        if !is_hmac && self.keys.is_none() {
+            tracing::error!("Token algorithm {algorithm:?} requires keys");
+
+            return Err(TokenError::InvalidAlgorithm.into());
+        }
+
+        let mut validation = Validation::new(algorithm);
+
+        if let Some(leeway) = self.leeway {
+            validation.leeway = leeway;
+        }
+
+        Ok(TokenCodec {
+            header: Header::new(algorithm),
+            validation,
+            keys: self.keys,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "authentication"))]
+mod tests {
+    use super::TokenCodec;
+    use crate::authentication::{AuthenticationToken, RefreshToken};
+    use crate::error::GateKeeperError;
+    use crate::tokens::{Claims, Token, TokenError, TokenKeys};
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+
+    fn claims() -> Claims {
+        let now = chrono::Utc::now().timestamp() as usize;
+
+        Claims {
+            exp: now + 1000,
+            iat: now,
+            sub: uuid::Uuid::new_v4().to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_codec() -> anyhow::Result<()> {
+        let codec = TokenCodec::default();
+        let claims = claims();
+        // Secrets may be loaded at runtime
+        let secret = String::from("test");
+        let encoded = codec.encode::<AuthenticationToken>(&claims, &secret)?;
+        let decoded = codec.decode::<AuthenticationToken>(&encoded, &secret)?;
+
+        assert_eq!(codec.algorithm(), Algorithm::HS512);
+        assert_eq!(decoded.get_claims(), &claims);
+        assert_eq!(decoded.get_headers()?.alg, Algorithm::HS512);
+        assert!(codec
+            .decode::<AuthenticationToken>(&encoded, "forged")
+            .is_err());
+        assert!(matches!(
+            codec.decode::<RefreshToken>(&encoded, "test"),
+            Err(GateKeeperError::Token(TokenError::UnexpectedType(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec_keys() -> anyhow::Result<()> {
+        let keys = TokenKeys::new(
+            EncodingKey::from_rsa_pem(include_bytes!("../../tests/fixtures/rsa_private.pem"))?,
+            DecodingKey::from_rsa_pem(include_bytes!("../../tests/fixtures/rsa_public.pem"))?,
+        );
+        let codec = TokenCodec::build()
+            .algorithm(Algorithm::RS256)
+            .keys(keys)
+            .build()?;
+        let claims = claims();
+        let encoded = codec.encode::<AuthenticationToken>(&claims, "test")?;
+        let decoded = codec.decode::<AuthenticationToken>(&encoded, "other")?;
+
+        assert_eq!(decoded.get_claims(), &claims);
+        assert_eq!(decoded.get_headers()?.alg, Algorithm::RS256);
+        // Tokens signed using another algorithm are rejected
+        assert!(codec
+            .decode::<AuthenticationToken>(
+                &TokenCodec::default().encode::<AuthenticationToken>(&claims, "test")?,
+                "test"
+            )
+            .is_err());
+        assert!(TokenCodec::build()
+            .algorithm(Algorithm::RS256)
+            .build()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_leeway() -> anyhow::Result<()> {
+        let now = chrono::Utc::now().timestamp() as usize;
+        let claims = Claims {
+            exp: now - 30,
+            ..claims()
+        };
+        let strict = TokenCodec::build().leeway(0).build()?;
+        let encoded = strict.encode::<AuthenticationToken>(&claims, "test")?;
+
+        assert!(strict
+            .decode::<AuthenticationToken>(&encoded, "test")
+            .is_err());
+        assert!(TokenCodec::default()
+            .decode::<AuthenticationToken>(&encoded, "test")
+            .is_ok());
+
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::challenge::{BearerError, Challenge};
+use crate::error::GateKeeperError;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 
@@ -5,10 +7,12 @@ use axum::response::{IntoResponse, Response};
 pub enum TokenError {
     #[error("Unknown error handling JWT: {0}")]
     Unknown(#[from] jsonwebtoken::errors::Error),
+    #[cfg(feature = "verification")]
     #[error("Error decoding base64 value: {0}")]
     Base64Decode(#[from] base64::DecodeError),
     #[error("Error reading UTF-8 value: {0}")]
     Utf8(#[from] std::string::FromUtf8Error),
+    #[cfg(feature = "authentication")]
     #[error("Couldn't read cookie expiration time: {0:?}")]
     ReadingExpiration(#[from] cookie::time::error::ComponentRange),
     #[error("Error signing tokens: {0}")]
@@ -21,11 +25,149 @@ pub enum TokenError {
     MissingTokenString,
     #[error("Error using refresh tokens: {0}")]
     RefreshToken(crate::ErrorResponse),
+    #[error("Token has expired")]
+    Expired,
+    #[error("Token isn't valid yet")]
+    Immature,
+    #[error("Token has been revoked")]
+    Revoked,
+    #[error("Invalid token signature")]
+    InvalidSignature,
+    #[error("Token uses an algorithm which isn't allowed")]
+    InvalidAlgorithm,
+    #[error("Token was issued by an unexpected issuer")]
+    InvalidIssuer,
+    #[error("Token was issued for another audience")]
+    InvalidAudience,
+    #[error("Malformed token: {0}")]
+    Malformed(String),
+    #[error("No key found for kid {0:?}")]
+    UnknownKid(Option<String>),
+    #[error("Unexpected token type {0:?}")]
+    UnexpectedType(Option<String>),
+}
+
+impl TokenError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            TokenError::Unknown(_) | TokenError::DecodeHeader(_) | TokenError::Decode(_) => {
+                "invalid_token"
+            }
+            #[cfg(feature = "verification")]
+            TokenError::Base64Decode(_) => "malformed_token",
+            TokenError::Utf8(_) => "malformed_token",
+            #[cfg(feature = "authentication")]
+            TokenError::ReadingExpiration(_) => "token_encoding_failed",
+            TokenError::Encode(_) => "token_encoding_failed",
+            TokenError::MissingTokenString => "missing_token",
+            TokenError::RefreshToken(_) => "invalid_refresh_token",
+            TokenError::Expired => "token_expired",
+            TokenError::Immature => "token_not_yet_valid",
+            TokenError::Revoked => "token_revoked",
+            TokenError::InvalidSignature => "invalid_token_signature",
+            TokenError::InvalidAlgorithm => "invalid_token_algorithm",
+            TokenError::InvalidIssuer => "invalid_token_issuer",
+            TokenError::InvalidAudience => "invalid_token_audience",
+            TokenError::Malformed(_) => "malformed_token",
+            TokenError::UnknownKid(_) => "unknown_token_key",
+            TokenError::UnexpectedType(_) => "unexpected_token_type",
+        }
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match self {
+            TokenError::Unknown(_) | TokenError::DecodeHeader(_) | TokenError::Decode(_) => {
+                "Invalid token"
+            }
+            #[cfg(feature = "verification")]
+            TokenError::Base64Decode(_) => "Malformed token",
+            TokenError::Utf8(_) => "Malformed token",
+            #[cfg(feature = "authentication")]
+            TokenError::ReadingExpiration(_) => "Error issuing token",
+            TokenError::Encode(_) => "Error issuing token",
+            TokenError::MissingTokenString => "Missing token",
+            TokenError::RefreshToken(_) => "Invalid refresh token",
+            TokenError::Expired => "Token has expired",
+            TokenError::Immature => "Token isn't valid yet",
+            TokenError::Revoked => "Token has been revoked",
+            TokenError::InvalidSignature => "Invalid token signature",
+            TokenError::InvalidAlgorithm => "Invalid token algorithm",
+            TokenError::InvalidIssuer => "Invalid token issuer",
+            TokenError::InvalidAudience => "Invalid token audience",
+            TokenError::Malformed(_) => "Malformed token",
+            TokenError::UnknownKid(_) => "Unknown token key",
+            TokenError::UnexpectedType(_) => "Unexpected token type",
+        }
+    }
+
+    /// Return details which are safe to show to clients, if any
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            TokenError::Encode(e)
+            | TokenError::DecodeHeader(e)
+            | TokenError::Decode(e)
+            | TokenError::RefreshToken(e) => Some(e.message().to_string()),
+            _ => None,
+        }
+    }
+
+    /// Return the `WWW-Authenticate` challenge to respond with, if any
+    pub fn challenge(&self) -> Option<Challenge> {
+        match self {
+            TokenError::MissingTokenString => Some(Challenge::default()),
+            TokenError::DecodeHeader(e) | TokenError::Decode(e) | TokenError::RefreshToken(e) => {
+                Some(Challenge::new(BearerError::InvalidToken, e.message()))
+            }
+            #[cfg(feature = "authentication")]
+            TokenError::ReadingExpiration(_) => None,
+            TokenError::Encode(_) => None,
+            _ => Some(Challenge::new(BearerError::InvalidToken, self.title())),
+        }
+    }
+
+    /// Map a failure of `jsonwebtoken` to decode or validate a token
+    pub fn from_jwt_error(e: &jsonwebtoken::errors::Error) -> Self {
+        use jsonwebtoken::errors::ErrorKind;
+
+        match e.kind() {
+            ErrorKind::ExpiredSignature => TokenError::Expired,
+            ErrorKind::ImmatureSignature => TokenError::Immature,
+            ErrorKind::InvalidSignature => TokenError::InvalidSignature,
+            ErrorKind::InvalidAlgorithm
+            | ErrorKind::InvalidAlgorithmName
+            | ErrorKind::MissingAlgorithm => TokenError::InvalidAlgorithm,
+            ErrorKind::InvalidIssuer => TokenError::InvalidIssuer,
+            ErrorKind::InvalidAudience => TokenError::InvalidAudience,
+            ErrorKind::InvalidToken
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_)
+            | ErrorKind::InvalidSubject
+            | ErrorKind::MissingRequiredClaim(_) => TokenError::Malformed(e.to_string()),
+            _ => {
+                let response = crate::ErrorResponse::build()
+                    .message("Couldn't decode token claims".to_string())
+                    .build();
+
+                TokenError::Decode(response)
+            }
+        }
+    }
 }
 
 impl IntoResponse for TokenError {
     fn into_response(self) -> Response {
+        GateKeeperError::from(self).into_response()
+    }
+}
+
+impl TokenError {
+    /// Render the error as done by previous versions
+    pub(crate) fn legacy_response(&self) -> Response {
         match self {
+            #[cfg(feature = "authentication")]
             TokenError::ReadingExpiration(e) => {
                 tracing::error!("Error reading expiration time: {:?}", e);
                 (
@@ -34,6 +176,7 @@ impl IntoResponse for TokenError {
                 )
                     .into_response()
             }
+            #[cfg(feature = "verification")]
             TokenError::Base64Decode(e) => {
                 tracing::error!("Error decoding base64 value: {:?}", e);
                 (
@@ -68,6 +211,27 @@ impl IntoResponse for TokenError {
             TokenError::RefreshToken(e) => {
                 (StatusCode::UNAUTHORIZED, e.to_string()).into_response()
             }
+            TokenError::UnknownKid(_) => {
+                let response = crate::ErrorResponse::build()
+                    .message("Unknown token subject".to_string())
+                    .build();
+
+                (StatusCode::UNAUTHORIZED, response.to_string()).into_response()
+            }
+            TokenError::Revoked => {
+                let response = crate::ErrorResponse::build()
+                    .message("Token has been revoked".to_string())
+                    .build();
+
+                (StatusCode::UNAUTHORIZED, response.to_string()).into_response()
+            }
+            _ => {
+                let response = crate::ErrorResponse::build()
+                    .message("Couldn't decode token claims".to_string())
+                    .build();
+
+                (StatusCode::UNAUTHORIZED, response.to_string()).into_response()
+            }
         }
     }
 }
@@ -0,0 +1,34 @@
+use jsonwebtoken::{DecodingKey, EncodingKey};
+
+/// Pre-built keys used to sign and verify tokens
+#[derive(Clone)]
+pub struct TokenKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl TokenKeys {
+    /// Create keys from pre-built `encoding` and `decoding` keys, e.g. an
+    /// RSA key pair
+    pub fn new(encoding: EncodingKey, decoding: DecodingKey) -> Self {
+        Self { encoding, decoding }
+    }
+
+    /// Build the keys of HMAC secret `secret`
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    /// Return the key used to sign tokens
+    pub fn encoding(&self) -> &EncodingKey {
+        &self.encoding
+    }
+
+    /// Return the key used to verify tokens
+    pub fn decoding(&self) -> &DecodingKey {
+        &self.decoding
+    }
+}
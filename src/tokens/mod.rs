@@ -1,6 +1,10 @@
 mod service;
 
+#[cfg(feature = "token-cache")]
+mod cache;
+mod codec;
 mod error;
+mod keys;
 
 #[cfg(feature = "authentication")]
 pub use crate::authentication::AuthenticationToken;
@@ -8,34 +12,143 @@ pub use crate::authentication::AuthenticationToken;
 pub use crate::authentication::RefreshToken;
 #[cfg(feature = "verification")]
 pub use crate::verification::VerificationError;
+#[cfg(feature = "verification")]
+pub use crate::verification::VerificationToken;
 
+#[cfg(feature = "token-cache")]
+pub use cache::{TokenCache, TokenCacheBuilder};
 use chrono::Utc;
+pub use codec::{TokenCodec, TokenCodecBuilder};
 pub use error::*;
+pub use keys::TokenKeys;
 pub use service::TokenService;
 
-use crate::error::GateKeeperError;
 use crate::model::GateKeeperModel;
 use crate::GateKeeperResult;
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::Header;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Eq, PartialEq)]
 pub struct Claims {
     pub exp: usize,
     pub iat: usize,
     pub sub: String,
+    /// Time the user actually authenticated, kept when tokens are refreshed
+    #[serde(default)]
+    pub auth_time: usize,
+    /// How the user authenticated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acr: Option<Acr>,
+    /// Space separated OAuth2 scopes granted to the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// OAuth2 client the token was issued to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Unique id of the token, used to revoke it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+/// Authentication context class, ordered by strength
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Acr {
+    /// Password login
+    Password,
+    /// Password login and a second factor
+    Mfa,
+}
+
+impl Acr {
+    /// Return the value used in claims and `acr_values`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Acr::Password => "password",
+            Acr::Mfa => "mfa",
+        }
+    }
+
+    /// Return the class proven by authentication method references (RFC 8176)
+    ///
+    /// Logins only count as multi-factor if `amr` contains `mfa` or methods of
+    /// at least two factor categories, as e.g. `hwk` alone is a single factor.
+    pub fn from_amr<'a>(amr: impl IntoIterator<Item = &'a str>) -> Acr {
+        let mut categories = [false; 3];
+
+        for method in amr {
+            let category = match method {
+                "mfa" => return Acr::Mfa,
+                // Something the user knows
+                "pwd" | "pin" | "kba" => 0,
+                // Something the user has
+                "hwk" | "swk" | "otp" | "sms" | "tel" | "sc" | "pop" => 1,
+                // Something the user is
+                "face" | "fpt" | "iris" | "retina" | "vbm" => 2,
+                _ => continue,
+            };
+
+            categories[category] = true;
+        }
+
+        match categories.iter().filter(|used| **used).count() {
+            0 | 1 => Acr::Password,
+            _ => Acr::Mfa,
+        }
+    }
 }
 
 pub trait Token {
     const EXPIRE_SECS_VAR: &'static str = "";
 
+    /// Value of the `typ` header, used to tell token kinds apart
+    const TOKEN_TYPE: &'static str = "JWT";
+
+    /// Name of the token kind, used e.g. to label metrics
+    const NAME: &'static str = "token";
+
     /// Create new tokens
     fn new(encoded: String, claims: Claims) -> Self
     where
         Self: Sized;
 
     /// Create tokens for provided user
-    fn try_new_for_model(user: &impl GateKeeperModel) -> GateKeeperResult<Self>
+    fn try_new_for_model(user: &impl GateKeeperModel, codec: &TokenCodec) -> GateKeeperResult<Self>
+    where
+        Self: Sized,
+    {
+        Self::try_new_for_authentication(user, Utc::now().timestamp() as usize, None, codec)
+    }
+
+    /// Create tokens for provided user, who authenticated at `auth_time` using `acr`
+    ///
+    /// Use the previous token's `auth_time` and `acr` when refreshing, so they
+    /// keep describing the original login.
+    fn try_new_for_authentication(
+        user: &impl GateKeeperModel,
+        auth_time: usize,
+        acr: Option<Acr>,
+        codec: &TokenCodec,
+    ) -> GateKeeperResult<Self>
+    where
+        Self: Sized,
+    {
+        let template = Claims {
+            auth_time,
+            acr,
+            ..Default::default()
+        };
+
+        Self::try_new_from_template(user, template, codec)
+    }
+
+    /// Create tokens for provided user, taking all claims but `exp`, `iat`,
+    /// `sub` and `jti` from `template`
+    fn try_new_from_template(
+        user: &impl GateKeeperModel,
+        template: Claims,
+        codec: &TokenCodec,
+    ) -> GateKeeperResult<Self>
     where
         Self: Sized,
     {
@@ -45,55 +158,28 @@ pub trait Token {
             exp,
             iat,
             sub: user.id().to_string(),
+            jti: Some(uuid::Uuid::new_v4().to_string()),
+            ..template
         };
-        let encoded = Self::encode(&claims, user.secret().to_string())?;
+        let encoded = Self::encode(&claims, codec, user.secret())?;
 
         Ok(Self::new(encoded, claims))
     }
 
-    /// Encode given claims to JWT tokens
-    fn encode(claims: &Claims, secret: String) -> GateKeeperResult<String> {
-        let mut header = Header::new(Algorithm::HS512);
-
-        header.kid = Some(claims.sub.clone());
-
-        let key = EncodingKey::from_secret(secret.as_bytes());
-        let encoded = jsonwebtoken::encode(&header, claims, &key).map_err(|e| {
-            tracing::error!("Couldn't encode token claims: {}", e);
-
-            let response = crate::ErrorResponse::build()
-                .message("Couldn't encode token claims".to_string())
-                .build();
-
-            GateKeeperError::Token(TokenError::Encode(response))
-        })?;
-
-        Ok(encoded)
+    /// Encode given claims to JWT tokens using `codec`
+    fn encode(claims: &Claims, codec: &TokenCodec, secret: &str) -> GateKeeperResult<String>
+    where
+        Self: Sized,
+    {
+        codec.encode::<Self>(claims, secret)
     }
 
-    /// Decode Token from given `encoded` string using `secret`
-    fn decode(encoded: String, secret: &str) -> GateKeeperResult<Self>
+    /// Decode Token from given `encoded` string using `codec`
+    fn decode(encoded: &str, codec: &TokenCodec, secret: &str) -> GateKeeperResult<Self>
     where
         Self: Sized,
     {
-        let validation = Validation::new(Algorithm::HS512);
-        let claims = jsonwebtoken::decode::<Claims>(
-            &encoded,
-            &DecodingKey::from_secret(secret.as_ref()),
-            &validation,
-        )
-        .map_err(|e| {
-            tracing::error!("Couldn't decode token claims: {}", e);
-
-            let response = crate::ErrorResponse::build()
-                .message("Couldn't decode token claims".to_string())
-                .build();
-
-            GateKeeperError::Token(TokenError::Decode(response))
-        })?
-        .claims;
-
-        Ok(Self::new(encoded, claims))
+        codec.decode(encoded, secret)
     }
 
     /// Test if tokens is expired
@@ -104,10 +190,31 @@ pub trait Token {
     /// Return the tokens claims
     fn get_claims(&self) -> &Claims;
 
+    /// Return the tokens claims, dropping the encoded string
+    fn into_claims(self) -> Claims
+    where
+        Self: Sized;
+
     /// Return encoded tokens string
-    fn get_encoded(&self) -> &String;
+    fn get_encoded(&self) -> &str;
 
     fn get_headers(&self) -> GateKeeperResult<Header> {
-        TokenService::get_token_headers_from_encoded(self.get_encoded().to_string())
+        TokenService::get_token_headers_from_encoded(self.get_encoded())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Acr;
+
+    #[test]
+    fn test_acr_from_amr() {
+        assert_eq!(Acr::from_amr(["mfa"]), Acr::Mfa);
+        assert_eq!(Acr::from_amr(["pwd", "otp"]), Acr::Mfa);
+        assert_eq!(Acr::from_amr(["hwk", "fpt"]), Acr::Mfa);
+        assert_eq!(Acr::from_amr(["hwk"]), Acr::Password);
+        assert_eq!(Acr::from_amr(["sms", "otp"]), Acr::Password);
+        assert_eq!(Acr::from_amr(["pwd", "unknown"]), Acr::Password);
+        assert_eq!(Acr::from_amr([]), Acr::Password);
     }
 }
@@ -6,9 +6,9 @@ pub struct TokenService;
 
 impl TokenService {
     pub fn get_token_headers_from_encoded(
-        encoded: String,
+        encoded: impl AsRef<str>,
     ) -> GateKeeperResult<jsonwebtoken::Header> {
-        jsonwebtoken::decode_header(&encoded).map_err(|e| {
+        jsonwebtoken::decode_header(encoded.as_ref()).map_err(|e| {
             tracing::error!("Error decoding token header: {}", e);
 
             let response = ErrorResponse::build()
@@ -18,12 +18,25 @@ impl TokenService {
             GateKeeperError::Token(TokenError::DecodeHeader(response))
         })
     }
+
+    /// Read the subject's ID from the `kid` header without verifying the token
+    pub fn get_subject_from_encoded(encoded: &str) -> GateKeeperResult<uuid::Uuid> {
+        let kid = Self::get_token_headers_from_encoded(encoded)?.kid;
+
+        kid.as_deref()
+            .and_then(|kid| kid.parse().ok())
+            .ok_or_else(|| {
+                tracing::error!("Token header contains no valid subject ID");
+
+                GateKeeperError::Token(TokenError::UnknownKid(kid.clone()))
+            })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::TokenService;
-    use crate::tokens::{Claims, Token};
+    use crate::tokens::{Claims, Token, TokenCodec};
 
     struct TestToken {
         encoded: String,
@@ -42,7 +55,11 @@ mod tests {
             &self.claims
         }
 
-        fn get_encoded(&self) -> &String {
+        fn into_claims(self) -> Claims {
+            self.claims
+        }
+
+        fn get_encoded(&self) -> &str {
             &self.encoded
         }
     }
@@ -55,13 +72,92 @@ mod tests {
             exp: now + 1000,
             iat: now,
             sub: uuid.to_string(),
+            ..Default::default()
         };
         let secret = "test";
-        let encoded = TestToken::encode(&claims, secret.to_string())?;
+        let encoded = TestToken::encode(&claims, &TokenCodec::default(), secret)?;
         let headers = TokenService::get_token_headers_from_encoded(encoded)?;
 
         assert_eq!(uuid.to_string(), headers.kid.unwrap());
 
         Ok(())
     }
+
+    #[test]
+    fn test_get_subject_from_encoded() -> anyhow::Result<()> {
+        let uuid = uuid::Uuid::new_v4();
+        let claims = Claims {
+            exp: 1,
+            iat: 1,
+            sub: uuid.to_string(),
+            ..Default::default()
+        };
+        let encoded = TestToken::encode(&claims, &TokenCodec::default(), "test")?;
+
+        assert_eq!(TokenService::get_subject_from_encoded(&encoded)?, uuid);
+        assert!(TokenService::get_subject_from_encoded("foo.bar.baz").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_errors() -> anyhow::Result<()> {
+        use crate::error::{GateKeeperError, TokenError};
+        use jsonwebtoken::errors::ErrorKind;
+
+        let now = chrono::Utc::now().timestamp() as usize;
+        let claims = Claims {
+            exp: now + 600,
+            iat: now,
+            sub: uuid::Uuid::new_v4().to_string(),
+            ..Default::default()
+        };
+        let expired = Claims {
+            exp: now - 600,
+            ..claims.clone()
+        };
+        let hs256 = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(b"test"),
+        )?;
+        let code =
+            |encoded: String| match TestToken::decode(&encoded, &TokenCodec::default(), "test") {
+                Err(GateKeeperError::Token(e)) => e.code(),
+                _ => "",
+            };
+
+        assert_eq!(
+            code(TestToken::encode(&expired, &TokenCodec::default(), "test")?),
+            "token_expired"
+        );
+        assert_eq!(
+            code(TestToken::encode(
+                &claims,
+                &TokenCodec::default(),
+                "forged"
+            )?),
+            "invalid_token_signature"
+        );
+        assert_eq!(code(hs256.clone()), "invalid_token_algorithm");
+        assert_eq!(code("foo.bar.baz".to_string()), "malformed_token");
+
+        for (kind, expected) in [
+            (ErrorKind::ImmatureSignature, "token_not_yet_valid"),
+            (ErrorKind::InvalidIssuer, "invalid_token_issuer"),
+            (ErrorKind::InvalidAudience, "invalid_token_audience"),
+        ] {
+            assert_eq!(TokenError::from_jwt_error(&kind.into()).code(), expected);
+        }
+
+        // Tokens of other issuers lack the subject as kid
+        assert_eq!(
+            TokenService::get_subject_from_encoded(&hs256)
+                .err()
+                .map(|e| e.code()),
+            Some("unknown_token_key")
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,36 @@
+//! Helpers shared by several features
+#[cfg(any(feature = "oauth2", feature = "oidc"))]
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+#[cfg(any(feature = "oauth2", feature = "oidc"))]
+use sha2::{Digest, Sha256};
+
+/// Compare `a` and `b` in time independent of where they differ
+#[cfg(any(
+    feature = "basic",
+    feature = "csrf",
+    feature = "mfa",
+    feature = "oauth2"
+))]
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Derive the S256 code challenge of `verifier` (RFC 7636)
+#[cfg(any(feature = "oauth2", feature = "oidc"))]
+pub(crate) fn pkce_challenge(verifier: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+#[cfg(all(test, any(feature = "oauth2", feature = "oidc")))]
+mod tests {
+    use super::pkce_challenge;
+
+    #[test]
+    fn test_pkce_challenge() {
+        // Example from RFC 7636, appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
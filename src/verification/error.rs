@@ -1,14 +1,23 @@
+use crate::error::GateKeeperError;
 use axum::response::{IntoResponse, Response};
 
 #[derive(thiserror::Error, Debug)]
 pub enum VerificationError {}
 
+impl VerificationError {
+    /// Return the machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match *self {}
+    }
+
+    /// Return a short summary which is safe to show to clients
+    pub fn title(&self) -> &'static str {
+        match *self {}
+    }
+}
+
 impl IntoResponse for VerificationError {
     fn into_response(self) -> Response {
-        let response = match self {
-            _ => "",
-        };
-
-        response.into_response()
+        GateKeeperError::from(self).into_response()
     }
 }
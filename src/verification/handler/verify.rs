@@ -0,0 +1,142 @@
+use crate::audit::{AuditContext, AuditEventKind};
+use crate::model::GateKeeperModel;
+use crate::rate_limit::{ClientIp, RateLimitKey};
+use crate::tokens::{Token, TokenService};
+use crate::verification::VerificationToken;
+use crate::{GateKeeper, GateKeeperResult};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+
+/// Query parameters of the verification link
+#[derive(Debug, Deserialize)]
+pub struct VerificationParams {
+    /// Base64 hash of the `VerificationToken`
+    pub token: String,
+}
+
+/// Handler redeeming a `VerificationToken` sent to the user, e.g. by email
+///
+/// Marks the user as verified using the `UserRepository` and responds with
+/// `204 No Content`.
+pub async fn verify<U: GateKeeperModel + 'static>(
+    State(gatekeeper): State<GateKeeper<U>>,
+    client_ip: ClientIp,
+    audit: AuditContext,
+    Query(params): Query<VerificationParams>,
+) -> GateKeeperResult<StatusCode> {
+    tracing::debug!("Using handler::verify");
+
+    let encoded = VerificationToken::try_decode_base64(&params.token).ok();
+    let subject = encoded
+        .as_deref()
+        .and_then(|encoded| TokenService::get_subject_from_encoded(encoded).ok());
+    let keys = client_ip.keys_with(subject.map(RateLimitKey::Subject));
+    let result = gatekeeper
+        .attempt(&keys, async {
+            let encoded = VerificationToken::try_decode_base64(&params.token)?;
+
+            gatekeeper
+                .decode_for_user::<VerificationToken>(&encoded)
+                .await
+        })
+        .await;
+    let (user, token) = audit
+        .record_failure(AuditEventKind::Verification, result)
+        .await?;
+
+    gatekeeper.users().mark_verified(&user).await?;
+
+    audit
+        .record(
+            audit
+                .event(AuditEventKind::Verification)
+                .subject(user.id())
+                .jti(token.get_claims().jti.as_deref()),
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify;
+    use crate::authentication::AuthenticationToken;
+    use crate::rate_limit::RateLimiter;
+    use crate::test_support::{init_env, test_gatekeeper, TestRepository, TestUser};
+    use crate::tokens::{Token, TokenCodec};
+    use crate::verification::VerificationToken;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use base64::{engine::general_purpose, Engine as _};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn app(repository: Arc<TestRepository>) -> anyhow::Result<Router> {
+        let gatekeeper = test_gatekeeper(repository)
+            .rate_limiter(RateLimiter::build().free_attempts(1).build())
+            .build()?;
+
+        Ok(Router::new()
+            .route("/verify", get(verify::<TestUser>))
+            .with_state(gatekeeper))
+    }
+
+    fn request(token: &str) -> anyhow::Result<Request<Body>> {
+        Ok(Request::get(format!("/verify?token={token}")).body(Body::empty())?)
+    }
+
+    #[tokio::test]
+    async fn test_verify() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let id = user.id;
+        let token =
+            VerificationToken::try_new_for_model(&user, &TokenCodec::default())?.try_as_base64()?;
+        let repository = Arc::new(TestRepository::with_users([user]));
+        let response = app(repository.clone())?.oneshot(request(&token)?).await?;
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(repository.get(id).unwrap().verified);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_access_tokens() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let id = user.id;
+        let token = AuthenticationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let encoded = general_purpose::URL_SAFE.encode(token.get_encoded());
+        let repository = Arc::new(TestRepository::with_users([user]));
+        let response = app(repository.clone())?.oneshot(request(&encoded)?).await?;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!repository.get(id).unwrap().verified);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_rate_limited() -> anyhow::Result<()> {
+        init_env();
+
+        let user = TestUser::new("alice", "");
+        let token = VerificationToken::try_new_for_model(&user, &TokenCodec::default())?;
+        let forged = general_purpose::URL_SAFE.encode(format!("{}x", token.get_encoded()));
+        let app = app(Arc::new(TestRepository::with_users([user])))?;
+        let failed = app.clone().oneshot(request(&forged)?).await?;
+        let limited = app.oneshot(request(&token.try_as_base64()?)?).await?;
+
+        assert_eq!(failed.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        Ok(())
+    }
+}
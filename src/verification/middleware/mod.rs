@@ -1,3 +1,4 @@
 mod verify;
 
+#[allow(unused_imports)]
 pub use verify::*;
@@ -2,8 +2,12 @@
 //!
 //! Only available on feature `verification`
 mod error;
+mod handler;
 mod middleware;
 mod token;
 
 pub use error::*;
+pub use handler::*;
+#[allow(unused_imports)]
 pub use middleware::*;
+pub use token::VerificationToken;
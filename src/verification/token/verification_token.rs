@@ -1,9 +1,12 @@
 use crate::error::TokenError;
-use crate::tokens::{Claims, Token};
+use crate::tokens::{Claims, Token, TokenCodec};
 use crate::GateKeeperResult;
 use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
 
+/// Token sent to users, e.g. by email, to verify their account
+///
+/// Uses its own `typ` header, so it's never accepted as `AuthenticationToken`.
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub struct VerificationToken {
     encoded: String,
@@ -16,20 +19,30 @@ impl VerificationToken {
         Ok(general_purpose::URL_SAFE.encode(self.encoded.as_bytes()))
     }
 
-    pub async fn try_from_base64(hash: &str, secret: &str) -> GateKeeperResult<Self> {
-        let encoded = String::from_utf8(
+    /// Decode the token from its base64 hash, verified using `codec` and `secret`
+    pub async fn try_from_base64(
+        hash: &str,
+        codec: &TokenCodec,
+        secret: &str,
+    ) -> GateKeeperResult<Self> {
+        Self::decode(&Self::try_decode_base64(hash)?, codec, secret)
+    }
+
+    /// Try reading the encoded tokens string from its base64 hash
+    pub fn try_decode_base64(hash: &str) -> GateKeeperResult<String> {
+        Ok(String::from_utf8(
             general_purpose::URL_SAFE
                 .decode(hash)
                 .map_err(TokenError::Base64Decode)?,
         )
-        .map_err(TokenError::Utf8)?;
-
-        Self::decode(encoded, secret)
+        .map_err(TokenError::Utf8)?)
     }
 }
 
 impl Token for VerificationToken {
     const EXPIRE_SECS_VAR: &'static str = "VERIFICATION_EXPIRE_SECS";
+    const TOKEN_TYPE: &'static str = "verification+jwt";
+    const NAME: &'static str = "verification";
 
     fn new(encoded: String, claims: Claims) -> Self
     where
@@ -42,7 +55,11 @@ impl Token for VerificationToken {
         &self.claims
     }
 
-    fn get_encoded(&self) -> &String {
+    fn into_claims(self) -> Claims {
+        self.claims
+    }
+
+    fn get_encoded(&self) -> &str {
         &self.encoded
     }
 }
@@ -1,11 +1,8 @@
-use axum_gatekeeper::tokens::{Claims, Token};
+#![cfg(feature = "authentication")]
 
-mod init_env;
+use axum_gatekeeper::tokens::{AuthenticationToken, Claims, RefreshToken, Token, TokenCodec};
 
-#[cfg(feature = "authentication")]
-use axum_gatekeeper::tokens::AuthenticationToken;
-#[cfg(feature = "authentication")]
-use axum_gatekeeper::tokens::RefreshToken;
+mod init_env;
 
 #[test]
 fn test_can_create_auth_token() -> anyhow::Result<()> {
@@ -16,8 +13,9 @@ fn test_can_create_auth_token() -> anyhow::Result<()> {
         exp: 0,
         iat: 0,
         sub: uuid.to_string(),
+        ..Default::default()
     };
-    let token = AuthenticationToken::encode(&claims, "secret".to_string())?;
+    let token = AuthenticationToken::encode(&claims, &TokenCodec::default(), "secret")?;
     let token = AuthenticationToken::new(token, claims);
 
     assert_eq!(token.get_claims().sub, uuid.to_string());
@@ -34,8 +32,9 @@ fn test_can_create_refresh_token() -> anyhow::Result<()> {
         exp: 0,
         iat: 0,
         sub: uuid.to_string(),
+        ..Default::default()
     };
-    let token = RefreshToken::encode(&claims, "secret".to_string())?;
+    let token = RefreshToken::encode(&claims, &TokenCodec::default(), "secret")?;
     let token = RefreshToken::new(token, claims);
 
     assert_eq!(token.get_claims().sub, uuid.to_string());
@@ -3,4 +3,5 @@ pub fn init_test_env() {
     std::env::set_var("AUTH_EXPIRE_SECS", "3600");
     std::env::set_var("REFRESH_EXPIRE_SECS", "3600");
     std::env::set_var("VERIFICATION_EXPIRE_SECS", "6000");
+    std::env::set_var("MFA_PENDING_EXPIRE_SECS", "300");
 }